@@ -0,0 +1,26 @@
+//! Checks that values in runtime error messages are shortened by the interpreter's render
+//! limits, the same as print. Run it with `cargo run --example error_render_limits`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	token::RenderLimits,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+fn main() {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	let mut interpreter = Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+		.render_limits(RenderLimits { max_elements: Some(3), max_depth: Some(16) })
+		.build();
+
+	let printed = interpreter.run_snippet("print range(0, 10);");
+	let result = interpreter.run_snippet("rng_next(range(0, 10));");
+	let message = result.diagnostics.join("\n");
+	let shown = printed.output.trim();
+	assert_eq!(shown, "[0, 1, 2, …, +7 more]");
+	assert!(message.contains(shown), "the error should show {} but was {}", shown, message);
+	println!("{}", message);
+}
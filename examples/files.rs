@@ -0,0 +1,66 @@
+//! Writes, appends to and reads back a file in a temporary directory with the file natives, and
+//! checks that a sandboxed interpreter can't use them. Run it with `cargo run --example files`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, InterpreterBuilder, Output},
+	token::LiteralType,
+};
+use std::{cell::RefCell, fs, io, process, rc::Rc};
+
+fn builder() -> InterpreterBuilder {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+}
+
+fn main() {
+	let dir = std::env::temp_dir().join(format!("jasn-files-{}", process::id()));
+	fs::create_dir_all(&dir).expect("the temporary directory can be made");
+	let path = dir.join("round_trip.txt");
+	let path_value = LiteralType::String(path.display().to_string());
+
+	let mut interpreter = builder().build();
+	interpreter.define_in_current("path", path_value.clone());
+	let result = interpreter.run_snippet(
+		"var before = file_exists(path);
+		write_file(path, \"first line\\n\");
+		append_file(path, \"second line\\n\");
+		[before, file_exists(path), read_file(path)];",
+	);
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	assert_eq!(result.value.to_string(), "[nah, yeah, first line\nsecond line\n]");
+	assert_eq!(fs::read_to_string(&path).unwrap(), "first line\nsecond line\n");
+
+	// Writing replaces what was there
+	let result = interpreter.run_snippet("write_file(path, \"only line\"); read_file(path);");
+	assert_eq!(result.value, LiteralType::String("only line".to_string()));
+
+	// Failures are errors carrying the reason, which try_call can catch
+	let missing = dir.join("missing").join("file.txt");
+	interpreter.define_in_current("missing", LiteralType::String(missing.display().to_string()));
+	let result = interpreter.run_snippet("[try_call(read_file, [missing]), file_exists(missing)];");
+	let message = result.value.to_string();
+	assert!(message.starts_with("[[nah, read_file couldn't read"), "{}", message);
+	assert!(message.ends_with("], nah]"), "{}", message);
+	let result = interpreter.run_snippet("try_call(write_file, [missing, \"x\"])[0];");
+	assert_eq!(result.value, LiteralType::Bool(false));
+
+	// None of them work in sandbox mode
+	let mut sandboxed = builder().sandbox(true).build();
+	sandboxed.define_in_current("path", path_value);
+	for call in ["read_file(path);", "write_file(path, \"x\");", "file_exists(path);"] {
+		let result = sandboxed.run_snippet(call);
+		assert!(
+			result.diagnostics.iter().any(|d| d.contains("disabled in sandbox mode")),
+			"{} ran in sandbox mode: {:?}",
+			call,
+			result.diagnostics
+		);
+	}
+	assert_eq!(fs::read_to_string(&path).unwrap(), "only line");
+
+	fs::remove_dir_all(&dir).expect("the temporary directory can be removed");
+	println!("The file natives round trip, and are disabled in sandbox mode");
+}
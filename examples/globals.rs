@@ -0,0 +1,37 @@
+//! Checks that globals() and Environment::entries agree on what the script defined, natives
+//! included. Run it with `cargo run --example globals`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	token::LiteralType,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+fn main() {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	let mut interpreter = Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+		.build();
+
+	let result =
+		interpreter.run_snippet("var a = 1; var b = \"two\"; funk f(x) { return x; } globals();");
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	let LiteralType::Array(names) = result.value else {
+		panic!("globals() gave {} rather than an array", result.value);
+	};
+	let names: Vec<String> = names.borrow().iter().map(|name| name.to_string()).collect();
+	for expected in ["a", "b", "f", "globals", "clock", "len", "sum"] {
+		assert!(names.iter().any(|name| name == expected), "globals() is missing {}", expected);
+	}
+
+	let global = interpreter.global.borrow();
+	let entries = global.entries();
+	let entry_names: Vec<String> = entries.iter().map(|(name, _)| name.clone()).collect();
+	assert_eq!(names, entry_names, "globals() and entries() should name the same things");
+	assert_eq!(names, global.names(), "globals() and names() should name the same things");
+	assert!(entries.contains(&("a".to_string(), LiteralType::Number(1.0))));
+	assert!(entries.contains(&("b".to_string(), LiteralType::String("two".to_string()))));
+	println!("globals() matches the {} global entries", entries.len());
+}
@@ -0,0 +1,59 @@
+//! Analyses examples/analysis.jasn and checks where the names in it are defined, including a
+//! reference that binds to the inner of two declarations with the same name.
+//! Run it with `cargo run --example go_to_definition`
+use jasn::{
+	analysis::{analyze_source, Analysis},
+	resolver::Definition,
+};
+use std::fs;
+
+const FIXTURE: &str = "examples/analysis.jasn";
+
+// The line and column of the nth use of name on a line, counting from 0
+fn position(source: &str, line: u32, name: &str, nth: usize) -> (u32, u32) {
+	let text = source.lines().nth(line as usize - 1).expect("the fixture has the line");
+	let column = text.match_indices(name).nth(nth).expect("the line has the name").0;
+	(line, column as u32 + 1)
+}
+
+// Where the reference at a position is defined
+fn definition(analysis: &Analysis, (line, column): (u32, u32)) -> Definition {
+	analysis
+		.references
+		.iter()
+		.find(|reference| reference.span.line == line && reference.span.column == column)
+		.unwrap_or_else(|| panic!("no reference at line {}, col {}", line, column))
+		.definition
+}
+
+// Whether a definition is declared at a position
+fn declared_at(definition: Definition, (line, column): (u32, u32)) -> bool {
+	matches!(definition, Definition::Declared(span) if span.line == line && span.column == column)
+}
+
+fn main() {
+	let source = fs::read_to_string(FIXTURE).expect("the fixture can be read");
+	let analysis = analyze_source(&source);
+	assert!(analysis.diagnostics.is_empty(), "{:?}", analysis.diagnostics);
+	let at = |line, name, nth| position(&source, line, name, nth);
+
+	// Inside inner, count is the local of outer that hides the global, step is outer's parameter
+	assert!(declared_at(definition(&analysis, at(7, "count", 0)), at(5, "count", 0)));
+	assert!(declared_at(definition(&analysis, at(7, "step", 0)), at(4, "step", 0)));
+	assert!(declared_at(definition(&analysis, at(5, "step", 0)), at(4, "step", 0)));
+	assert!(declared_at(definition(&analysis, at(9, "inner", 0)), at(6, "inner", 0)));
+	// this is defined by its class
+	assert!(declared_at(definition(&analysis, at(14, "this", 0)), at(12, "Counter", 0)));
+	// At the top level count is the global again
+	assert!(declared_at(definition(&analysis, at(18, "count", 0)), at(2, "count", 0)));
+	assert!(declared_at(definition(&analysis, at(18, "outer", 0)), at(4, "outer", 0)));
+	assert_eq!(definition(&analysis, at(18, "len", 0)), Definition::Native);
+	assert_eq!(definition(&analysis, at(18, "missing", 0)), Definition::Unresolved);
+
+	for reference in &analysis.references {
+		println!(
+			"{}:{} {} -> {:?}",
+			reference.span.line, reference.span.column, reference.name, reference.definition
+		);
+	}
+}
@@ -0,0 +1,148 @@
+//! Applies thousands of random edits to the example scripts with Scanner::rescan and checks
+//! that the tokens always match scanning the edited source from scratch, with and without
+//! trivia. Then times typing into a 10,000 line file both ways.
+//! Run it with `cargo run --release --example incremental_scan`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::Output,
+	scanner::{Edit, Scanner},
+	token::Token,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{cell::RefCell, fs, io, rc::Rc, time::Instant};
+
+const EDITS_PER_FILE: usize = 200;
+const TYPED: &str = "var typed = 1;\n";
+// Pieces of source that change how the text around them scans
+const PIECES: [&str; 20] = [
+	"", " ", "\n", "\"", "\\", "/*", "*/", "//", "x", "_9", "12", ".5", "e", "=", "==", "&",
+	"funk", "é", "\n\n  ", "\"a\nb\"",
+];
+
+fn error_handler() -> Rc<RefCell<ErrorHandler>> {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	Rc::new(RefCell::new(ErrorHandler::with_output(output)))
+}
+
+// Every scanner stamps its own source id on its tokens, everything else has to match
+fn same_tokens(a: &Scanner, b: &Scanner) -> bool {
+	let unstamped = |scanner: &Scanner| -> Vec<Token> {
+		scanner
+			.tokens
+			.iter()
+			.map(|token| Token { source: 0, ..token.clone() })
+			.collect()
+	};
+	unstamped(a) == unstamped(b)
+}
+
+// Scan source, giving back the error handler too so the errors can be compared
+fn scanner(source: &[u8], trivia: bool) -> (Scanner, Rc<RefCell<ErrorHandler>>) {
+	let error_handler = error_handler();
+	let source = source.to_vec();
+	let mut scanner = if trivia {
+		Scanner::with_trivia(source, Rc::clone(&error_handler))
+	} else {
+		Scanner::new(source, Rc::clone(&error_handler))
+	};
+	scanner.scan_tokens();
+	(scanner, error_handler)
+}
+
+// A random edit that starts and ends on whole characters
+fn random_edit(rng: &mut StdRng, source: &[u8]) -> Edit {
+	let boundary = |i: usize| source.get(i).map_or(true, |&c| c & 0xC0 != 0x80);
+	let mut start = rng.gen_range(0..=source.len());
+	while !boundary(start) {
+		start -= 1;
+	}
+	let mut end = (start + rng.gen_range(0..8)).min(source.len());
+	while !boundary(end) {
+		end += 1;
+	}
+	let new_text = PIECES[rng.gen_range(0..PIECES.len())].to_string();
+	Edit { start: start as u32, old_len: (end - start) as u32, new_text }
+}
+
+fn main() {
+	let mut rng = StdRng::seed_from_u64(289);
+	let mut files: Vec<_> = fs::read_dir("examples")
+		.expect("run it from the repository root")
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.extension().is_some_and(|extension| extension == "jasn"))
+		.collect();
+	files.sort();
+	let (mut edits, mut partial) = (0, 0);
+	for path in &files {
+		let source = fs::read(path).unwrap();
+		for trivia in [false, true] {
+			let (mut incremental, mut errors) = scanner(&source, trivia);
+			for _ in 0..EDITS_PER_FILE {
+				let edit = random_edit(&mut rng, incremental.source());
+				let scanned = incremental.rescan(&edit);
+				if scanned.len() + 1 < incremental.tokens.len() {
+					partial += 1;
+				}
+				let (fresh, fresh_errors) = scanner(incremental.source(), trivia);
+				assert!(
+					same_tokens(&incremental, &fresh),
+					"{} scanned differently after {:?}",
+					path.display(),
+					edit
+				);
+				assert_eq!(
+					errors.borrow().diagnostics,
+					fresh_errors.borrow().diagnostics,
+					"{} reported different errors after {:?}",
+					path.display(),
+					edit
+				);
+				edits += 1;
+				// Most edits that break the source break it for good, so start again from the
+				// original to keep testing edits to source that scans
+				if fresh_errors.borrow().had_error {
+					(incremental, errors) = scanner(&source, trivia);
+				}
+			}
+		}
+	}
+	// Edits that can't be rescanned in part are checked too, but they aren't the point
+	assert!(partial > edits / 2, "only {} of the {} edits were rescanned in part", partial, edits);
+	println!(
+		"{} random edits to {} files all matched a scan from scratch, {} rescanned in part",
+		edits,
+		files.len(),
+		partial
+	);
+
+	// Type a line into the middle of a big file one character at a time
+	let fixture = fs::read("examples/classes.jasn").unwrap();
+	let mut source = Vec::new();
+	while source.iter().filter(|&&c| c == b'\n').count() < 10_000 {
+		source.extend_from_slice(&fixture);
+	}
+	let middle = source.len() / 2;
+	let middle = middle + source[middle..].iter().position(|&c| c == b'\n').unwrap() + 1;
+
+	let (mut incremental, _) = scanner(&source, false);
+	let started = Instant::now();
+	for (i, c) in TYPED.char_indices() {
+		let edit = Edit { start: (middle + i) as u32, old_len: 0, new_text: c.to_string() };
+		incremental.rescan(&edit);
+	}
+	let rescanning = started.elapsed();
+
+	let mut typed = source.clone();
+	let (mut fresh, _) = scanner(&typed, false);
+	let started = Instant::now();
+	for (i, c) in TYPED.char_indices() {
+		typed.splice(middle + i..middle + i, c.to_string().bytes());
+		fresh = scanner(&typed, false).0;
+	}
+	let from_scratch = started.elapsed();
+
+	assert!(same_tokens(&incremental, &fresh));
+	println!("Typing {} characters into a file of {} tokens", TYPED.len(), fresh.tokens.len());
+	println!("Scanning from scratch: {:?}", from_scratch);
+	println!("Rescanning the edits:  {:?}", rescanning);
+}
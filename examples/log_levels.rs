@@ -0,0 +1,36 @@
+//! Checks that the log natives write where errors go and hide messages below the log level,
+//! both the one set up front and the one set_log_level changes to. Run it with
+//! `cargo run --example log_levels`
+use jasn::{interpreter::Output, native_functions::LogLevel, Options, Session};
+use std::{cell::RefCell, io, rc::Rc};
+
+fn main() {
+	let options = Options { log_level: Some(LogLevel::Warn), ..Default::default() };
+	let output = Rc::new(RefCell::new(Vec::new()));
+	let mut session = Session::with_io(
+		&options,
+		Rc::new(RefCell::new(io::empty())),
+		Rc::clone(&output) as Output,
+	);
+	session.interpreter.time_source = || 1.5;
+	session
+		.run_source(
+			"log_debug(\"hidden debug\");
+			log_info(\"hidden info\");
+			log_warn(\"shown warning\");
+			log_error([1, 2]);
+			set_log_level(\"debug\");
+			log_debug(\"shown debug\");",
+		)
+		.expect("the script runs");
+	let printed = String::from_utf8(output.borrow().clone()).unwrap();
+
+	let lines: Vec<&str> = printed.lines().collect();
+	assert_eq!(
+		lines,
+		["[WARN 1.500] shown warning", "[ERROR 1.500] [1, 2]", "[DEBUG 1.500] shown debug"],
+		"{}",
+		printed
+	);
+	println!("Log levels filter messages:\n{}", printed);
+}
@@ -0,0 +1,34 @@
+//! Parses if expressions nested in each other's conditions, which used to take twice as long
+//! for every level, and checks the result still runs. Run it with
+//! `cargo run --example nested_conditions`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	token::LiteralType,
+};
+use std::{cell::RefCell, io, rc::Rc, time::Instant};
+
+const LEVELS: usize = 30;
+
+fn main() {
+	let mut condition = "yeah".to_string();
+	for _ in 0..LEVELS {
+		condition = format!("if ({}) == yeah {{ yeah }} else {{ nah }}", condition);
+	}
+	let source =
+		format!("var result = nah; if ({}) == yeah {{ result = yeah; }} result;", condition);
+
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	let mut interpreter = Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+		.build();
+	let started = Instant::now();
+	let result = interpreter.run_snippet(&source);
+	let elapsed = started.elapsed();
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	assert_eq!(result.value, LiteralType::Bool(true));
+	assert!(elapsed.as_secs() < 5, "{} levels took {:?}", LEVELS, elapsed);
+	println!("{} nested conditions parsed and ran in {:?}", LEVELS, elapsed);
+}
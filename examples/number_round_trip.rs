@@ -0,0 +1,59 @@
+//! Checks that every number printed by to_string reads back with to_number as exactly the same
+//! number, bit for bit, for a table of values that are awkward to write out.
+//! Run it with `cargo run --example number_round_trip`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	token::LiteralType,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+const AWKWARD: [f64; 20] = [
+	0.0,
+	-0.0,
+	0.1 + 0.2,
+	1.0 / 3.0,
+	-2.5,
+	100.0,
+	123456789012345680000.0,
+	1e21,
+	1e-6,
+	// The number just below 1e-6
+	9.999999999999997e-7,
+	5e-324,
+	2.2250738585072014e-308,
+	f64::MIN_POSITIVE,
+	f64::MAX,
+	f64::MIN,
+	f64::EPSILON,
+	9007199254740993.0,
+	f64::INFINITY,
+	f64::NEG_INFINITY,
+	f64::NAN,
+];
+
+fn main() {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	let mut interpreter = Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+		.build();
+
+	for x in AWKWARD {
+		interpreter.define_in_current("x", LiteralType::Number(x));
+		let printed = interpreter.run_snippet("print x;");
+		let result = interpreter.run_snippet("to_number(to_string(x));");
+		assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+		let LiteralType::Number(back) = result.value else {
+			panic!("{} didn't read back as a number but as {}", x, result.value);
+		};
+		if x.is_nan() {
+			assert!(back.is_nan(), "NaN read back as {}", back);
+		} else {
+			assert_eq!(x.to_bits(), back.to_bits(), "{} read back as {}", x, back);
+		}
+		print!("{}", printed.output);
+	}
+	println!("All {} numbers read back exactly", AWKWARD.len());
+}
@@ -0,0 +1,29 @@
+//! Runs examples/process.jasn through a Session with arguments passed in, checking what args()
+//! gives the script and that exit() becomes the exit code. Run it with
+//! `cargo run --example process_args`
+use jasn::{interpreter::Output, Options, Session};
+use std::{cell::RefCell, io, path::Path, rc::Rc};
+
+fn main() {
+	let options =
+		Options { args: vec!["one".to_string(), "--two".to_string()], ..Default::default() };
+	let output = Rc::new(RefCell::new(Vec::new()));
+	let mut session = Session::with_io(
+		&options,
+		Rc::new(RefCell::new(io::empty())),
+		Rc::clone(&output) as Output,
+	);
+	let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/process.jasn");
+	let code = session.run_path(&script).expect("the script runs");
+	let printed = String::from_utf8(output.borrow().clone()).unwrap();
+
+	assert_eq!(code, 3, "exit(3) should set the exit code");
+	assert_eq!(session.interpreter.exit_code(), Some(3));
+	let lines: Vec<&str> = printed.lines().collect();
+	assert_eq!(lines[0], "[one, --two]");
+	assert_eq!(lines[1], "2");
+	assert_eq!(lines[2], "null");
+	assert!(lines.contains(&"leaving"));
+	assert!(!printed.contains("never printed"), "{}", printed);
+	println!("args() and exit() work:\n{}", printed);
+}
@@ -0,0 +1,31 @@
+//! Drives a REPL session line by line through Session with in-memory IO: defines a function,
+//! calls it, makes a mistake and carries on. Run it with `cargo run --example repl_session`
+use jasn::{interpreter::Output, Options, Session};
+use std::{cell::RefCell, io, rc::Rc};
+
+fn main() {
+	let output = Rc::new(RefCell::new(Vec::new()));
+	let mut session = Session::with_io(
+		&Options::default(),
+		Rc::new(RefCell::new(io::empty())),
+		Rc::clone(&output) as Output,
+	);
+	let lines = [
+		"funk double(n) { return n * 2; }",
+		"print double(21);",
+		"print missing;",
+		"var total = double(2);",
+		"total + 1",
+	];
+	for line in lines {
+		session.run_line(line).expect("the line runs");
+	}
+	let printed = String::from_utf8(output.borrow().clone()).unwrap();
+
+	let answer = printed.find("42").expect("double(21) prints 42");
+	let error = printed.find("missing").expect("the undefined variable is reported");
+	let echoed = printed.rfind('5').expect("the trailing expression is echoed");
+	assert!(answer < error && error < echoed, "{}", printed);
+	assert!(!session.error_handler.borrow().had_error, "the error is cleared by the next line");
+	println!("The session recovered after an error:\n{}", printed);
+}
@@ -0,0 +1,52 @@
+//! Runs scripts whose tokens sit at the same line and offset as tokens in the prelude or in a
+//! module they import, checking each source keeps its own resolved locals. Run it with
+//! `cargo run --example separate_sources`
+use jasn::{interpreter::Output, program::PRELUDE_SOURCE, Options, Session};
+use std::{cell::RefCell, env, fs, io, rc::Rc};
+
+fn main() {
+	let dir = env::temp_dir().join(format!("jasn_separate_sources_{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+
+	// The prelude starts with clamp, whose body uses its parameter x as a local. Line up a use
+	// of the global x inside a block with it
+	let prelude: Vec<&str> = PRELUDE_SOURCE.lines().collect();
+	let column = prelude[5].find("(x").expect("clamp uses x") + 1;
+	let mut lines: Vec<String> = prelude[..3].iter().map(|line| line.to_string()).collect();
+	lines.push(format!("{:width$}", "var x = 3;", width = prelude[3].len()));
+	lines.push(" ".repeat(prelude[4].len()));
+	lines.push(format!("{{ print {:width$}x; }}", "", width = column - 8));
+	let script = lines.join("\n") + "\n";
+	let line_start: usize = prelude[..5].iter().map(|line| line.len() + 1).sum();
+	assert_eq!(script.find("x;"), Some(line_start + column));
+	assert_eq!(run(&dir, "prelude.jasn", &script, &[]), "3\n");
+
+	// The module's q is a local one block out, the script's q after the import is a global
+	let main = "var q = 7; import \"module.jasn\"; print q;";
+	let offset = main.rfind('q').unwrap();
+	let module = format!("{{ var q = 1; {{ {:width$}q; }} }}", "", width = offset - 15);
+	assert_eq!(module.rfind('q'), Some(offset));
+	let printed = run(&dir, "main.jasn", main, &[("module.jasn", &module)]);
+	assert_eq!(printed, "7\n");
+
+	fs::remove_dir_all(&dir).unwrap();
+	println!("The prelude, modules and the script each resolve their own locals");
+}
+
+// Write the script and any modules into dir and run the script, returning what it printed
+fn run(dir: &std::path::Path, name: &str, script: &str, modules: &[(&str, &str)]) -> String {
+	for (module, source) in modules {
+		fs::write(dir.join(module), source).unwrap();
+	}
+	let path = dir.join(name);
+	fs::write(&path, script).unwrap();
+	let output = Rc::new(RefCell::new(Vec::new()));
+	let mut session = Session::with_io(
+		&Options::default(),
+		Rc::new(RefCell::new(io::empty())),
+		Rc::clone(&output) as Output,
+	);
+	session.run_path(&path).expect("the script runs");
+	let printed = String::from_utf8(output.borrow().clone()).unwrap();
+	printed
+}
@@ -0,0 +1,64 @@
+//! Loads damaged and hostile state files, checking each one is refused with an error instead of
+//! panicking or running out of memory, and that deeply nested values save and load up to the
+//! limit. Run it with `cargo run --example state_file`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	state::{State, STATE_VERSION},
+	token::LiteralType,
+};
+use std::{cell::RefCell, env, fs, io, rc::Rc};
+
+fn interpreter() -> Interpreter {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+		.build()
+}
+
+fn main() {
+	let path = env::temp_dir().join(format!("jasn_state_file_{}.state", std::process::id()));
+	let header = format!("JASN-STATE {}\n", STATE_VERSION);
+
+	let hostile = [
+		format!("x s:{}:\n", usize::MAX),
+		format!("x a:{}:\n", usize::MAX),
+		"x a:99999999999999999:\n".to_string(),
+		"x m:99999999999999999:\n".to_string(),
+		format!("x m:1:{}:k\n", usize::MAX),
+		format!("x {}null\n", "a:1:".repeat(100_000)),
+		"x a:2:null\n".to_string(),
+		"x s:-1:\n".to_string(),
+	];
+	for body in &hostile {
+		fs::write(&path, format!("{}{}", header, body)).unwrap();
+		let result = State::load(&mut interpreter(), &path);
+		assert!(result.is_err(), "{} was loaded", &body[..body.len().min(40)]);
+	}
+
+	// A value nested as deeply as a state file allows round trips, one deeper is left out
+	let mut interpreter = interpreter();
+	let mut value = LiteralType::Bool(true);
+	for _ in 0..512 {
+		value = LiteralType::array(vec![value]);
+	}
+	interpreter.define_in_current("deep", value.clone());
+	interpreter.define_in_current("too_deep", LiteralType::array(vec![value]));
+	let skipped = State::save(&interpreter, &path).unwrap();
+	assert_eq!(skipped, ["too_deep"]);
+	let mut loaded = self::interpreter();
+	State::load(&mut loaded, &path).unwrap();
+	let deep = loaded.lookup("deep").expect("deep was saved");
+	let mut depth = 0;
+	let mut inner = deep;
+	while let LiteralType::Array(values) = inner {
+		inner = values.borrow()[0].clone();
+		depth += 1;
+	}
+	assert_eq!((depth, inner), (512, LiteralType::Bool(true)));
+
+	fs::remove_file(&path).unwrap();
+	println!("{} hostile state files were refused", hostile.len());
+}
@@ -0,0 +1,45 @@
+//! Builds 1,000 interpreters with the prelude, first compiling it for each one and then reusing
+//! the copy every interpreter shares, and checks that both ways behave the same.
+//! Run it with `cargo run --release --example warm_start`
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, InterpreterBuilder, Output},
+	program::{compile_source, PRELUDE_SOURCE},
+};
+use std::{cell::RefCell, io, rc::Rc, time::Instant};
+
+const INTERPRETERS: usize = 1000;
+const CHECK: &str = "print clamp(15, 0, 10); sum([1, 2, 3]) + product([2, 3, 4]);";
+
+fn builder() -> InterpreterBuilder {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+}
+
+fn main() {
+	let start = Instant::now();
+	let mut compiled = Vec::new();
+	for _ in 0..INTERPRETERS {
+		let mut interpreter = builder().prelude(false).build();
+		let prelude = compile_source(PRELUDE_SOURCE).expect("the prelude compiles");
+		interpreter.load_compiled(&prelude).expect("the prelude runs");
+		compiled.push(interpreter.run_snippet(CHECK));
+	}
+	let cold = start.elapsed();
+
+	let start = Instant::now();
+	let mut shared = Vec::new();
+	for _ in 0..INTERPRETERS {
+		shared.push(builder().build().run_snippet(CHECK));
+	}
+	let warm = start.elapsed();
+
+	assert!(compiled.iter().chain(&shared).all(|result| result.diagnostics.is_empty()));
+	assert_eq!(compiled, shared, "both ways of loading the prelude should behave the same");
+	println!("{} interpreters, {:?}", INTERPRETERS, shared[0]);
+	println!("Compiling the prelude each time: {:?}", cold);
+	println!("Sharing the compiled prelude:    {:?}", warm);
+}
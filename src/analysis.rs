@@ -0,0 +1,73 @@
+use crate::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	parser::Parser,
+	resolver::{Definition, Resolver},
+	scanner::Scanner,
+	token::Span,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+/// A variable name used in the source, along with what it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+	pub name: String,
+	pub span: Span,
+	pub definition: Definition,
+}
+
+/// What every name used in some source refers to, worked out by the resolver without running
+/// anything. This is what an editor needs for "go to definition"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+	/// Every reference in the order the resolver reached them, which isn't always source order
+	pub references: Vec<Reference>,
+	/// Errors and warnings found on the way. Syntax errors leave nothing to analyse, resolver
+	/// errors leave out the references in the statement at fault
+	pub diagnostics: Vec<String>,
+}
+
+impl Analysis {
+	/// The reference covering a byte offset into the source
+	pub fn reference_at(&self, offset: u32) -> Option<&Reference> {
+		self.references.iter().find(|reference| {
+			(reference.span.offset..reference.span.offset + reference.span.length).contains(&offset)
+		})
+	}
+}
+
+/// Scan, parse and resolve source to find what each name in it refers to. Natives and the
+/// prelude's functions are known, anything else has to be declared in the source
+pub fn analyze_source(source: &str) -> Analysis {
+	let sink: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = Rc::new(RefCell::new(ErrorHandler::with_output(Rc::clone(&sink))));
+	let mut analysis = Analysis::default();
+
+	let mut scanner = Scanner::new(source.as_bytes().to_vec(), Rc::clone(&error_handler));
+	scanner.scan_tokens();
+	// Nothing runs, but the default input would lock standard input away from the caller
+	let mut interpreter = Interpreter::builder(Rc::clone(&error_handler))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(sink)
+		.build();
+	if !error_handler.borrow().had_syntax_error {
+		let statements = Parser::new(scanner.tokens, Rc::clone(&error_handler))
+			.max_nesting(interpreter.max_nesting)
+			.parse();
+		if !error_handler.borrow().had_syntax_error {
+			let mut resolver = Resolver::new(&mut interpreter, Rc::clone(&error_handler));
+			resolver.resolve_block(&statements);
+			analysis.references = resolver
+				.bindings
+				.iter()
+				.map(|binding| Reference {
+					name: binding.name.lexeme.clone(),
+					span: binding.name.span(),
+					definition: binding.definition,
+				})
+				.collect();
+		}
+	}
+	analysis.diagnostics = std::mem::take(&mut error_handler.borrow_mut().diagnostics);
+	analysis
+}
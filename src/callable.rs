@@ -3,10 +3,9 @@ use crate::{
 	error_handler::Error,
 	interpreter::Interpreter,
 	statements,
-	statements::Stmt,
-	token::{LiteralType, Token},
+	token::{LiteralType, Span, Token},
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 /// FunctionType is an enum that represents the type of function that is being resolved
 #[derive(Copy, Clone, PartialEq)]
@@ -14,10 +13,18 @@ pub enum FunctionType {
 	None,
 	Function,
 	Method,
-	// Initializer,
+	Initializer,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// ClassType tracks whether the code being resolved is inside a class declaration
+#[derive(Copy, Clone, PartialEq)]
+pub enum ClassType {
+	None,
+	Class,
+	Subclass,
+}
+
+#[derive(Debug, Clone)]
 pub enum Callable {
 	NativeFunction(NativeFunction),
 	Function(JasnFunction),
@@ -25,33 +32,111 @@ pub enum Callable {
 	Instance(JasnInstanceRef),
 }
 
+/// The max_arity of a native that takes any number of arguments. The parser stops a call from
+/// passing more than this, so nothing past it needs checking
+pub const VARIADIC: u8 = u8::MAX;
+
+/// The signature of the Rust function behind a native function
+pub type NativeFn = fn(&mut Interpreter, Vec<LiteralType>) -> Result<LiteralType, Error>;
+
 // Native functions are functions that are implemented in Rust and are callable from JASN
 #[derive(Debug, PartialEq, Clone)]
 pub struct NativeFunction {
+	pub name: String,
+	// The fewest arguments it takes, any past that up to max_arity are optional. A max_arity of
+	// VARIADIC takes as many as a call can pass
 	pub arity: u8,
-	pub function: fn(&mut Interpreter, Vec<LiteralType>) -> Result<LiteralType, Error>,
+	pub max_arity: u8,
+	pub function: NativeFn,
+	pub docs: NativeDocs,
+	// Sandboxed natives raise an error instead of running
+	pub sandboxed: bool,
+}
+
+/// What help() says about a native. It is written where the native is defined so the two can't
+/// drift apart
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NativeDocs {
+	// The group help() lists the native under
+	pub category: &'static str,
+	// How to call it, like `fill(n, value)`
+	pub signature: &'static str,
+	pub description: &'static str,
+}
+
+impl NativeDocs {
+	pub const fn new(
+		category: &'static str,
+		signature: &'static str,
+		description: &'static str,
+	) -> Self {
+		Self { category, signature, description }
+	}
 }
 
 // Functions are user-defined functions that are defined in JASN
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct JasnFunction {
 	pub declaration: Box<statements::Function>,
 	pub closure: Rc<RefCell<Environment>>,
+	// The name of the class this function is a method of
+	pub class_name: Option<String>,
+	// An init method, which always gives back its instance
+	pub is_initializer: bool,
+}
+
+// Two functions are the same when they are the same declaration closed over the same environment.
+// Comparing environments by value could go round forever, as they often hold the function
+impl PartialEq for JasnFunction {
+	fn eq(&self, other: &Self) -> bool {
+		self.declaration == other.declaration &&
+			Rc::ptr_eq(&self.closure, &other.closure) &&
+			self.class_name == other.class_name &&
+			self.is_initializer == other.is_initializer
+	}
+}
+
+impl JasnFunction {
+	/// Make a copy of this method where `this` refers to the given instance
+	pub fn bind(&self, instance: JasnInstanceRef) -> JasnFunction {
+		let environment = Environment::new(Some(self.closure.clone()));
+		environment
+			.borrow_mut()
+			.define("this".to_string(), LiteralType::Callable(Callable::Instance(instance)));
+		JasnFunction { closure: environment, ..self.clone() }
+	}
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct JasnClass {
 	pub name: String,
+	pub superclass: Option<Box<JasnClass>>,
 	pub methods: HashMap<String, Callable>,
 }
 
 impl JasnClass {
-	pub fn new(name: String, methods: HashMap<String, Callable>) -> Self {
-		Self { name, methods }
+	pub fn new(
+		name: String,
+		superclass: Option<JasnClass>,
+		methods: HashMap<String, Callable>,
+	) -> Self {
+		Self { name, superclass: superclass.map(Box::new), methods }
 	}
 
+	/// Whether this class is called name or inherits from a class that is
+	pub fn is_or_inherits(&self, name: &str) -> bool {
+		self.name == name ||
+			self.superclass
+				.as_ref()
+				.is_some_and(|superclass| superclass.is_or_inherits(name))
+	}
+
+	/// Find a method on this class, falling back to the superclass chain
 	pub fn find_method(&self, name: &str) -> Option<Callable> {
-		self.methods.get(name).cloned()
+		match self.methods.get(name) {
+			Some(method) => Some(method.clone()),
+			None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+		}
 	}
 }
 
@@ -68,16 +153,24 @@ impl JasnInstance {
 		JasnInstanceRef::new(RefCell::new(Self { class, fields: Default::default() }))
 	}
 
-	pub fn get(&self, name: &Token) -> Result<LiteralType, Error> {
-		if let Some(value) = self.fields.get(&name.lexeme) {
-			Ok(value.clone())
-		} else if let Some(method) = self.class.find_method(&name.lexeme) {
-			Ok(LiteralType::Callable(method.clone()))
+	/// Look up a field, or failing that a method bound to this instance
+	pub fn get(instance: &JasnInstanceRef, name: &Token) -> Result<LiteralType, Error> {
+		Self::lookup(instance, &name.lexeme).ok_or_else(|| {
+			Error::RuntimeError(name.span(), format!("Undefined property '{}'", name.lexeme))
+		})
+	}
+
+	/// Look up a field, or a method bound to the instance, returning None if neither exists
+	pub fn lookup(instance: &JasnInstanceRef, name: &str) -> Option<LiteralType> {
+		let this = instance.borrow();
+		if let Some(value) = this.fields.get(name) {
+			Some(value.clone())
 		} else {
-			Err(Error::RuntimeError(
-				name.get_line(),
-				format!("Undefined property '{}'", name.lexeme),
-			))
+			this.class.find_method(name).map(|method| match method {
+				Callable::Function(function) =>
+					LiteralType::Callable(Callable::Function(function.bind(instance.clone()))),
+				_ => LiteralType::Callable(method),
+			})
 		}
 	}
 
@@ -86,6 +179,19 @@ impl JasnInstance {
 	}
 }
 
+impl PartialEq for Callable {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Callable::NativeFunction(a), Callable::NativeFunction(b)) => a == b,
+			(Callable::Function(a), Callable::Function(b)) => a == b,
+			(Callable::Class(a), Callable::Class(b)) => a == b,
+			// An instance is only equal to itself, its fields can refer back to it
+			(Callable::Instance(a), Callable::Instance(b)) => Rc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+}
+
 impl Callable {
 	pub fn call(
 		&self,
@@ -93,35 +199,50 @@ impl Callable {
 		arguments: Vec<LiteralType>,
 	) -> Result<LiteralType, Error> {
 		match self {
-			Callable::NativeFunction(native_function) =>
-				(native_function.function)(interpreter, arguments),
+			Callable::NativeFunction(native_function) => {
+				interpreter.record_native_call();
+				if native_function.sandboxed {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!("native '{}' is disabled in sandbox mode", native_function.name),
+					));
+				}
+				(native_function.function)(interpreter, arguments)
+			},
 			Callable::Function(function) => {
 				// Create a new environment whenever the function is called and pass the arguments
-				// into that environment
-				let mut environment = function.closure.clone();
+				// into that environment. It encloses the closure, which for a bound method is the
+				// environment holding "this"
+				let environment = Environment::new(Some(function.closure.clone()));
 				for (i, argument) in arguments.iter().enumerate() {
 					environment.borrow_mut().define(
 						function.declaration.params[i].lexeme.to_string(),
 						argument.clone(),
 					);
 				}
-				match interpreter.execute_block(&function.declaration.body, environment) {
+				interpreter.enter_call()?;
+				let result = interpreter.execute_block(&function.declaration.body, environment);
+				interpreter.exit_call();
+				match result {
+					Ok(_) | Err(Error::Return(_)) if function.is_initializer =>
+						function.closure.borrow().get_at(0, "this"),
 					Ok(_) => Ok(LiteralType::Null),
-					Err(error) =>
-						if let Error::Return(value) = error {
-							Ok(value)
-						} else {
-							Err(error)
-						},
+					Err(Error::Return(value)) => Ok(value),
+					Err(error) => Err(error),
 				}
 			},
 			Callable::Class(class) => {
 				let instance = JasnInstance::new(class.clone());
-				Ok(LiteralType::Callable(Callable::Instance(instance.clone())))
-			},
-			Callable::Instance(instance) => {
-				todo!()
+				if let Some(Callable::Function(initializer)) = class.find_method("init") {
+					Callable::Function(initializer.bind(instance.clone()))
+						.call(interpreter, arguments)?;
+				}
+				Ok(LiteralType::Callable(Callable::Instance(instance)))
 			},
+			Callable::Instance(instance) => Err(Error::RuntimeError(
+				Span::default(),
+				format!("Can't call an instance of {}.", instance.borrow().class.name),
+			)),
 		}
 	}
 
@@ -129,20 +250,77 @@ impl Callable {
 		match self {
 			Callable::NativeFunction(native_function) => native_function.arity,
 			Callable::Function(function) => function.declaration.params.len() as u8,
-			Callable::Class(_) => 0,
+			Callable::Class(class) => class.find_method("init").map_or(0, |init| init.arity()),
 			Callable::Instance(_) => 0,
 		}
 	}
+
+	/// The most arguments it takes, the same as arity unless a native has optional arguments
+	pub fn max_arity(&self) -> u8 {
+		match self {
+			Callable::NativeFunction(native_function) => native_function.max_arity,
+			_ => self.arity(),
+		}
+	}
+
+	/// Whether it can be called with count arguments
+	pub fn accepts(&self, count: usize) -> bool {
+		(self.arity() as usize..=self.max_arity() as usize).contains(&count)
+	}
+
+	/// How many arguments it takes, like "2", "at least 1" or "1 to 3"
+	pub fn expected_arguments(&self) -> String {
+		let (arity, max_arity) = (self.arity(), self.max_arity());
+		if arity == max_arity {
+			arity.to_string()
+		} else if max_arity == VARIADIC {
+			format!("at least {}", arity)
+		} else {
+			format!("{} to {}", arity, max_arity)
+		}
+	}
+
+	/// Check that it can be called with count arguments
+	pub fn check_arity(&self, count: usize) -> Result<(), String> {
+		if self.accepts(count) {
+			return Ok(());
+		}
+		Err(format!("Expected {} arguments but found {}.", self.expected_arguments(), count))
+	}
 }
 
-impl ToString for Callable {
-	fn to_string(&self) -> String {
+impl fmt::Display for Callable {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
-			Callable::NativeFunction(native_function) => "<fn native>".to_string(),
-			Callable::Function(function) => format!("<fn {:?}>", function.declaration.name.lexeme),
-			Callable::Class(class) => format!("<class {:?}>", class.name),
+			Callable::NativeFunction(native) =>
+				if native.arity == native.max_arity {
+					write!(f, "<native fn {}/{}>", native.name, native.arity)
+				} else if native.max_arity == VARIADIC {
+					write!(f, "<native fn {}/{}+>", native.name, native.arity)
+				} else {
+					write!(f, "<native fn {}/{}-{}>", native.name, native.arity, native.max_arity)
+				},
+			Callable::Function(function) => {
+				write!(f, "<{}fn ", if function.declaration.pure { "pure " } else { "" })?;
+				if let Some(class_name) = &function.class_name {
+					write!(f, "{}.", class_name)?;
+				}
+				let params: Vec<&str> =
+					function.declaration.params.iter().map(|param| param.lexeme.as_str()).collect();
+				write!(f, "{}({})>", function.declaration.name.lexeme, params.join(", "))
+			},
+			Callable::Class(class) => {
+				write!(f, "<class {}", class.name)?;
+				if let Some(superclass) = &class.superclass {
+					write!(f, " < {}", superclass.name)?;
+				}
+				match class.methods.len() {
+					1 => write!(f, " with 1 method>"),
+					count => write!(f, " with {} methods>", count),
+				}
+			},
 			Callable::Instance(instance) =>
-				format!("<{:?} instance>", instance.borrow().class.name),
+				write!(f, "<{} instance>", instance.borrow().class.name),
 		}
 	}
 }
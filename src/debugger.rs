@@ -0,0 +1,144 @@
+use crate::{
+	environment::EnvRef,
+	error_handler::Error,
+	interpreter::Interpreter,
+	statements::Stmt,
+	token::{LiteralType, Span},
+};
+use std::{
+	collections::{BTreeSet, HashSet},
+	fs, io,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+
+/// Breakpoints set on lines of source files. A script pauses into the debug prompt when the next
+/// statement starts on one of them, or when it calls breakpoint()
+#[derive(Debug, Default)]
+pub struct Debugger {
+	// Canonical file paths and the lines in them to pause at
+	breakpoints: BTreeSet<(PathBuf, u32)>,
+	// Pause at the very next statement, set by the step command
+	stepping: bool,
+	// The line of the last statement checked, so a line only pauses once each time it is reached
+	last_line: Option<u32>,
+}
+
+impl Debugger {
+	/// Pause whenever a statement starting on line of file is about to run
+	pub fn add_breakpoint(&mut self, file: &Path, line: u32) -> io::Result<()> {
+		self.breakpoints.insert((fs::canonicalize(file)?, line));
+		Ok(())
+	}
+
+	/// Every breakpoint, sorted by file and then line
+	pub fn breakpoints(&self) -> impl Iterator<Item = &(PathBuf, u32)> {
+		self.breakpoints.iter()
+	}
+
+	pub fn clear_breakpoints(&mut self) {
+		self.breakpoints.clear();
+	}
+
+	/// Whether statements need checking at all, so scripts without breakpoints run at full speed
+	pub(crate) fn is_active(&self) -> bool {
+		self.stepping || !self.breakpoints.is_empty()
+	}
+
+	/// Check whether a statement starting on line of file should pause first
+	pub(crate) fn should_pause(&mut self, file: Option<&Path>, line: u32) -> bool {
+		let new_line = self.last_line != Some(line);
+		self.last_line = Some(line);
+		if std::mem::take(&mut self.stepping) {
+			return true;
+		}
+		new_line && file.is_some_and(|file| self.breakpoints.contains(&(file.to_path_buf(), line)))
+	}
+
+	/// The breakpoint lines in file that no statement starts on, these can never be hit
+	pub(crate) fn unused_lines(&self, file: &Path, statements: &[Stmt]) -> Vec<u32> {
+		let mut lines = HashSet::new();
+		for statement in statements {
+			statement.walk(&mut |stmt| {
+				lines.extend(stmt.line());
+			});
+		}
+		self.breakpoints
+			.iter()
+			.filter(|(path, line)| path == file && !lines.contains(line))
+			.map(|(_, line)| *line)
+			.collect()
+	}
+}
+
+/// Pause the script and read debug commands until told to carry on. Reaching the end of the
+/// input carries on as well
+pub(crate) fn prompt(interpreter: &mut Interpreter, line: Option<u32>) -> Result<(), Error> {
+	let error_line = line.unwrap_or(0);
+	let io_error = |e: io::Error| Error::RuntimeError(Span::line(error_line), e.to_string());
+	let output = Rc::clone(&interpreter.output);
+	let mut output = output.borrow_mut();
+	match line {
+		Some(line) => writeln!(output, "Paused at line {}.", line),
+		None => writeln!(output, "Paused at breakpoint()."),
+	}
+	.map_err(io_error)?;
+	writeln!(
+		output,
+		"Type a variable name to see its value, 'locals' for everything in scope, 'step' to run one statement or 'continue'."
+	)
+	.map_err(io_error)?;
+	loop {
+		write!(output, "debug> ").map_err(io_error)?;
+		output.flush().map_err(io_error)?;
+		let mut command = String::new();
+		if interpreter.input.borrow_mut().read_line(&mut command).map_err(io_error)? == 0 {
+			writeln!(output).map_err(io_error)?;
+			return Ok(());
+		}
+		match command.trim() {
+			"" | "c" | "continue" => return Ok(()),
+			"s" | "step" => {
+				interpreter.debugger.stepping = true;
+				return Ok(());
+			},
+			"locals" => {
+				let locals = locals(&interpreter.current_env(), &interpreter.global);
+				if locals.is_empty() {
+					writeln!(output, "There are no local variables here.").map_err(io_error)?;
+				}
+				for (name, value) in locals {
+					let value = value.render(&interpreter.render_limits);
+					writeln!(output, "{} = {}", name, value).map_err(io_error)?;
+				}
+			},
+			name => match interpreter.lookup(name) {
+				Some(value) => {
+					let value = value.render(&interpreter.render_limits);
+					writeln!(output, "{} = {}", name, value).map_err(io_error)?;
+				},
+				None => writeln!(output, "There's no variable called '{}' here.", name)
+					.map_err(io_error)?,
+			},
+		}
+	}
+}
+
+/// Every variable between environment and the globals, innermost first. A name hidden by an
+/// inner variable is left out
+fn locals(environment: &EnvRef, global: &EnvRef) -> Vec<(String, LiteralType)> {
+	let mut locals: Vec<(String, LiteralType)> = Vec::new();
+	let mut environment = Rc::clone(environment);
+	while !Rc::ptr_eq(&environment, global) {
+		for (name, value) in environment.borrow().entries() {
+			if !locals.iter().any(|(local, _)| *local == name) {
+				locals.push((name, value));
+			}
+		}
+		let Some(enclosing) = environment.borrow().enclosing() else {
+			break;
+		};
+		environment = enclosing;
+	}
+	locals
+}
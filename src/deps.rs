@@ -0,0 +1,93 @@
+use crate::{error_handler::ErrorHandler, parser::Parser, scanner::Scanner, statements::Stmt};
+use std::{
+	cell::RefCell,
+	fs, io,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+
+/// Every file a script imports, directly or through the files it imports, found by parsing
+/// them without running anything. This is what `jasn --emit-deps` prints
+#[derive(Debug, Default)]
+pub struct Dependencies {
+	/// Canonical paths in the order they are first imported, each one only once
+	pub files: Vec<PathBuf>,
+	/// Missing files, import cycles and files that don't parse, each with the file and line of
+	/// the import at fault like `main.jasn:3: ...`
+	pub diagnostics: Vec<String>,
+}
+
+impl Dependencies {
+	/// Find the dependencies of script. Syntax errors in any of the files are reported to
+	/// wherever error_handler reports to
+	pub fn find(script: &Path, error_handler: &ErrorHandler) -> io::Result<Self> {
+		let script = fs::canonicalize(script)?;
+		let mut dependencies = Self::default();
+		dependencies.visit(&script, &mut vec![script.clone()], error_handler);
+		Ok(dependencies)
+	}
+
+	// Follow the imports of file, chain is the files imported on the way to it, file included
+	fn visit(&mut self, file: &Path, chain: &mut Vec<PathBuf>, error_handler: &ErrorHandler) {
+		let Some(statements) = self.parse(file, error_handler) else {
+			return;
+		};
+		let dir = file.parent().unwrap_or(file);
+		// The resolver only allows imports at the top level
+		for statement in statements {
+			let Stmt::Import(import) = statement else {
+				continue;
+			};
+			let name = import.path.literal.to_string();
+			let location = format!("{}:{}", file.display(), import.keyword.line);
+			let path = match fs::canonicalize(dir.join(&name)) {
+				Ok(path) => path,
+				Err(e) => {
+					self.diagnostics.push(format!("{}: Can't import '{}': {}", location, name, e));
+					continue;
+				},
+			};
+			if let Some(start) = chain.iter().position(|imported| *imported == path) {
+				let cycle: Vec<String> =
+					chain[start..].iter().chain([&path]).map(|p| p.display().to_string()).collect();
+				self.diagnostics.push(format!(
+					"{}: Import cycle: {}",
+					location,
+					cycle.join(" -> ")
+				));
+				continue;
+			}
+			if self.files.contains(&path) {
+				continue;
+			}
+			self.files.push(path.clone());
+			chain.push(path.clone());
+			self.visit(&path, chain, error_handler);
+			chain.pop();
+		}
+	}
+
+	// Scan and parse a file, None if it can't be read or has syntax errors
+	fn parse(&mut self, file: &Path, error_handler: &ErrorHandler) -> Option<Vec<Stmt>> {
+		let source = match fs::read(file) {
+			Ok(source) => source,
+			Err(e) => {
+				self.diagnostics.push(format!("{}: Can't read the file: {}", file.display(), e));
+				return None;
+			},
+		};
+		let error_handler = Rc::new(RefCell::new(error_handler.sharing_output()));
+		let mut scanner = Scanner::new(source, Rc::clone(&error_handler));
+		scanner.scan_tokens();
+		let statements = if error_handler.borrow().had_syntax_error {
+			Vec::new()
+		} else {
+			Parser::new(scanner.tokens, Rc::clone(&error_handler)).parse()
+		};
+		if error_handler.borrow().had_syntax_error {
+			self.diagnostics.push(format!("{}: It has syntax errors", file.display()));
+			return None;
+		}
+		Some(statements)
+	}
+}
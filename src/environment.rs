@@ -1,9 +1,8 @@
 use crate::{
 	error_handler::Error,
-	native_functions::*,
-	token::{LiteralType, Token},
+	token::{LiteralType, Span, Token},
 };
-use std::{cell::RefCell, collections::HashMap, hash::Hash, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 pub type EnvRef = Rc<RefCell<Environment>>;
 
@@ -11,18 +10,54 @@ pub type EnvRef = Rc<RefCell<Environment>>;
 pub struct Environment {
 	enclosing: Option<EnvRef>,
 	values: HashMap<String, LiteralType>,
+	// The line each global was declared on, only kept for the globals because that's all
+	// anything asks about
+	lines: HashMap<String, u32>,
 }
 
 impl Environment {
 	pub fn new(enclosing_env: Option<EnvRef>) -> EnvRef {
-		Rc::new(RefCell::new(Self { enclosing: enclosing_env, values: HashMap::new() }))
+		Rc::new(RefCell::new(Self {
+			enclosing: enclosing_env,
+			values: HashMap::new(),
+			lines: HashMap::new(),
+		}))
+	}
+
+	/// Every variable defined directly in this environment, sorted by name
+	pub fn entries(&self) -> Vec<(String, LiteralType)> {
+		let mut entries: Vec<(String, LiteralType)> =
+			self.values.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+		entries.sort_by(|a, b| a.0.cmp(&b.0));
+		entries
+	}
+
+	/// The name of every variable defined directly in this environment, sorted
+	pub fn names(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.values.keys().cloned().collect();
+		names.sort();
+		names
 	}
 
 	// Store a variable in our hashmap. Note, we allow redefining variables
 	pub fn define(&mut self, name: String, value: LiteralType) {
+		self.lines.remove(&name);
 		self.values.insert(name, value);
 	}
 
+	/// Define a variable declared in the source, remembering the line of its declaration
+	pub fn declare(&mut self, name: &Token, value: LiteralType) {
+		self.define(name.lexeme.clone(), value);
+		if self.enclosing.is_none() {
+			self.lines.insert(name.lexeme.clone(), name.line);
+		}
+	}
+
+	/// The line a global was declared on, None for locals and anything the host defined
+	pub fn defined_line(&self, name: &str) -> Option<u32> {
+		self.lines.get(name).copied()
+	}
+
 	// Get a variable from our hashmap. If it doesn't exist, check the enclosing environment
 	pub fn get(&self, token: &Token) -> Result<LiteralType, Error> {
 		if let Some(value) = self.values.get(&token.lexeme) {
@@ -30,7 +65,20 @@ impl Environment {
 		} else if self.enclosing.is_some() {
 			self.enclosing.as_ref().unwrap().borrow().get(token)
 		} else {
-			Err(Error::RuntimeError(token.line, format!("Undefined variable '{}'", token.lexeme)))
+			Err(Error::RuntimeError(token.span(), format!("Undefined variable '{}'", token.lexeme)))
+		}
+	}
+
+	/// The environment this one is nested in, None for the globals
+	pub fn enclosing(&self) -> Option<EnvRef> {
+		self.enclosing.clone()
+	}
+
+	/// Look a name up in this environment or any enclosing one
+	pub fn lookup(&self, name: &str) -> Option<LiteralType> {
+		match self.values.get(name) {
+			Some(value) => Some(value.clone()),
+			None => self.enclosing.as_ref()?.borrow().lookup(name),
 		}
 	}
 
@@ -45,7 +93,21 @@ impl Environment {
 		if let Some(enclosing) = &self.enclosing {
 			return enclosing.borrow().get_at(distance - 1, name);
 		}
-		Err(Error::RuntimeError(0, "Environment not found".to_string()))
+		Err(Error::RuntimeError(Span::default(), "Environment not found".to_string()))
+	}
+
+	/// Run a closure against the value a fixed distance away from the current environment,
+	/// allowing it to be modified in place. Returns None if the value can't be found
+	pub fn update_at<R>(
+		&mut self,
+		distance: u64,
+		name: &str,
+		update: impl FnOnce(&mut LiteralType) -> R,
+	) -> Option<R> {
+		if distance == 0 {
+			return self.values.get_mut(name).map(update);
+		}
+		self.enclosing.as_ref()?.borrow_mut().update_at(distance - 1, name, update)
 	}
 
 	// Assign a value to a variable in our hashmap. If it doesn't exist, check the enclosing
@@ -57,7 +119,7 @@ impl Environment {
 		} else if let Some(enclosing) = &mut self.enclosing {
 			enclosing.borrow_mut().assign(token, value)
 		} else {
-			Err(Error::RuntimeError(token.line, format!("Undefined variable '{}'", token.lexeme)))
+			Err(Error::RuntimeError(token.span(), format!("Undefined variable '{}'", token.lexeme)))
 		}
 	}
 
@@ -77,6 +139,6 @@ impl Environment {
 			return enclosing.borrow_mut().assign_at(distance - 1, token, value);
 		}
 
-		Err(Error::RuntimeError(0, "Environment not found".to_string()))
+		Err(Error::RuntimeError(Span::default(), "Environment not found".to_string()))
 	}
 }
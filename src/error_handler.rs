@@ -1,71 +1,176 @@
-use crate::token::{LiteralType, Token, TokenType};
+use crate::{
+	interpreter::Output,
+	token::{LiteralType, Span, Token, TokenType},
+};
 use colored::Colorize;
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum Error {
 	SyntaxError(u32, String),
 	ParseError(Token, String),
-	/// Error type for runtime errors (line, message)
-	RuntimeError(u32, String),
+	/// Error type for runtime errors (where, message). Natives don't know where they were
+	/// called from so they leave the span empty, and the call fills it in
+	RuntimeError(Span, String),
 	ResolverError(Token, String),
 	Return(LiteralType),
+	/// Unwinds to the nearest loop, which stops
+	Break,
+	/// Unwinds to the nearest loop, which moves on to its next iteration
+	Continue,
+	/// Execution was aborted by the user (Ctrl-C)
+	Interrupted,
+	/// The script ran into one of the interpreter's Limits (line, message). Unlike a runtime
+	/// error this can't be caught, it always stops the script
+	LimitExceeded(u32, String),
+	/// The script called exit(code). It unwinds everything, try_call included, and stops the
+	/// script without reporting anything
+	Exit(i32),
 	Unknown,
 }
 
 pub struct ErrorHandler {
+	// Set by any error that stops a program from running: syntax, parse and resolver errors
 	pub had_error: bool,
+	// Set by scanner and parser errors only
+	pub had_syntax_error: bool,
+	// Set by resolver errors only
+	pub had_resolve_error: bool,
 	pub had_runtime_error: bool,
+	pub was_interrupted: bool,
+	// How many warnings have been reported, these don't stop anything from running
+	pub warnings: usize,
+	// Every error and warning reported since the last reset, without colours
+	pub diagnostics: Vec<String>,
+	// Where errors and warnings are written, standard error if this is None
+	output: Option<Output>,
+}
+
+impl Default for ErrorHandler {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl ErrorHandler {
 	pub fn new() -> Self {
-		Self { had_error: false, had_runtime_error: false }
+		Self {
+			had_error: false,
+			had_syntax_error: false,
+			had_resolve_error: false,
+			had_runtime_error: false,
+			was_interrupted: false,
+			warnings: 0,
+			diagnostics: Vec::new(),
+			output: None,
+		}
+	}
+
+	/// An error handler that writes its reports to output instead of standard error
+	pub fn with_output(output: Output) -> Self {
+		Self { output: Some(output), ..Self::new() }
+	}
+
+	/// A fresh error handler that reports to the same place as this one
+	pub fn sharing_output(&self) -> Self {
+		Self { output: self.output.clone(), ..Self::new() }
+	}
+
+	/// Write a line where errors are reported, the log natives use this too
+	pub(crate) fn write_line(&self, line: fmt::Arguments) {
+		match &self.output {
+			// There is nowhere left to report a failure to write an error, so it is dropped
+			Some(output) => {
+				let _ = writeln!(output.borrow_mut(), "{}", line);
+			},
+			None => eprintln!("{}", line),
+		}
 	}
 
 	pub fn reset(&mut self) {
 		self.had_error = false;
+		self.had_syntax_error = false;
+		self.had_resolve_error = false;
 		self.had_runtime_error = false;
+		self.was_interrupted = false;
+		self.warnings = 0;
+		self.diagnostics.clear();
+	}
+
+	/// Report something that is probably a mistake but is still allowed to run
+	pub fn report_warning(&mut self, line: u32, message: &str) {
+		self.warnings += 1;
+		self.diagnostics.push(format!("[line {}] Warning: {}", line, message));
+		self.write_line(format_args!(
+			"[line {}] {} {}",
+			line,
+			"Warning:".yellow().italic(),
+			message.yellow()
+		));
 	}
 
 	pub fn report_error(&mut self, error: Error) {
 		// Only print this silly message on the first error in a block
-		if !self.had_error && !self.had_runtime_error {
-			eprintln!("{}", "(╯°□°)╯︵ ɹoɹɹƎ".red().bold());
-		}
-		match error {
+		let first_error = !self.had_error && !self.had_runtime_error;
+		// Errors with a token say where on the line it is as well
+		let (location, label, message) = match error {
 			Error::SyntaxError(line, message) => {
 				self.had_error = true;
-				eprintln!("[line {}] {} {}", line, "Syntax Error:".red().italic(), message.red());
+				self.had_syntax_error = true;
+				(Some(format!("line {}", line)), "Syntax Error:".to_string(), message)
 			},
 			Error::ParseError(token, message) => {
 				self.had_error = true;
-				eprintln!(
-					"[line {}] {} {}",
-					token.get_line(),
-					"Parse Error:".red().italic(),
-					message.red()
-				)
+				self.had_syntax_error = true;
+				// Say which token the parser stopped at
+				let label = match token.token_type {
+					TokenType::Eof => "Parse Error at end:".to_string(),
+					_ => format!("Parse Error at '{}':", token.lexeme),
+				};
+				(Some(token.location()), label, message)
 			},
-			Error::RuntimeError(line, message) => {
+			Error::RuntimeError(span, message) => {
 				self.had_runtime_error = true;
-				eprintln!("[line {}] {} {}", line, "Runtime Error:".red().italic(), message.red())
+				(Some(span.location()), "Runtime Error:".to_string(), message)
 			},
 			Error::ResolverError(token, message) => {
 				self.had_error = true;
-				eprintln!(
-					"[line {}] {} {}",
-					token.get_line(),
-					"Resolver Error:".red().italic(),
-					message.red()
-				)
+				self.had_resolve_error = true;
+				(Some(token.location()), "Resolver Error:".to_string(), message)
+			},
+			// No need to throw an error
+			Error::Return(_) | Error::Break | Error::Continue | Error::Exit(_) => return,
+			Error::LimitExceeded(line, message) => {
+				self.had_runtime_error = true;
+				(Some(format!("line {}", line)), "Limit Error:".to_string(), message)
 			},
-			Error::Return(literal) => {
-				// No need to throw an error
-				return;
+			Error::Interrupted => {
+				self.had_runtime_error = true;
+				self.was_interrupted = true;
+				(None, String::new(), "Interrupted.".to_string())
 			},
 			Error::Unknown => {
 				self.had_error = true;
-				eprintln!("{}", "An unknown error occurred. Sorry :(".red())
+				(None, String::new(), "An unknown error occurred. Sorry :(".to_string())
+			},
+		};
+
+		if first_error {
+			self.write_line(format_args!("{}", "(╯°□°)╯︵ ɹoɹɹƎ".red().bold()));
+		}
+		match location {
+			Some(location) => {
+				self.diagnostics.push(format!("[{}] {} {}", location, label, message));
+				self.write_line(format_args!(
+					"[{}] {} {}",
+					location,
+					label.red().italic(),
+					message.red()
+				));
+			},
+			None => {
+				self.diagnostics.push(message.clone());
+				self.write_line(format_args!("{}", message.red()));
 			},
 		}
 	}
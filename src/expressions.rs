@@ -1,7 +1,9 @@
 use crate::{
 	error_handler::Error,
+	statements::Stmt,
 	token::{LiteralType, Token},
 };
+use std::hash::{Hash, Hasher};
 
 pub trait Visitor {
 	type Value;
@@ -13,10 +15,14 @@ pub trait Visitor {
 	fn visit_set(&mut self, set: &Set) -> Result<Self::Value, Error>;
 	fn visit_grouping(&mut self, grouping: &Grouping) -> Result<Self::Value, Error>;
 	fn visit_array(&mut self, array: &Array) -> Result<Self::Value, Error>;
+	fn visit_map(&mut self, map: &Map) -> Result<Self::Value, Error>;
+	fn visit_if_expr(&mut self, if_expr: &IfExpr) -> Result<Self::Value, Error>;
 	fn visit_index(&mut self, index: &Index) -> Result<Self::Value, Error>;
 	fn visit_assign_index(&mut self, assign_index: &AssignIndex) -> Result<Self::Value, Error>;
 	fn visit_literal(&mut self, literal: &Literal) -> Result<Self::Value, Error>;
 	fn visit_logical(&mut self, logical: &Logical) -> Result<Self::Value, Error>;
+	fn visit_postfix(&mut self, postfix: &Postfix) -> Result<Self::Value, Error>;
+	fn visit_slice(&mut self, slice: &Slice) -> Result<Self::Value, Error>;
 	fn visit_super(&mut self, super_: &Super) -> Result<Self::Value, Error>;
 	fn visit_this(&mut self, this: &This) -> Result<Self::Value, Error>;
 	fn visit_unary(&mut self, unary: &Unary) -> Result<Self::Value, Error>;
@@ -32,10 +38,14 @@ pub enum Expr {
 	Set(Box<Set>),
 	Grouping(Box<Grouping>),
 	Array(Box<Array>),
+	Map(Box<Map>),
+	IfExpr(Box<IfExpr>),
 	Index(Box<Index>),
 	AssignIndex(Box<AssignIndex>),
 	Literal(Literal),
 	Logical(Box<Logical>),
+	Postfix(Box<Postfix>),
+	Slice(Box<Slice>),
 	Super(Super),
 	This(This),
 	Unary(Box<Unary>),
@@ -52,16 +62,169 @@ impl Expr {
 			Expr::Set(set) => visitor.visit_set(set),
 			Expr::Grouping(grouping) => visitor.visit_grouping(grouping),
 			Expr::Array(array) => visitor.visit_array(array),
+			Expr::Map(map) => visitor.visit_map(map),
+			Expr::IfExpr(if_expr) => visitor.visit_if_expr(if_expr),
 			Expr::Index(index) => visitor.visit_index(index),
 			Expr::AssignIndex(assign_index) => visitor.visit_assign_index(assign_index),
 			Expr::Literal(literal) => visitor.visit_literal(literal),
 			Expr::Logical(logical) => visitor.visit_logical(logical),
+			Expr::Postfix(postfix) => visitor.visit_postfix(postfix),
+			Expr::Slice(slice) => visitor.visit_slice(slice),
 			Expr::Super(super_) => visitor.visit_super(super_),
 			Expr::This(this) => visitor.visit_this(this),
 			Expr::Unary(unary) => visitor.visit_unary(unary),
 			Expr::Variable(variable) => visitor.visit_variable(variable),
 		}
 	}
+
+	/// Whether evaluating this expression could change program state through an assignment or
+	/// a call
+	pub fn has_side_effects(&self) -> bool {
+		match self {
+			Expr::Assign(_) |
+			Expr::Call(_) |
+			Expr::Set(_) |
+			Expr::AssignIndex(_) |
+			Expr::Postfix(_) => true,
+			Expr::Binary(binary) =>
+				binary.left.has_side_effects() || binary.right.has_side_effects(),
+			Expr::Logical(logical) =>
+				logical.left.has_side_effects() || logical.right.has_side_effects(),
+			Expr::Get(get) => get.object.has_side_effects(),
+			Expr::Grouping(grouping) => grouping.expression.has_side_effects(),
+			Expr::Array(array) => array.values.iter().any(|value| value.has_side_effects()),
+			Expr::Map(map) => map.expressions().any(|expr| expr.has_side_effects()),
+			Expr::Index(index) => index.object.has_side_effects() || index.index.has_side_effects(),
+			Expr::IfExpr(if_expr) =>
+				if_expr.condition.has_side_effects() ||
+					if_expr.branches().any(|branch| {
+						!branch.statements.is_empty() ||
+							branch.value.as_ref().is_some_and(Expr::has_side_effects)
+					}),
+			Expr::Slice(slice) =>
+				slice.object.has_side_effects() ||
+					slice.bounds().any(|bound| bound.has_side_effects()),
+			Expr::Unary(unary) => unary.right.has_side_effects(),
+			Expr::Literal(_) | Expr::Super(_) | Expr::This(_) | Expr::Variable(_) => false,
+		}
+	}
+
+	/// Call f on this expression and then on every expression inside it
+	pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+		f(self);
+		match self {
+			Expr::Assign(assign) => assign.value.walk(f),
+			Expr::Binary(binary) => {
+				binary.left.walk(f);
+				binary.right.walk(f);
+			},
+			Expr::Call(call) => {
+				call.callee.walk(f);
+				call.arguments.iter().for_each(|argument| argument.walk(f));
+			},
+			Expr::Get(get) => get.object.walk(f),
+			Expr::Set(set) => {
+				set.object.walk(f);
+				set.value.walk(f);
+			},
+			Expr::Grouping(grouping) => grouping.expression.walk(f),
+			Expr::Array(array) => array.values.iter().for_each(|value| value.walk(f)),
+			Expr::Map(map) => map.expressions().for_each(|expr| expr.walk(f)),
+			Expr::IfExpr(if_expr) => {
+				if_expr.condition.walk(f);
+				for branch in if_expr.branches() {
+					for statement in &branch.statements {
+						statement.walk(&mut |statement| {
+							statement.expressions().into_iter().for_each(|expr| expr.walk(f))
+						});
+					}
+					if let Some(value) = &branch.value {
+						value.walk(f);
+					}
+				}
+			},
+			Expr::Index(index) => {
+				index.object.walk(f);
+				index.index.walk(f);
+			},
+			Expr::AssignIndex(assign_index) => {
+				assign_index.object.walk(f);
+				assign_index.index.walk(f);
+				assign_index.value.walk(f);
+			},
+			Expr::Logical(logical) => {
+				logical.left.walk(f);
+				logical.right.walk(f);
+			},
+			Expr::Postfix(postfix) => postfix.assign.walk(f),
+			Expr::Slice(slice) => {
+				slice.object.walk(f);
+				slice.bounds().for_each(|bound| bound.walk(f));
+			},
+			Expr::Unary(unary) => unary.right.walk(f),
+			Expr::Literal(_) | Expr::Super(_) | Expr::This(_) | Expr::Variable(_) => (),
+		}
+	}
+
+	/// A token from this expression to report errors against, None for literals and arrays of
+	/// them. Follows groupings and array elements without recursing so it works on any depth
+	pub fn token(&self) -> Option<&Token> {
+		let mut expr = self;
+		loop {
+			return match expr {
+				Expr::Assign(assign) => Some(&assign.name),
+				Expr::Binary(binary) => Some(&binary.operator),
+				Expr::Call(call) => Some(&call.paren),
+				Expr::Get(get) => Some(&get.name),
+				Expr::Set(set) => Some(&set.name),
+				Expr::IfExpr(if_expr) => Some(&if_expr.keyword),
+				Expr::Index(index) => Some(&index.bracket),
+				Expr::Map(map) => Some(&map.brace),
+				Expr::AssignIndex(assign_index) => Some(&assign_index.bracket),
+				Expr::Logical(logical) => Some(&logical.operator),
+				Expr::Postfix(postfix) => Some(&postfix.operator),
+				Expr::Slice(slice) => Some(&slice.bracket),
+				Expr::Super(super_) => Some(&super_.keyword),
+				Expr::This(this) => Some(&this.keyword),
+				Expr::Unary(unary) => Some(&unary.operator),
+				Expr::Variable(variable) => Some(&variable.name),
+				Expr::Literal(_) => None,
+				Expr::Grouping(grouping) => {
+					expr = &grouping.expression;
+					continue;
+				},
+				Expr::Array(array) => match array.values.first() {
+					Some(first) => {
+						expr = first;
+						continue;
+					},
+					None => None,
+				},
+			};
+		}
+	}
+
+	/// The variable this expression changes when it is evaluated, not counting anything inside
+	/// it. Writing to a field or array element counts as changing the variable holding it
+	pub fn assigned_variable(&self) -> Option<&Token> {
+		match self {
+			Expr::Assign(assign) => Some(&assign.name),
+			Expr::Set(set) => set.object.root_variable(),
+			Expr::AssignIndex(assign_index) => assign_index.object.root_variable(),
+			_ => None,
+		}
+	}
+
+	/// The variable a chain of property accesses and indexes starts from, eg `a` for `a.b[0]`
+	pub fn root_variable(&self) -> Option<&Token> {
+		match self {
+			Expr::Variable(variable) => Some(&variable.name),
+			Expr::Get(get) => get.object.root_variable(),
+			Expr::Index(index) => index.object.root_variable(),
+			Expr::Grouping(grouping) => grouping.expression.root_variable(),
+			_ => None,
+		}
+	}
 }
 
 // Variable assignment
@@ -106,17 +269,82 @@ pub struct Array {
 	pub values: Vec<Expr>,
 }
 
+// Map Expression, {"key": value}. The keys are expressions that have to give strings
+#[derive(Debug, PartialEq, Clone, Hash, Eq)]
+pub struct Map {
+	// The opening '{', used to report errors on the right line
+	pub brace: Token,
+	pub entries: Vec<(Expr, Expr)>,
+}
+
+impl Map {
+	/// Every key and value in order
+	pub fn expressions(&self) -> impl Iterator<Item = &Expr> {
+		self.entries.iter().flat_map(|(key, value)| [key, value])
+	}
+}
+
 // Index Expression
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct Index {
 	pub object: Expr,
+	// The opening '[', used to report errors on the right line
+	pub bracket: Token,
 	pub index: Expr,
 }
 
+// If used as an expression, each branch is a scope whose last expression is its value
+#[derive(Debug, PartialEq, Clone)]
+pub struct IfExpr {
+	pub keyword: Token,
+	pub condition: Expr,
+	pub then_branch: Branch,
+	pub else_branch: Option<Branch>,
+}
+
+impl IfExpr {
+	pub fn branches(&self) -> impl Iterator<Item = &Branch> {
+		std::iter::once(&self.then_branch).chain(&self.else_branch)
+	}
+}
+
+// Statements hold floats so they can't derive these. Two different if expressions never share
+// a keyword token, so the keyword is enough to tell them apart
+impl Eq for IfExpr {}
+
+impl Hash for IfExpr {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.keyword.hash(state);
+	}
+}
+
+// One branch of an if expression
+#[derive(Debug, PartialEq, Clone)]
+pub struct Branch {
+	pub statements: Vec<Stmt>,
+	pub value: Option<Expr>,
+}
+
+// Slice of an array or string, a[start:end]. Either bound can be left out
+#[derive(Debug, PartialEq, Clone, Hash, Eq)]
+pub struct Slice {
+	pub object: Expr,
+	pub bracket: Token,
+	pub start: Option<Expr>,
+	pub end: Option<Expr>,
+}
+
+impl Slice {
+	pub fn bounds(&self) -> impl Iterator<Item = &Expr> {
+		self.start.iter().chain(&self.end)
+	}
+}
+
 // Variable assignment at index (For arrays)
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct AssignIndex {
 	pub object: Expr,
+	pub bracket: Token,
 	pub index: Expr,
 	pub value: Expr,
 }
@@ -135,6 +363,14 @@ pub struct Logical {
 	pub right: Expr,
 }
 
+// Postfix increment or decrement, assign is the desugared assignment of target + 1 or target - 1
+#[derive(Debug, PartialEq, Clone, Hash, Eq)]
+pub struct Postfix {
+	pub target: Expr,
+	pub operator: Token,
+	pub assign: Expr,
+}
+
 // Set Expression
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct Set {
@@ -1,88 +1,1438 @@
 use crate::{
-	callable,
-	callable::{Callable, JasnClass, JasnFunction, NativeFunction},
+	callable::{
+		Callable, JasnClass, JasnFunction, JasnInstance, JasnInstanceRef, NativeDocs, NativeFn,
+		NativeFunction, VARIADIC,
+	},
+	debugger,
+	debugger::Debugger,
 	environment::{EnvRef, Environment},
 	error,
 	error_handler::{Error, ErrorHandler},
 	expressions::*,
 	native_functions::*,
+	parser::{Parser, DEFAULT_MAX_NESTING},
+	program::{self, CompiledProgram, SourceTables},
+	resolver::Resolver,
+	rng::Xoshiro128,
+	scanner::Scanner,
 	statements::*,
-	token::{LiteralType, Token, TokenType},
+	token::{LiteralType, MapRef, RenderLimits, Span, Token, TokenType},
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+	cell::{Cell, RefCell},
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt, fs,
+	io::{self, BufRead, Write},
+	ops::{Range, RangeInclusive},
+	path::{Path, PathBuf},
+	rc::Rc,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+/// Where print writes to, standard output unless the interpreter is built with another
+pub type Output = Rc<RefCell<dyn Write>>;
+/// Where input reads from, standard input unless the interpreter is built with another
+pub type Input = Rc<RefCell<dyn BufRead>>;
+
+/// Everything a snippet run with run_snippet produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnippetResult {
+	// The value of the final statement if it was an expression, null otherwise
+	pub value: LiteralType,
+	// Everything the snippet printed
+	pub output: String,
+	// Every error and warning reported while scanning, parsing, resolving and running
+	pub diagnostics: Vec<String>,
+}
+
+/// Strings longer than this are almost certainly a runaway loop
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Each JASN call takes several Rust frames, so recursion much deeper than this overflows the
+/// interpreter thread's stack in a debug build before any other limit is reached
+pub const DEFAULT_MAX_CALL_DEPTH: u64 = 1_000;
+
+/// Caps on what a script is allowed to use. None means no cap. Going over the step budget, call
+/// depth or timeout stops the script with an error that try_call can't catch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	// Statements a run may execute
+	pub max_steps: Option<u64>,
+	// JASN functions that may be running inside each other
+	pub max_call_depth: Option<u64>,
+	// How long a run may take
+	pub timeout: Option<Duration>,
+	// Longest string + is allowed to build
+	pub max_string_length: Option<usize>,
+	// Largest array a native is allowed to build in one go
+	pub max_array_length: Option<usize>,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Self {
+			max_steps: None,
+			max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+			timeout: None,
+			max_string_length: Some(DEFAULT_MAX_STRING_LENGTH),
+			max_array_length: Some(MAX_ARRAY_LENGTH),
+		}
+	}
+}
+
+impl Limits {
+	/// Tight limits for scripts that can't be trusted to finish
+	pub fn strict() -> Self {
+		Self {
+			max_steps: Some(1_000_000),
+			max_call_depth: Some(200),
+			timeout: Some(Duration::from_secs(5)),
+			max_string_length: Some(1024 * 1024),
+			max_array_length: Some(100_000),
+		}
+	}
+
+	/// Limits only a runaway script should reach
+	pub fn relaxed() -> Self {
+		Self {
+			max_steps: Some(1_000_000_000),
+			max_call_depth: Some(2_000),
+			timeout: Some(Duration::from_secs(60)),
+			..Self::default()
+		}
+	}
+
+	/// No limits at all
+	pub fn off() -> Self {
+		Self {
+			max_steps: None,
+			max_call_depth: None,
+			timeout: None,
+			max_string_length: None,
+			max_array_length: None,
+		}
+	}
+
+	/// The preset called "strict", "relaxed" or "off"
+	pub fn preset(name: &str) -> Option<Self> {
+		match name {
+			"strict" => Some(Self::strict()),
+			"relaxed" => Some(Self::relaxed()),
+			"off" => Some(Self::off()),
+			_ => None,
+		}
+	}
+}
+
+/// A limit a script ran into, along with the value it was set to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+	Steps(u64),
+	CallDepth(u64),
+	Timeout(Duration),
+	StringLength(usize),
+	ArrayLength(usize),
+}
+
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Limit::Steps(max) => write!(f, "step budget of {} statements", max),
+			Limit::CallDepth(max) => write!(f, "call depth of {}", max),
+			Limit::Timeout(timeout) => write!(f, "timeout of {}s", timeout.as_secs_f64()),
+			Limit::StringLength(max) => write!(f, "string length of {} bytes", max),
+			Limit::ArrayLength(max) => write!(f, "array length of {} elements", max),
+		}
+	}
+}
+
+/// Counters gathered while the interpreter runs. These are exact, not sampled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+	pub statements: u64,
+	pub expressions: u64,
+	pub function_calls: u64,
+	pub native_calls: u64,
+	pub max_call_depth: u64,
+	pub loop_iterations: u64,
+	pub assertions_passed: u64,
+}
+
+impl Stats {
+	pub fn reset(&mut self) {
+		*self = Self::default();
+	}
+}
+
+impl fmt::Display for Stats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Statements executed:   {}", self.statements)?;
+		writeln!(f, "Expressions evaluated: {}", self.expressions)?;
+		writeln!(f, "Function calls:        {}", self.function_calls)?;
+		writeln!(f, "Native calls:          {}", self.native_calls)?;
+		writeln!(f, "Max call depth:        {}", self.max_call_depth)?;
+		writeln!(f, "Loop iterations:       {}", self.loop_iterations)?;
+		write!(f, "Assertions passed:     {}", self.assertions_passed)
+	}
+}
+
+/// What reading a missing instance field does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingField {
+	/// Report an undefined property runtime error
+	#[default]
+	Error,
+	/// Evaluate to null
+	Null,
+}
+
+/// What using a field or method whose name starts with an underscore does from outside its
+/// class. Through `this` it is always allowed, and so is using it inside the class or one of
+/// its subclasses in an instance of that class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrivateAccess {
+	/// Report a warning the first time each place does it
+	#[default]
+	Warn,
+	/// Report a runtime error
+	Error,
+}
+
+/// What happened the first time a module was imported, keyed by its canonical path
+#[derive(Debug, Clone)]
+enum Module {
+	/// The module is still running, importing it again means there is a cycle
+	Loading,
+	/// The module ran, these are the statements it was made of
+	Loaded(Rc<Vec<Stmt>>),
+	/// The module failed, importing it again fails straight away with the same message
+	Failed(String),
+}
 
 pub struct Interpreter {
 	// The fixed global environment
 	pub global: EnvRef,
 	// The current environment we are in based on the current scope
 	environment: EnvRef,
-	locals: HashMap<Expr, u64>,
+	// How many scopes out each resolved local lives, keyed on the token naming it. Tokens carry
+	// their offset so every use of a variable has its own key, and looking one up is cheap
+	// however large the expression around it. The prelude, modules and the script each have
+	// their own table
+	pub(crate) locals: SourceTables<u64>,
+	// Every use of an underscore name other than through this, keyed on the token naming it
+	// like locals, with the class it is written inside of if there is one
+	pub(crate) private_uses: SourceTables<Option<String>>,
 	// The error handler
 	pub error_handler: Rc<RefCell<ErrorHandler>>,
+	// Messages from the log_* natives below this level are dropped
+	pub log_level: LogLevel,
+	// Where natives get the current time from, swappable so the time can be frozen
+	pub time_source: fn() -> f64,
+	// Counters for what the interpreter has done so far
+	stats: Stats,
+	// How many JASN functions are currently being executed
+	call_depth: u64,
+	// How deeply nested the expression being evaluated is within the current call, and the
+	// depths the calls below it were at
+	depth: usize,
+	caller_depths: Vec<usize>,
+	// Expressions nested more deeply than this are rejected by the parser, resolver and evaluate
+	pub(crate) max_nesting: usize,
+	// Natives that are replaced with an error when they are defined, used by sandbox mode
+	sandboxed_natives: HashSet<String>,
+	// Prefix added to natives defined while a module is being loaded
+	module_prefix: Option<String>,
+	// Every native defined so far by name, for help() to describe
+	natives: BTreeMap<String, NativeFunction>,
+	// The functions the prelude defined
+	prelude: HashSet<String>,
+	// Keep running the remaining top level statements after a runtime error
+	continue_on_error: bool,
+	limits: Limits,
+	// The limit that stopped the script, if one did
+	limit_hit: Cell<Option<Limit>>,
+	// Statements executed in the current run and when it has to finish by, for the limits
+	steps: u64,
+	deadline: Option<Instant>,
+	// How much of a value print shows
+	pub render_limits: RenderLimits,
+	pub(crate) missing_field: MissingField,
+	private_access: PrivateAccess,
+	pub(crate) output: Output,
+	pub(crate) input: Input,
+	// Imports are refused in sandbox mode as they read files
+	sandbox: bool,
+	// Every module imported so far, so each one only runs once
+	modules: HashMap<PathBuf, Module>,
+	// The directory of the script or module being run, imports are relative to it
+	script_dir: Option<PathBuf>,
+	// Set from another thread or a signal handler to abort the running script
+	interrupt: Arc<AtomicBool>,
+	// Where random and shuffle get their randomness, reseeded by random_seed
+	pub(crate) rng: Xoshiro128,
+	// The canonical path of the file being run, None for source that didn't come from a file
+	pub(crate) current_file: Option<PathBuf>,
+	// The arguments given to the script, for args()
+	pub(crate) args: Vec<String>,
+	// The code the script asked to exit with, once it has called exit()
+	exit_code: Option<i32>,
+	pub debugger: Debugger,
 }
 
-impl Interpreter {
-	pub fn new(error_handler: Rc<RefCell<ErrorHandler>>) -> Self {
+/// Configures an Interpreter before it is created
+pub struct InterpreterBuilder {
+	error_handler: Rc<RefCell<ErrorHandler>>,
+	sandbox: bool,
+	allowed_natives: HashSet<String>,
+	denied_natives: HashSet<String>,
+	continue_on_error: bool,
+	limits: Limits,
+	render_limits: RenderLimits,
+	missing_field: MissingField,
+	private_access: PrivateAccess,
+	max_nesting: usize,
+	output: Option<Output>,
+	input: Option<Input>,
+	prelude: bool,
+	args: Vec<String>,
+}
+
+impl InterpreterBuilder {
+	/// Send everything the script prints to output instead of standard output
+	pub fn output(mut self, output: Output) -> Self {
+		self.output = Some(output);
+		self
+	}
+
+	/// Read the script's input from input instead of standard input
+	pub fn input(mut self, input: Input) -> Self {
+		self.input = Some(input);
+		self
+	}
+
+	/// Choose whether reading a field that doesn't exist is an error or null
+	pub fn missing_field(mut self, missing_field: MissingField) -> Self {
+		self.missing_field = missing_field;
+		self
+	}
+
+	/// Whether using an underscore field or method from outside its class warns or errors
+	pub fn private_access(mut self, private_access: PrivateAccess) -> Self {
+		self.private_access = private_access;
+		self
+	}
+
+	/// Limit how much of large or deeply nested values print shows
+	pub fn render_limits(mut self, render_limits: RenderLimits) -> Self {
+		self.render_limits = render_limits;
+		self
+	}
+
+	/// Limit how long a string built by concatenation can get, None removes the limit
+	pub fn max_string_length(mut self, max_string_length: Option<usize>) -> Self {
+		self.limits.max_string_length = max_string_length;
+		self
+	}
+
+	/// Replace every limit at once, usually with one of the presets like Limits::strict()
+	pub fn limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Limit how deeply expressions can nest, see DEFAULT_MAX_NESTING
+	pub fn max_nesting(mut self, max_nesting: usize) -> Self {
+		self.max_nesting = max_nesting;
+		self
+	}
+
+	/// Keep executing the remaining top level statements after a runtime error instead of
+	/// stopping, useful for REPL-like embedders
+	pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+		self.continue_on_error = continue_on_error;
+		self
+	}
+
+	/// Sandbox mode disables every native in SANDBOXED_NATIVES as well as any added with
+	/// deny_native, so untrusted scripts can't reach outside the interpreter
+	pub fn sandbox(mut self, sandbox: bool) -> Self {
+		self.sandbox = sandbox;
+		self
+	}
+
+	/// Keep a native available in sandbox mode
+	pub fn allow_native(mut self, name: &str) -> Self {
+		self.allowed_natives.insert(name.to_string());
+		self
+	}
+
+	/// Disable an extra native in sandbox mode
+	pub fn deny_native(mut self, name: &str) -> Self {
+		self.denied_natives.insert(name.to_string());
+		self
+	}
+
+	/// The arguments the script is run with, which it reads with args()
+	pub fn args(mut self, args: Vec<String>) -> Self {
+		self.args = args;
+		self
+	}
+
+	/// Choose whether the prelude is loaded, it is by default. Leaving it out gives an
+	/// interpreter with nothing but the natives
+	pub fn prelude(mut self, prelude: bool) -> Self {
+		self.prelude = prelude;
+		self
+	}
+
+	pub fn build(self) -> Interpreter {
 		let environment = Environment::new(None);
-		NativeFunctions::define_native_functions(environment.clone());
+		let sandboxed_natives = if self.sandbox {
+			SANDBOXED_NATIVES
+				.iter()
+				.map(|name| name.to_string())
+				.chain(self.denied_natives)
+				.filter(|name| !self.allowed_natives.contains(name))
+				.collect()
+		} else {
+			HashSet::new()
+		};
 
-		Self {
+		let mut interpreter = Interpreter {
 			global: environment.clone(),
 			environment: environment.clone(),
 			locals: Default::default(),
+			private_uses: Default::default(),
+			error_handler: self.error_handler,
+			log_level: LogLevel::Info,
+			time_source: system_time,
+			stats: Stats::default(),
+			call_depth: 0,
+			depth: 0,
+			caller_depths: Vec::new(),
+			max_nesting: self.max_nesting,
+			sandboxed_natives,
+			module_prefix: None,
+			natives: BTreeMap::new(),
+			prelude: HashSet::new(),
+			continue_on_error: self.continue_on_error,
+			limits: self.limits,
+			limit_hit: Cell::new(None),
+			steps: 0,
+			deadline: None,
+			render_limits: self.render_limits,
+			missing_field: self.missing_field,
+			private_access: self.private_access,
+			output: self.output.unwrap_or_else(|| Rc::new(RefCell::new(io::stdout()))),
+			input: self.input.unwrap_or_else(|| Rc::new(RefCell::new(io::stdin().lock()))),
+			sandbox: self.sandbox,
+			modules: HashMap::new(),
+			script_dir: None,
+			interrupt: Arc::new(AtomicBool::new(false)),
+			rng: Xoshiro128::seed(rand::random()),
+			current_file: None,
+			args: self.args,
+			exit_code: None,
+			debugger: Debugger::default(),
+		};
+		NativeFunctions {}.register(&mut interpreter);
+		if self.prelude {
+			interpreter.load_prelude();
+		}
+		interpreter
+	}
+}
+
+impl Interpreter {
+	pub fn new(error_handler: Rc<RefCell<ErrorHandler>>) -> Self {
+		Self::builder(error_handler).build()
+	}
+
+	pub fn builder(error_handler: Rc<RefCell<ErrorHandler>>) -> InterpreterBuilder {
+		InterpreterBuilder {
 			error_handler,
+			sandbox: false,
+			allowed_natives: HashSet::new(),
+			denied_natives: HashSet::new(),
+			continue_on_error: false,
+			limits: Limits::default(),
+			render_limits: RenderLimits::default(),
+			missing_field: MissingField::default(),
+			private_access: PrivateAccess::default(),
+			max_nesting: DEFAULT_MAX_NESTING,
+			output: None,
+			input: None,
+			prelude: true,
+			args: Vec::new(),
+		}
+	}
+
+	/// Define a native function in the global environment so hosts can extend the language
+	pub fn define_native(&mut self, name: &str, arity: u8, docs: NativeDocs, function: NativeFn) {
+		self.define_native_range(name, arity..=arity, docs, function);
+	}
+
+	/// Define a native function that takes at least arity arguments and any number after them
+	pub fn define_native_variadic(
+		&mut self,
+		name: &str,
+		arity: u8,
+		docs: NativeDocs,
+		function: NativeFn,
+	) {
+		self.define_native_range(name, arity..=VARIADIC, docs, function);
+	}
+
+	/// Define a native function that takes a range of arguments, any it isn't given are left out
+	/// of the arguments it is called with
+	pub fn define_native_range(
+		&mut self,
+		name: &str,
+		arity: RangeInclusive<u8>,
+		docs: NativeDocs,
+		function: NativeFn,
+	) {
+		let name = match &self.module_prefix {
+			Some(prefix) => format!("{}_{}", prefix, name),
+			None => name.to_string(),
+		};
+		debug_assert!(!docs.description.is_empty(), "native '{}' needs a description", name);
+		let sandboxed = self.sandboxed_natives.contains(&name);
+		let native = NativeFunction {
+			name: name.clone(),
+			arity: *arity.start(),
+			max_arity: *arity.end(),
+			function,
+			docs,
+			sandboxed,
+		};
+		self.natives.insert(name.clone(), native.clone());
+		self.global
+			.borrow_mut()
+			.define(name, LiteralType::Callable(Callable::NativeFunction(native)));
+	}
+
+	/// Every native defined in this interpreter, sorted by name
+	pub fn natives(&self) -> impl Iterator<Item = &NativeFunction> {
+		self.natives.values()
+	}
+
+	/// Load a module of natives, prefixing each of its functions with the module's name
+	pub fn load_module(&mut self, module: Box<dyn NativeModule>) {
+		let enclosing_prefix = self.module_prefix.replace(module.name().to_string());
+		module.register(self);
+		self.module_prefix = enclosing_prefix;
+	}
+
+	/// The environment of whatever scope is currently executing. During a native call this is
+	/// the caller's scope.
+	/// Be careful with what gets changed through it: the resolver has already worked out where
+	/// every variable lives, so defining or shadowing names in a scope that isn't yours can make
+	/// resolved lookups find something else, or nothing, once the native returns
+	pub fn current_env(&self) -> EnvRef {
+		self.environment.clone()
+	}
+
+	/// Find a variable by name, starting at the current scope and walking out to the globals
+	pub fn lookup(&self, name: &str) -> Option<LiteralType> {
+		self.environment.borrow().lookup(name)
+	}
+
+	/// Define a variable in the current scope, see current_env for why this needs care
+	pub fn define_in_current(&mut self, name: &str, value: LiteralType) {
+		self.environment.borrow_mut().define(name.to_string(), value);
+	}
+
+	/// Tell the interpreter which file it is running so imports can be found next to it
+	pub fn set_script_path(&mut self, path: &Path) {
+		self.script_dir = path.parent().map(Path::to_path_buf);
+		self.current_file = fs::canonicalize(path).ok();
+	}
+
+	/// Run a program made by compile_source in the global environment without scanning,
+	/// parsing or resolving it again. Its resolved locals become this interpreter's table for
+	/// the program's source, which is only sound because they don't depend on where the program
+	/// runs, see CompiledProgram
+	pub fn load_compiled(&mut self, program: &CompiledProgram) -> Result<(), Error> {
+		self.locals.load(program.source, program.locals.clone());
+		self.private_uses.load(program.source, program.private_uses.clone());
+		self.start_run();
+		self.execute_block(&program.statements, self.global.clone())
+	}
+
+	/// Run the shared prelude. It only declares functions, so nothing it does counts towards
+	/// the statistics
+	fn load_prelude(&mut self) {
+		let prelude = program::prelude();
+		if let Err(e) = self.load_compiled(&prelude) {
+			error!(self, e);
+		}
+		for statement in prelude.statements() {
+			let Stmt::Function(function) = statement else {
+				continue;
+			};
+			let name = function.name.lexeme.clone();
+			// Defining it again forgets its line, which would point into the prelude rather
+			// than anything the user wrote
+			let value = self.global.borrow().lookup(&name);
+			if let Some(value) = value {
+				self.global.borrow_mut().define(name.clone(), value);
+			}
+			self.prelude.insert(name);
+		}
+		self.stats.reset();
+	}
+
+	/// Whether name is one of the functions the prelude defined
+	pub(crate) fn is_prelude(&self, name: &str) -> bool {
+		self.prelude.contains(name)
+	}
+
+	/// The names of the functions the prelude defined
+	pub(crate) fn prelude_names(&self) -> impl Iterator<Item = &String> {
+		self.prelude.iter()
+	}
+
+	/// Warn about breakpoints in the current file that are on lines no statement starts on
+	pub(crate) fn warn_unused_breakpoints(&self, statements: &[Stmt]) {
+		let Some(file) = &self.current_file else {
+			return;
+		};
+		for line in self.debugger.unused_lines(file, statements) {
+			self.error_handler.borrow_mut().report_warning(
+				line,
+				"No statement starts on this line, so its breakpoint will never be hit.",
+			);
+		}
+	}
+
+	/// Forget a cached module so the next import reads and runs the file again. Returns whether
+	/// the module had been imported
+	pub fn invalidate_module(&mut self, path: &Path) -> bool {
+		match fs::canonicalize(path) {
+			Ok(path) => self.modules.remove(&path).is_some(),
+			Err(_) => false,
+		}
+	}
+
+	/// The statements of a module that was imported successfully
+	pub fn module_statements(&self, path: &Path) -> Option<Rc<Vec<Stmt>>> {
+		match self.modules.get(&fs::canonicalize(path).ok()?) {
+			Some(Module::Loaded(statements)) => Some(Rc::clone(statements)),
+			_ => None,
+		}
+	}
+
+	/// Scan, parse, resolve and run a module file in the global environment
+	fn load_module_file(&mut self, path: &Path) -> Result<Rc<Vec<Stmt>>, Error> {
+		let source =
+			fs::read(path).map_err(|e| Error::RuntimeError(Span::default(), e.to_string()))?;
+		let error_handler = Rc::new(RefCell::new(self.error_handler.borrow().sharing_output()));
+		let mut scanner = Scanner::new(source, Rc::clone(&error_handler));
+		scanner.scan_tokens();
+		if error_handler.borrow().had_syntax_error {
+			return Err(Error::RuntimeError(Span::default(), "it has syntax errors.".to_string()));
+		}
+		let statements = Parser::new(scanner.tokens, Rc::clone(&error_handler))
+			.max_nesting(self.max_nesting)
+			.parse();
+		if error_handler.borrow().had_syntax_error {
+			return Err(Error::RuntimeError(Span::default(), "it has parse errors.".to_string()));
+		}
+		Resolver::new(self, Rc::clone(&error_handler)).resolve_block(&statements);
+		if error_handler.borrow().had_resolve_error {
+			return Err(Error::RuntimeError(Span::default(), "it has resolver errors.".to_string()));
+		}
+
+		let statements = Rc::new(statements);
+		let enclosing_dir = self.script_dir.replace(path.parent().unwrap_or(path).to_path_buf());
+		let enclosing_file = self.current_file.replace(path.to_path_buf());
+		self.warn_unused_breakpoints(&statements);
+		let result = self.execute_block(&statements, self.global.clone());
+		self.script_dir = enclosing_dir;
+		self.current_file = enclosing_file;
+		result.map(|_| statements)
+	}
+
+	/// A flag that aborts the running script with an Interrupted error when set. It is checked
+	/// before every statement and loop iteration, and cleared once the interrupt is raised
+	pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+		self.interrupt.clone()
+	}
+
+	/// Raise an Interrupted error if the interrupt flag has been set
+	fn check_interrupt(&self) -> Result<(), Error> {
+		if self.interrupt.swap(false, Ordering::Relaxed) {
+			return Err(Error::Interrupted);
+		}
+		Ok(())
+	}
+
+	/// The limits this interpreter was built with
+	pub fn limits(&self) -> Limits {
+		self.limits
+	}
+
+	/// The code the script asked to exit with by calling exit(), None if it hasn't
+	pub fn exit_code(&self) -> Option<i32> {
+		self.exit_code
+	}
+
+	/// The limit that stopped the last run, if one did
+	pub fn limit_hit(&self) -> Option<Limit> {
+		self.limit_hit.get()
+	}
+
+	/// Note that a limit was hit and make the error that stops the script
+	pub(crate) fn exceeded(&self, line: u32, limit: Limit, message: String) -> Error {
+		self.limit_hit.set(Some(limit));
+		Error::LimitExceeded(line, message)
+	}
+
+	/// Start counting steps and time for a new run
+	fn start_run(&mut self) {
+		self.steps = 0;
+		self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+		self.limit_hit.set(None);
+	}
+
+	/// Raise an error once the run has used up its step budget or time
+	fn check_limits(&mut self, stmt: &Stmt) -> Result<(), Error> {
+		self.take_step(|| Self::first_line(stmt))
+	}
+
+	/// Count a step against the limits, line is only worked out if one of them is hit
+	fn take_step(&mut self, line: impl Fn() -> u32) -> Result<(), Error> {
+		self.steps += 1;
+		if let Some(max) = self.limits.max_steps {
+			if self.steps > max {
+				let message = format!("Ran out of steps after {} statements.", max);
+				return Err(self.exceeded(line(), Limit::Steps(max), message));
+			}
+		}
+		// Reading the clock every statement would slow everything down
+		if let (Some(deadline), 0) = (self.deadline, self.steps % 256) {
+			if Instant::now() >= deadline {
+				let timeout = self.limits.timeout.unwrap_or_default();
+				let message = format!("Timed out after {} seconds.", timeout.as_secs_f64());
+				return Err(self.exceeded(line(), Limit::Timeout(timeout), message));
+			}
+		}
+		Ok(())
+	}
+
+	/// Count one time round a loop that a native runs for the script. Each one is a step, so
+	/// natives calling back many times can't get round the step budget, the timeout or Ctrl-C
+	/// even when what they call is another native
+	pub(crate) fn native_iteration(&mut self) -> Result<(), Error> {
+		self.stats.loop_iterations += 1;
+		self.check_interrupt()?;
+		self.take_step(|| 0)
+	}
+
+	/// The line a statement starts on, looking inside blocks for the first statement with one
+	fn first_line(stmt: &Stmt) -> u32 {
+		let mut line = None;
+		stmt.walk(&mut |stmt| line = line.or(stmt.line()));
+		line.unwrap_or(0)
+	}
+
+	/// Returns the counters gathered since the interpreter was created or last reset
+	pub fn stats(&self) -> Stats {
+		self.stats
+	}
+
+	pub fn reset_stats(&mut self) {
+		self.stats.reset();
+	}
+
+	/// Track an assertion that held
+	pub(crate) fn record_assertion(&mut self) {
+		self.stats.assertions_passed += 1;
+	}
+
+	/// Track a native function call
+	pub(crate) fn record_native_call(&mut self) {
+		self.stats.native_calls += 1;
+	}
+
+	/// Track entering a JASN function, must be paired with exit_call
+	pub(crate) fn enter_call(&mut self) -> Result<(), Error> {
+		if let Some(max) = self.limits.max_call_depth {
+			if self.call_depth >= max {
+				let message = format!("Call depth limit of {} reached.", max);
+				return Err(self.exceeded(0, Limit::CallDepth(max), message));
+			}
+		}
+		// Nesting is counted per call, recursion is a different kind of depth
+		self.caller_depths.push(std::mem::take(&mut self.depth));
+		self.stats.function_calls += 1;
+		self.call_depth += 1;
+		self.stats.max_call_depth = self.stats.max_call_depth.max(self.call_depth);
+		Ok(())
+	}
+
+	pub(crate) fn exit_call(&mut self) {
+		self.depth = self.caller_depths.pop().unwrap_or_default();
+		self.call_depth -= 1;
+	}
+
+	/// Interpret a list of statements,
+	/// This is the main entry point for the interpreter. Execution stops at the first runtime
+	/// error unless the interpreter was built with continue_on_error
+	pub fn interpret(&mut self, statements: Vec<Stmt>) {
+		self.start_run();
+		for stmt in statements {
+			if let Err(e) = self.execute(&stmt) {
+				if let Error::Exit(code) = e {
+					self.exit_code = Some(code);
+					return;
+				}
+				// Carrying on past a limit would defeat it
+				let limited = matches!(e, Error::LimitExceeded(..));
+				error!(self, e);
+				if !self.continue_on_error || limited {
+					return;
+				}
+			}
+		}
+	}
+
+	/// Scan, parse, resolve and run a piece of source code, capturing what it prints instead of
+	/// writing it to the output. Errors are still reported where this interpreter's error handler
+	/// reports them, and collected in the result as well
+	pub fn run_snippet(&mut self, source: &str) -> SnippetResult {
+		let buffer = Rc::new(RefCell::new(Vec::new()));
+		let output = std::mem::replace(&mut self.output, buffer.clone());
+		let error_handler = Rc::new(RefCell::new(self.error_handler.borrow().sharing_output()));
+		let enclosing_handler = std::mem::replace(&mut self.error_handler, error_handler.clone());
+
+		let value = self.run_snippet_statements(source);
+
+		self.output = output;
+		self.error_handler = enclosing_handler;
+		let diagnostics = std::mem::take(&mut error_handler.borrow_mut().diagnostics);
+		let output = String::from_utf8_lossy(&buffer.borrow()).into_owned();
+		SnippetResult { value, output, diagnostics }
+	}
+
+	fn run_snippet_statements(&mut self, source: &str) -> LiteralType {
+		let error_handler = Rc::clone(&self.error_handler);
+		let mut scanner = Scanner::new(source.as_bytes().to_vec(), Rc::clone(&error_handler));
+		scanner.scan_tokens();
+		if error_handler.borrow().had_syntax_error {
+			return LiteralType::Null;
+		}
+		let statements = Parser::new(scanner.tokens, Rc::clone(&error_handler))
+			.max_nesting(self.max_nesting)
+			.parse();
+		if error_handler.borrow().had_syntax_error {
+			return LiteralType::Null;
+		}
+		Resolver::new(self, Rc::clone(&error_handler)).resolve_block(&statements);
+		// The resolver's bindings are incomplete, interpreting now would look up the wrong
+		// variables
+		if error_handler.borrow().had_resolve_error {
+			return LiteralType::Null;
+		}
+
+		self.start_run();
+		let mut value = LiteralType::Null;
+		let last = statements.len().saturating_sub(1);
+		for (i, stmt) in statements.iter().enumerate() {
+			// The final expression is evaluated rather than executed so its value can be kept
+			let result = match stmt {
+				Stmt::Expression(expression) if i == last => {
+					self.stats.statements += 1;
+					self.check_interrupt()
+						.and_then(|_| self.check_limits(stmt))
+						.and_then(|_| self.evaluate(&expression.expression))
+						.map(|result| value = result)
+				},
+				_ => self.execute(stmt),
+			};
+			if let Err(e) = result {
+				if let Error::Exit(code) = e {
+					self.exit_code = Some(code);
+					break;
+				}
+				let limited = matches!(e, Error::LimitExceeded(..));
+				error!(self, e);
+				if !self.continue_on_error || limited {
+					break;
+				}
+			}
+		}
+		value
+	}
+
+	/// Execute a statement
+	fn execute(&mut self, stmt: &Stmt) -> Result<(), Error> {
+		self.stats.statements += 1;
+		self.check_interrupt()?;
+		self.check_limits(stmt)?;
+		if self.debugger.is_active() {
+			if let Some(line) = stmt.line() {
+				if self.debugger.should_pause(self.current_file.as_deref(), line) {
+					debugger::prompt(self, Some(line))?;
+				}
+			}
+		}
+		stmt.accept(self)
+	}
+
+	/// Evaluate an expression
+	fn evaluate(&mut self, expr: &Expr) -> Result<LiteralType, Error> {
+		self.stats.expressions += 1;
+		if self.depth >= self.max_nesting {
+			let span = expr.token().map_or(Span::default(), Token::span);
+			return Err(Error::RuntimeError(span, "Expression too deeply nested.".to_string()));
+		}
+		self.depth += 1;
+		let value = expr.accept(self);
+		self.depth -= 1;
+		value
+	}
+
+	/// Execute a block of statements, throwing an error if one occurs
+	pub(crate) fn execute_block(
+		&mut self,
+		statements: &Vec<Stmt>,
+		environment: EnvRef,
+	) -> Result<(), Error> {
+		let previous = self.environment.clone();
+		self.environment = environment;
+		for stmt in statements {
+			if let Err(e) = self.execute(stmt) {
+				// Return to the previous environment before passing the error up
+				self.environment = previous;
+				return Err(e);
+			}
+		}
+		self.environment = previous;
+		Ok(())
+	}
+
+	/// Run the statements of an if expression branch and evaluate its value
+	fn evaluate_branch(&mut self, branch: &Branch) -> Result<LiteralType, Error> {
+		for statement in &branch.statements {
+			self.execute(statement)?;
+		}
+		match &branch.value {
+			Some(value) => self.evaluate(value),
+			None => Ok(LiteralType::Null),
+		}
+	}
+
+	/// Resolve a variable in the current scope
+	pub fn resolve(&mut self, name: &Token, depth: u64) -> Result<(), Error> {
+		self.locals.insert(name, depth);
+		Ok(())
+	}
+
+	/// Append to a string variable without copying it when a statement has the shape `s = s + x`
+	/// or `s += x`. Building a string in a loop is otherwise quadratic as every `+` allocates a
+	/// new string. Returns false if the assignment doesn't fit that shape and should be
+	/// evaluated normally
+	fn append_in_place(&mut self, assign: &Assign) -> Result<bool, Error> {
+		let Expr::Binary(binary) = &assign.value else {
+			return Ok(false);
+		};
+		let Expr::Variable(variable) = &binary.left else {
+			return Ok(false);
+		};
+		// The right side must not be able to touch the variable while we evaluate it
+		if !matches!(binary.operator.token_type, TokenType::Plus | TokenType::PlusEqual) ||
+			variable.name.lexeme != assign.name.lexeme ||
+			binary.right.has_side_effects()
+		{
+			return Ok(false);
+		}
+		let distance = self.locals.get(&assign.name).copied();
+		if self.locals.get(&variable.name).copied() != distance {
+			return Ok(false);
+		}
+
+		let environment =
+			if distance.is_some() { self.environment.clone() } else { self.global.clone() };
+		let name = &assign.name.lexeme;
+		let length =
+			environment
+				.borrow_mut()
+				.update_at(distance.unwrap_or(0), name, |value| match value {
+					LiteralType::String(s) => Some(s.len()),
+					_ => None,
+				});
+		let Some(Some(length)) = length else {
+			return Ok(false);
+		};
+
+		let piece = self.evaluate(&binary.right)?.to_string();
+		self.check_string_length(binary.operator.line, length + piece.len())?;
+		environment.borrow_mut().update_at(distance.unwrap_or(0), name, |value| {
+			if let LiteralType::String(s) = value {
+				s.push_str(&piece);
+			}
+		});
+		Ok(true)
+	}
+
+	/// Stop a string from being built past max_string_length, the length is worked out from the
+	/// operands before anything gets allocated
+	pub(crate) fn check_string_length(&self, line: u32, length: usize) -> Result<(), Error> {
+		match self.limits.max_string_length {
+			Some(max) if length > max => Err(self.exceeded(
+				line,
+				Limit::StringLength(max),
+				format!(
+					"String of {} bytes would exceed the maximum string length of {} bytes.",
+					length, max
+				),
+			)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Check an index value is a whole number that falls inside an array or string of the given
+	/// length. Negative indices count back from the end, so -1 is the last element. Kind names
+	/// what is being indexed in error messages
+	fn checked_index(
+		bracket: &Token,
+		kind: &str,
+		index: &LiteralType,
+		length: usize,
+	) -> Result<usize, Error> {
+		let LiteralType::Number(n) = index else {
+			return Err(Error::RuntimeError(
+				bracket.span(),
+				format!("{} index must be a number.", kind),
+			));
+		};
+		if n.fract() != 0.0 {
+			return Err(Error::RuntimeError(
+				bracket.span(),
+				format!("{} index must be a whole number but found {}.", kind, n),
+			));
+		}
+		let idx = if *n < 0.0 { *n + length as f64 } else { *n };
+		if idx < 0.0 || idx >= length as f64 {
+			return Err(Error::RuntimeError(
+				bracket.span(),
+				format!("{} index {} is out of bounds for length {}.", kind, n, length),
+			));
+		}
+		Ok(idx as usize)
+	}
+
+	/// Check a map key is a string
+	fn map_key(bracket: &Token, key: &LiteralType) -> Result<String, Error> {
+		match key {
+			LiteralType::String(key) => Ok(key.clone()),
+			_ => Err(Error::RuntimeError(
+				bracket.span(),
+				format!("Map keys must be strings but found a {}.", key.type_name()),
+			)),
+		}
+	}
+
+	/// Underscore names are private to their class, the resolver noted every use of one that
+	/// isn't through this along with the class it is written in. Maps use the same syntax for
+	/// their keys, so this can only be checked once the object turns out to be an instance
+	fn check_private(&mut self, name: &Token, instance: &JasnInstanceRef) -> Result<(), Error> {
+		if !name.lexeme.starts_with('_') {
+			return Ok(());
+		}
+		let Some(inside) = self.private_uses.get(name).cloned() else {
+			return Ok(());
+		};
+		let class = &instance.borrow().class;
+		if inside.as_ref().is_some_and(|inside| class.is_or_inherits(inside)) {
+			return Ok(());
+		}
+		let message = format!(
+			"'{}' is private to {}, it can only be used inside the class.",
+			name.lexeme, class.name
+		);
+		match self.private_access {
+			PrivateAccess::Error => Err(Error::RuntimeError(name.span(), message)),
+			PrivateAccess::Warn => {
+				self.error_handler.borrow_mut().report_warning(name.line, &message);
+				self.private_uses.remove(name);
+				Ok(())
+			},
+		}
+	}
+
+	/// Read a key out of a map, a missing key does what the missing_field policy says
+	fn read_key(&self, map: &MapRef, token: &Token, key: &str) -> Result<LiteralType, Error> {
+		match (map.borrow().get(key), self.missing_field) {
+			(Some(value), _) => Ok(value.clone()),
+			(None, MissingField::Null) => Ok(LiteralType::Null),
+			(None, MissingField::Error) => Err(Error::RuntimeError(
+				token.span(),
+				format!("There's no key '{}' in the map.", key),
+			)),
+		}
+	}
+
+	/// Read a field or method of an instance, a missing one does what the missing_field policy
+	/// says
+	fn read_field(&self, instance: &JasnInstanceRef, name: &Token) -> Result<LiteralType, Error> {
+		match self.missing_field {
+			MissingField::Error => JasnInstance::get(instance, name),
+			MissingField::Null =>
+				Ok(JasnInstance::lookup(instance, &name.lexeme).unwrap_or(LiteralType::Null)),
+		}
+	}
+
+	/// Read one index of a chain like grid[i][j] where dimension says which index of the chain
+	/// this is. Each level reads straight out of the array before it, nothing is copied
+	fn index_dimension(
+		&mut self,
+		index: &Index,
+		dimension: usize,
+		dimensions: usize,
+	) -> Result<LiteralType, Error> {
+		let array_value = match &index.object {
+			Expr::Index(inner) if dimension > 1 => {
+				self.stats.expressions += 1;
+				self.index_dimension(inner, dimension - 1, dimensions)?
+			},
+			object => self.evaluate(object)?,
+		};
+		let index_value = self.evaluate(&index.index)?;
+
+		let result = self.read_index(&index.bracket, array_value, &index_value);
+		// Say which index of the chain failed, the message already names the value and length
+		match result {
+			Err(Error::RuntimeError(span, message)) if dimensions > 1 => Err(Error::RuntimeError(
+				span,
+				format!(
+					"{} at the {} of {} indices.",
+					message.trim_end_matches('.'),
+					ordinal(dimension),
+					dimensions
+				),
+			)),
+			result => result,
+		}
+	}
+
+	/// Read the element at index_value out of an array or string, or the value at a key of a map
+	fn read_index(
+		&self,
+		bracket: &Token,
+		array_value: LiteralType,
+		index_value: &LiteralType,
+	) -> Result<LiteralType, Error> {
+		match array_value {
+			LiteralType::Array(elements) => {
+				let elements = elements.borrow();
+				let idx = Self::checked_index(bracket, "Array", index_value, elements.len())?;
+				Ok(elements[idx].clone())
+			},
+			// Strings are indexed by character rather than byte, so every index lands on a
+			// whole character
+			LiteralType::String(string) => {
+				let length = string.chars().count();
+				let idx = Self::checked_index(bracket, "String", index_value, length)?;
+				Ok(LiteralType::String(string.chars().nth(idx).unwrap_or_default().to_string()))
+			},
+			LiteralType::Map(map) =>
+				self.read_key(&map, bracket, &Self::map_key(bracket, index_value)?),
+			_ => Err(Error::RuntimeError(
+				bracket.span(),
+				format!(
+					"Only arrays, strings and maps can be indexed, not a {}.",
+					array_value.type_name()
+				),
+			)),
+		}
+	}
+
+	/// Work out which elements a slice covers. Bounds must be whole numbers, negative ones count
+	/// back from the end like indices do, and anything past either end is clamped to it. A
+	/// start at or after the end gives an empty range
+	fn slice_range(
+		bracket: &Token,
+		start: Option<LiteralType>,
+		end: Option<LiteralType>,
+		length: usize,
+	) -> Result<Range<usize>, Error> {
+		let bound = |value: Option<LiteralType>, default: usize| -> Result<usize, Error> {
+			let Some(value) = value else {
+				return Ok(default);
+			};
+			let LiteralType::Number(n) = value else {
+				return Err(Error::RuntimeError(
+					bracket.span(),
+					format!("Slice bounds must be numbers but found a {}.", value.type_name()),
+				));
+			};
+			if n.fract() != 0.0 {
+				return Err(Error::RuntimeError(
+					bracket.span(),
+					format!("Slice bounds must be whole numbers but found {}.", n),
+				));
+			}
+			let n = if n < 0.0 { n + length as f64 } else { n };
+			Ok(n.clamp(0.0, length as f64) as usize)
+		};
+		let start = bound(start, 0)?;
+		let end = bound(end, length)?;
+		Ok(if start < end { start..end } else { 0..0 })
+	}
+
+	/// Apply a binary operator to two values that have already been evaluated
+	fn binary_operation(
+		&mut self,
+		operator: &Token,
+		left: LiteralType,
+		right: LiteralType,
+	) -> Result<LiteralType, Error> {
+		let span = operator.span();
+		match operator.token_type {
+			TokenType::BangEqual => Ok(LiteralType::Bool(left != right)),
+			TokenType::EqualEqual => Ok(LiteralType::Bool(left == right)),
+			TokenType::Greater => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Bool(left_num > right_num))
+			},
+			TokenType::GreaterEqual => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Bool(left_num >= right_num))
+			},
+			TokenType::Less => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Bool(left_num < right_num))
+			},
+			TokenType::LessEqual => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Bool(left_num <= right_num))
+			},
+			TokenType::Minus | TokenType::MinusEqual | TokenType::MinusMinus => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Number(left_num - right_num))
+			},
+			TokenType::Slash | TokenType::SlashEqual => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				if right_num == 0.0 {
+					return Err(Error::RuntimeError(span, "Division by zero.".to_string()));
+				}
+				Ok(LiteralType::Number(left_num / right_num))
+			},
+			TokenType::Star | TokenType::StarEqual => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				Ok(LiteralType::Number(left_num * right_num))
+			},
+			TokenType::Plus | TokenType::PlusEqual | TokenType::PlusPlus => {
+				match (left.clone(), right.clone()) {
+					(LiteralType::Number(left_num), LiteralType::Number(right_num)) =>
+						Ok(LiteralType::Number(left_num + right_num)),
+					(LiteralType::String(left_str), _) => {
+						let right_str: String = right.to_string();
+						self.check_string_length(span.line, left_str.len() + right_str.len())?;
+						Ok(LiteralType::String(format!("{}{}", left_str, right_str)))
+					},
+					(_, LiteralType::String(right_str)) => {
+						let left_str: String = left.to_string();
+						self.check_string_length(span.line, left_str.len() + right_str.len())?;
+						Ok(LiteralType::String(format!("{}{}", left_str, right_str)))
+					},
+					_ => Err(Error::RuntimeError(
+						span,
+						format!(
+							"Operands of '{}' must be two numbers or include a string but have types {} and {}.",
+							operator.lexeme,
+							left.type_name(),
+							right.type_name()
+						),
+					)),
+				}
+			},
+			TokenType::Modulo => {
+				let (left_num, right_num) = number_operands(operator, left, right)?;
+				if right_num == 0.0 {
+					return Err(Error::RuntimeError(span, "Modulo by zero.".to_string()));
+				}
+				Ok(LiteralType::Number(left_num % right_num))
+			},
+			_ => Err(Error::RuntimeError(span, "Invalid binary operator.".to_string())),
 		}
 	}
 
-	/// Interpret a list of statements,
-	/// This is the main entry point for the interpreter
-	pub fn interpret(&mut self, statements: Vec<Stmt>) {
-		for stmt in statements {
-			if let Err(e) = self.execute(&stmt) {
-				error!(self, e);
-			}
+	/// Work out the value an assignment to a field or element stores. The parser turns
+	/// `target += value` and `target++` into an assignment of a binary expression with the target
+	/// on the left, marked by its operator. Those read the slot the assignment already found
+	/// instead of evaluating the target again, and give back what was there before
+	fn assigned_value(
+		&mut self,
+		value: &Expr,
+		read: impl FnOnce(&mut Self) -> Result<LiteralType, Error>,
+	) -> Result<(Option<LiteralType>, LiteralType), Error> {
+		let Expr::Binary(binary) = value else {
+			return Ok((None, self.evaluate(value)?));
+		};
+		let operator = &binary.operator;
+		let step = matches!(operator.token_type, TokenType::PlusPlus | TokenType::MinusMinus);
+		let compound = matches!(
+			operator.token_type,
+			TokenType::PlusEqual |
+				TokenType::MinusEqual |
+				TokenType::StarEqual |
+				TokenType::SlashEqual
+		);
+		if !step && !compound {
+			return Ok((None, self.evaluate(value)?));
 		}
+		let current = read(self)?;
+		if step {
+			Self::check_step_operand(operator, &current)?;
+		}
+		let right = self.evaluate(&binary.right)?;
+		let value = self.binary_operation(operator, current.clone(), right)?;
+		Ok((Some(current), value))
 	}
 
-	/// Execute a statement
-	fn execute(&mut self, stmt: &Stmt) -> Result<(), Error> {
-		stmt.accept(self)
+	/// ++ and -- only work on numbers, even though + would join a string
+	fn check_step_operand(operator: &Token, value: &LiteralType) -> Result<(), Error> {
+		if matches!(value, LiteralType::Number(_)) {
+			return Ok(());
+		}
+		Err(Error::RuntimeError(
+			operator.span(),
+			format!(
+				"Operand of '{}' must be a number but has type {}.",
+				operator.lexeme,
+				value.type_name()
+			),
+		))
 	}
 
-	/// Evaluate an expression
-	fn evaluate(&mut self, expr: &Expr) -> Result<LiteralType, Error> {
-		expr.accept(self)
+	/// Assign to a field of an instance or a key of a map, giving back the value it had before
+	/// if the assignment is compound as well as the value stored
+	fn set_field(&mut self, set: &Set) -> Result<(Option<LiteralType>, LiteralType), Error> {
+		match self.evaluate(&set.object)? {
+			LiteralType::Callable(Callable::Instance(instance)) => {
+				self.check_private(&set.name, &instance)?;
+				let (previous, value) = self.assigned_value(&set.value, |interpreter| {
+					interpreter.read_field(&instance, &set.name)
+				})?;
+				instance.borrow_mut().set(&set.name.lexeme, value.clone());
+				Ok((previous, value))
+			},
+			LiteralType::Map(map) => {
+				let (previous, value) = self.assigned_value(&set.value, |interpreter| {
+					interpreter.read_key(&map, &set.name, &set.name.lexeme)
+				})?;
+				map.borrow_mut().insert(set.name.lexeme.clone(), value.clone());
+				Ok((previous, value))
+			},
+			_ => Err(Error::RuntimeError(
+				set.name.span(),
+				"Only instances and maps have fields.".to_string(),
+			)),
+		}
 	}
 
-	/// Execute a block of statements, throwing an error if one occurs
-	pub(crate) fn execute_block(
+	/// Assign to an element of an array or a key of a map, the object and index are evaluated
+	/// once even when the assignment is compound
+	fn assign_index(
 		&mut self,
-		statements: &Vec<Stmt>,
-		environment: EnvRef,
-	) -> Result<(), Error> {
-		let previous = self.environment.clone();
-		self.environment = environment;
-		for stmt in statements {
-			if let Err(e) = self.execute(stmt) {
-				// Throw the error without exiting as we want to return to the previous environment
-				self.environment = previous;
-				error!(self, e.clone());
-				return Err(e);
-			}
+		assign_index: &AssignIndex,
+	) -> Result<(Option<LiteralType>, LiteralType), Error> {
+		let array_val = self.evaluate(&assign_index.object)?;
+		let index_val = self.evaluate(&assign_index.index)?;
+		let (previous, value_val) = self.assigned_value(&assign_index.value, |interpreter| {
+			interpreter.read_index(&assign_index.bracket, array_val.clone(), &index_val)
+		})?;
+
+		if let LiteralType::String(_) = array_val {
+			return Err(Error::RuntimeError(
+				assign_index.bracket.span(),
+				"Strings can't be changed by assigning to an index.".to_string(),
+			));
 		}
-		self.environment = previous;
-		Ok(())
+		if let LiteralType::Map(map) = array_val {
+			let key = Self::map_key(&assign_index.bracket, &index_val)?;
+			map.borrow_mut().insert(key, value_val.clone());
+			return Ok((previous, value_val));
+		}
+		let LiteralType::Array(elements) = array_val else {
+			return Err(Error::RuntimeError(
+				assign_index.bracket.span(),
+				"Attempted to index a non-array value.".to_string(),
+			));
+		};
+		let idx = Self::checked_index(
+			&assign_index.bracket,
+			"Array",
+			&index_val,
+			elements.borrow().len(),
+		)?;
+		elements.borrow_mut()[idx] = value_val.clone();
+		Ok((previous, value_val))
 	}
 
-	/// Resolve a variable in the current scope
-	pub fn resolve(&mut self, expr: Expr, depth: u64) -> Result<(), Error> {
-		self.locals.insert(expr, depth);
-		Ok(())
+	/// Write a value back to the variable, field or array element an expression refers to
+	fn store(&mut self, target: &Expr, value: LiteralType) -> Result<(), Error> {
+		match target {
+			Expr::Variable(variable) =>
+				if let Some(distance) = self.locals.get(&variable.name) {
+					self.environment.borrow_mut().assign_at(*distance, &variable.name, value)
+				} else {
+					self.global.borrow_mut().assign(&variable.name, value)
+				},
+			Expr::Get(get) => match self.evaluate(&get.object)? {
+				LiteralType::Callable(Callable::Instance(instance)) => {
+					self.check_private(&get.name, &instance)?;
+					instance.borrow_mut().set(&get.name.lexeme, value);
+					Ok(())
+				},
+				LiteralType::Map(map) => {
+					map.borrow_mut().insert(get.name.lexeme.clone(), value);
+					Ok(())
+				},
+				_ => Err(Error::RuntimeError(
+					get.name.span(),
+					"Only instances and maps have fields.".to_string(),
+				)),
+			},
+			Expr::Index(index) => match self.evaluate(&index.object)? {
+				LiteralType::Array(array) => {
+					let idx = Self::checked_index(
+						&index.bracket,
+						"Array",
+						&self.evaluate(&index.index)?,
+						array.borrow().len(),
+					)?;
+					array.borrow_mut()[idx] = value;
+					Ok(())
+				},
+				LiteralType::Map(map) => {
+					let key = Self::map_key(&index.bracket, &self.evaluate(&index.index)?)?;
+					map.borrow_mut().insert(key, value);
+					Ok(())
+				},
+				_ => Err(Error::RuntimeError(
+					index.bracket.span(),
+					"Attempted to index a non-array value.".to_string(),
+				)),
+			},
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				"Can only assign to a variable, field or array element.".to_string(),
+			)),
+		}
 	}
 
 	/// Check if we are looking up a global or local variable
-	fn look_up_variable(&self, name: &Token, expr: &Expr) -> Result<LiteralType, Error> {
-		let distance = self.locals.get(expr);
+	fn look_up_variable(&self, name: &Token) -> Result<LiteralType, Error> {
+		let distance = self.locals.get(name);
 		if let Some(distance) = distance {
 			self.environment.borrow().get_at(*distance, &name.lexeme)
 		} else {
@@ -91,6 +1441,53 @@ impl Interpreter {
 	}
 }
 
+/// The English ordinal of a number, first to tenth in words and 11th, 22nd and so on after that
+fn ordinal(n: usize) -> String {
+	const ORDINALS: [&str; 10] = [
+		"first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth",
+		"tenth",
+	];
+	if let Some(ordinal) = ORDINALS.get(n.wrapping_sub(1)) {
+		return ordinal.to_string();
+	}
+	let suffix = match (n % 10, n % 100) {
+		(_, 11..=13) => "th",
+		(1, _) => "st",
+		(2, _) => "nd",
+		(3, _) => "rd",
+		_ => "th",
+	};
+	format!("{}{}", n, suffix)
+}
+
+/// Check that both operands of an arithmetic or comparison operator are numbers, naming the
+/// operand at fault if not
+fn number_operands(
+	operator: &Token,
+	left: LiteralType,
+	right: LiteralType,
+) -> Result<(f64, f64), Error> {
+	match (left, right) {
+		(LiteralType::Number(left), LiteralType::Number(right)) => Ok((left, right)),
+		(LiteralType::Number(_), right) => Err(Error::RuntimeError(
+			operator.span(),
+			format!(
+				"Right operand of '{}' must be a number but has type {}.",
+				operator.lexeme,
+				right.type_name()
+			),
+		)),
+		(left, _) => Err(Error::RuntimeError(
+			operator.span(),
+			format!(
+				"Left operand of '{}' must be a number but has type {}.",
+				operator.lexeme,
+				left.type_name()
+			),
+		)),
+	}
+}
+
 /// Statement Visitor will visit all types of statements
 /// ^ Lol, what a nothing statement
 impl crate::statements::Visitor for Interpreter {
@@ -99,9 +1496,28 @@ impl crate::statements::Visitor for Interpreter {
 	}
 
 	fn visit_class(&mut self, class: &Class) -> Result<(), Error> {
-		self.environment
-			.borrow_mut()
-			.define(class.name.lexeme.clone(), LiteralType::Null);
+		let superclass = match &class.superclass {
+			Some(superclass) => match self.look_up_variable(&superclass.name)? {
+				LiteralType::Callable(Callable::Class(superclass)) => Some(superclass),
+				_ =>
+					return Err(Error::RuntimeError(
+						superclass.name.span(),
+						"Superclass must be a class.".to_string(),
+					)),
+			},
+			None => None,
+		};
+		self.environment.borrow_mut().declare(&class.name, LiteralType::Null);
+
+		// Methods of a subclass close over an environment holding "super"
+		let enclosing = self.environment.clone();
+		if let Some(superclass) = &superclass {
+			self.environment = Environment::new(Some(enclosing.clone()));
+			self.environment.borrow_mut().define(
+				"super".to_string(),
+				LiteralType::Callable(Callable::Class(superclass.clone())),
+			);
+		}
 
 		// Create the methods
 		let mut methods = HashMap::new();
@@ -109,11 +1525,14 @@ impl crate::statements::Visitor for Interpreter {
 			let function = JasnFunction {
 				declaration: Box::new(method.clone()),
 				closure: self.environment.clone(),
+				class_name: Some(class.name.lexeme.clone()),
+				is_initializer: method.name.lexeme == "init",
 			};
 			methods.insert(method.name.lexeme.clone(), Callable::Function(function));
 		}
 
-		let jasn_class = JasnClass::new(class.name.lexeme.clone(), methods);
+		self.environment = enclosing;
+		let jasn_class = JasnClass::new(class.name.lexeme.clone(), superclass, methods);
 		self.environment
 			.borrow_mut()
 			.assign(&class.name, LiteralType::Callable(Callable::Class(jasn_class)))?;
@@ -121,19 +1540,120 @@ impl crate::statements::Visitor for Interpreter {
 	}
 
 	fn visit_expression(&mut self, expression: &Expression) -> Result<(), Error> {
+		// The value of a statement is thrown away, so string appends can happen in place
+		if let Expr::Assign(assign) = &expression.expression {
+			if self.append_in_place(assign)? {
+				return Ok(());
+			}
+		}
 		let _ = self.evaluate(&expression.expression)?;
 		Ok(())
 	}
 
+	fn visit_global(&mut self, global: &Global) -> Result<(), Error> {
+		let value = self.evaluate(&global.value)?;
+		self.global.borrow_mut().declare(&global.name, value);
+		Ok(())
+	}
+
+	fn visit_import(&mut self, import: &Import) -> Result<(), Error> {
+		let span = import.keyword.span();
+		if self.sandbox {
+			return Err(Error::RuntimeError(
+				span,
+				"import is disabled in sandbox mode.".to_string(),
+			));
+		}
+		let name = import.path.literal.to_string();
+		let path = match &self.script_dir {
+			Some(dir) => dir.join(&name),
+			None => PathBuf::from(&name),
+		};
+		let path = fs::canonicalize(&path)
+			.map_err(|e| Error::RuntimeError(span, format!("Can't import '{}': {}", name, e)))?;
+
+		match self.modules.get(&path) {
+			Some(Module::Loaded(_)) => return Ok(()),
+			Some(Module::Loading) =>
+				return Err(Error::RuntimeError(
+					span,
+					format!("Can't import '{}' while it is still being imported.", name),
+				)),
+			Some(Module::Failed(message)) => return Err(Error::RuntimeError(span, message.clone())),
+			None => (),
+		}
+
+		self.modules.insert(path.clone(), Module::Loading);
+		match self.load_module_file(&path) {
+			Ok(statements) => {
+				self.modules.insert(path, Module::Loaded(statements));
+				Ok(())
+			},
+			Err(Error::RuntimeError(module_span, message)) => {
+				let message = if module_span.line == 0 {
+					format!("Failed to import '{}': {}", name, message)
+				} else {
+					format!("Failed to import '{}': [{}] {}", name, module_span.location(), message)
+				};
+				self.modules.insert(path, Module::Failed(message.clone()));
+				Err(Error::RuntimeError(span, message))
+			},
+			// Interrupts aren't the module's fault, so it can be tried again
+			Err(error) => {
+				self.modules.remove(&path);
+				Err(error)
+			},
+		}
+	}
+
+	fn visit_multi_assign(&mut self, multi_assign: &MultiAssign) -> Result<(), Error> {
+		let span = multi_assign.equals.span();
+		// Every value is worked out before anything is assigned, so `a, b = b, a` swaps
+		let mut values = Vec::with_capacity(multi_assign.values.len());
+		for value in &multi_assign.values {
+			values.push(self.evaluate(value)?);
+		}
+		if values.len() == 1 && multi_assign.targets.len() > 1 {
+			values = match values.pop() {
+				Some(LiteralType::Array(elements)) => elements.borrow().clone(),
+				Some(value) =>
+					return Err(Error::RuntimeError(
+						span,
+						format!(
+							"Expected an array to unpack into {} targets but found {}.",
+							multi_assign.targets.len(),
+							value.render(&self.render_limits)
+						),
+					)),
+				None => unreachable!(),
+			};
+		}
+		if values.len() != multi_assign.targets.len() {
+			return Err(Error::RuntimeError(
+				span,
+				format!(
+					"Can't assign {} values to {} targets.",
+					values.len(),
+					multi_assign.targets.len()
+				),
+			));
+		}
+		for (target, value) in multi_assign.targets.iter().zip(values) {
+			self.store(target, value)?;
+		}
+		Ok(())
+	}
+
 	fn visit_function(&mut self, function: &Function) -> Result<(), Error> {
 		let jasn_function = JasnFunction {
 			declaration: Box::new(function.clone()),
 			closure: self.environment.clone(),
+			class_name: None,
+			is_initializer: false,
 		};
-		self.environment.borrow_mut().define(
-			function.name.lexeme.clone(),
-			LiteralType::Callable(Callable::Function(jasn_function)),
-		);
+		self.environment
+			.borrow_mut()
+			.declare(&function.name, LiteralType::Callable(Callable::Function(jasn_function)));
 		Ok(())
 	}
 
@@ -147,9 +1667,9 @@ impl crate::statements::Visitor for Interpreter {
 	}
 
 	fn visit_print(&mut self, print: &Print) -> Result<(), Error> {
-		let value: String = self.evaluate(&print.expression)?.to_string();
-		println!("{}", value);
-		Ok(())
+		let value: String = self.evaluate(&print.expression)?.render(&self.render_limits);
+		writeln!(self.output.borrow_mut(), "{}", value)
+			.map_err(|e| Error::RuntimeError(Span::default(), format!("Couldn't print: {}", e)))
 	}
 
 	fn visit_return(&mut self, return_stmt: &Return) -> Result<(), Error> {
@@ -166,16 +1686,33 @@ impl crate::statements::Visitor for Interpreter {
 		} else {
 			LiteralType::Null
 		};
-		self.environment.borrow_mut().define(variable.name.lexeme.clone(), value);
+		self.environment.borrow_mut().declare(&variable.name, value);
 		Ok(())
 	}
 
 	fn visit_while(&mut self, while_stmt: &While) -> Result<(), Error> {
 		while self.evaluate(&while_stmt.condition)?.is_truthy() {
-			self.execute(&while_stmt.body)?;
+			self.stats.loop_iterations += 1;
+			self.check_interrupt()?;
+			match self.execute(&while_stmt.body) {
+				Ok(()) | Err(Error::Continue) => (),
+				Err(Error::Break) => break,
+				Err(error) => return Err(error),
+			}
+			if let Some(increment) = &while_stmt.increment {
+				self.evaluate(increment)?;
+			}
 		}
 		Ok(())
 	}
+
+	fn visit_break(&mut self, _break_stmt: &Break) -> Result<(), Error> {
+		Err(Error::Break)
+	}
+
+	fn visit_continue(&mut self, _continue_stmt: &Continue) -> Result<(), Error> {
+		Err(Error::Continue)
+	}
 }
 
 impl crate::expressions::Visitor for Interpreter {
@@ -183,7 +1720,7 @@ impl crate::expressions::Visitor for Interpreter {
 
 	fn visit_assign(&mut self, assign: &Assign) -> Result<Self::Value, Error> {
 		let value: LiteralType = self.evaluate(&assign.value)?;
-		let distance = self.locals.get(&Expr::Assign(Box::new(assign.clone())));
+		let distance = self.locals.get(&assign.name);
 		if let Some(distance) = distance {
 			self.environment
 				.borrow_mut()
@@ -197,75 +1734,7 @@ impl crate::expressions::Visitor for Interpreter {
 	fn visit_binary(&mut self, binary: &Binary) -> Result<Self::Value, Error> {
 		let left = self.evaluate(&binary.left)?;
 		let right = self.evaluate(&binary.right)?;
-		let line = binary.operator.line;
-
-		match binary.operator.token_type {
-			TokenType::BangEqual => Ok(LiteralType::Bool(left != right)),
-			TokenType::EqualEqual => Ok(LiteralType::Bool(left == right)),
-			TokenType::Greater => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Bool(left_num > right_num))
-			},
-			TokenType::GreaterEqual => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Bool(left_num >= right_num))
-			},
-			TokenType::Less => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Bool(left_num < right_num))
-			},
-			TokenType::LessEqual => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Bool(left_num <= right_num))
-			},
-			TokenType::Minus | TokenType::MinusEqual | TokenType::MinusMinus => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Number(left_num - right_num))
-			},
-			TokenType::Slash | TokenType::SlashEqual => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				if right_num == 0.0 {
-					return Err(Error::RuntimeError(line, "Division by zero.".to_string()));
-				}
-				Ok(LiteralType::Number(left_num / right_num))
-			},
-			TokenType::Star | TokenType::StarEqual => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Number(left_num * right_num))
-			},
-			TokenType::Plus | TokenType::PlusEqual | TokenType::PlusPlus => {
-				match (left.clone(), right.clone()) {
-					(LiteralType::Number(left_num), LiteralType::Number(right_num)) =>
-						Ok(LiteralType::Number(left_num + right_num)),
-					(LiteralType::String(left_str), _) => {
-						let right_str: String = right.to_string();
-						Ok(LiteralType::String(format!("{}{}", left_str, right_str)))
-					},
-					(_, LiteralType::String(right_str)) => {
-						let left_str: String = left.to_string();
-						Ok(LiteralType::String(format!("{}{}", left_str, right_str)))
-					},
-					_ => {
-						return Err(Error::RuntimeError(line, "Invalid Operands.".to_string()));
-					},
-				}
-			},
-			TokenType::Modulo => {
-				let left_num: f64 = left.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Number(left_num % right_num))
-			},
-			_ => {
-				return Err(Error::RuntimeError(line, "Invalid binary operator.".to_string()));
-			},
-		}
+		self.binary_operation(&binary.operator, left, right)
 	}
 
 	fn visit_call(&mut self, call: &Call) -> Result<Self::Value, Error> {
@@ -275,60 +1744,58 @@ impl crate::expressions::Visitor for Interpreter {
 			arguments.push(self.evaluate(argument)?);
 		}
 		let function = match callee {
+			// Instances are stored as callables but there is nothing to call on them
+			LiteralType::Callable(Callable::Instance(_)) => Err(Error::RuntimeError(
+				call.paren.span(),
+				"Can only call functions and classes.".to_string(),
+			))?,
 			LiteralType::Callable(callable) => callable,
 			_ => Err(Error::RuntimeError(
-				call.paren.line,
+				call.paren.span(),
 				"Can only call functions and classes.".to_string(),
 			))?,
 		};
 
-		if arguments.len() as u8 != function.arity() {
-			return Err(Error::RuntimeError(
-				call.paren.line,
-				format!("Expected {} arguments but found {}.", function.arity(), arguments.len()),
-			));
-		}
+		function
+			.check_arity(arguments.len())
+			.map_err(|message| Error::RuntimeError(call.paren.span(), message))?;
 
-		function.call(self, arguments)
+		function.call(self, arguments).map_err(|error| match error {
+			// Natives and limits hit on the way into a call don't know where the call is
+			Error::LimitExceeded(0, message) => Error::LimitExceeded(call.paren.line, message),
+			Error::RuntimeError(span, message) if span.line == 0 =>
+				Error::RuntimeError(call.paren.span(), message),
+			error => error,
+		})
 	}
 
+	// A property of a map is the value at that key, so `config.timeout` is `config["timeout"]`
 	fn visit_get(&mut self, get: &Get) -> Result<Self::Value, Error> {
 		let object = self.evaluate(&get.object)?;
+		if let LiteralType::Map(map) = &object {
+			return self.read_key(map, &get.name, &get.name.lexeme);
+		}
 		if let LiteralType::Callable(callable) = object {
 			match callable {
 				Callable::Instance(instance) => {
-					let value = instance.borrow().get(&get.name)?;
-					return Ok(value);
+					self.check_private(&get.name, &instance)?;
+					self.read_field(&instance, &get.name)
 				},
 				_ => Err(Error::RuntimeError(
-					get.name.line,
+					get.name.span(),
 					"Only instances have properties.".to_string(),
 				)),
 			}
 		} else {
 			Err(Error::RuntimeError(
-				get.name.line,
+				get.name.span(),
 				"Only class instances have properties.".to_string(),
 			))
 		}
 	}
 
 	fn visit_set(&mut self, set: &Set) -> Result<Self::Value, Error> {
-		let object = self.evaluate(&set.object)?;
-		match object {
-			LiteralType::Callable(callable) => match callable {
-				Callable::Instance(instance) => {
-					let value = self.evaluate(&set.value)?;
-					instance.borrow_mut().set(&set.name.lexeme, value.clone());
-					return Ok(value);
-				},
-				_ => Err(Error::RuntimeError(
-					set.name.line,
-					"Only instances have fields.".to_string(),
-				)),
-			},
-			_ => Err(Error::RuntimeError(set.name.line, "Only instances have fields.".to_string())),
-		}
+		Ok(self.set_field(set)?.1)
 	}
 
 	fn visit_grouping(&mut self, grouping: &Grouping) -> Result<Self::Value, Error> {
@@ -340,56 +1807,89 @@ impl crate::expressions::Visitor for Interpreter {
 		for value in &array.values {
 			values.push(self.evaluate(value)?);
 		}
-		Ok(LiteralType::Array(values))
+		Ok(LiteralType::array(values))
 	}
 
-	fn visit_index(&mut self, index: &Index) -> Result<Self::Value, Error> {
-		let array_value = self.evaluate(&index.object)?;
-		let index_value = self.evaluate(&index.index)?;
+	fn visit_map(&mut self, map: &Map) -> Result<Self::Value, Error> {
+		let mut entries = BTreeMap::new();
+		for (key, value) in &map.entries {
+			let key = Self::map_key(&map.brace, &self.evaluate(key)?)?;
+			entries.insert(key, self.evaluate(value)?);
+		}
+		Ok(LiteralType::map(entries))
+	}
 
-		if let LiteralType::Array(elements) = array_value {
-			if let LiteralType::Number(n) = index_value {
-				let idx = n as usize;
-				if idx < elements.len() {
-					return Ok(elements[idx].clone());
-				} else {
-					return Err(Error::RuntimeError(0, "Array index out of bounds.".to_string()));
-				}
-			} else {
-				return Err(Error::RuntimeError(0, "Array index must be a number.".to_string()));
+	fn visit_if_expr(&mut self, if_expr: &IfExpr) -> Result<Self::Value, Error> {
+		let branch = if self.evaluate(&if_expr.condition)?.is_truthy() {
+			&if_expr.then_branch
+		} else {
+			match &if_expr.else_branch {
+				Some(else_branch) => else_branch,
+				None => return Ok(LiteralType::Null),
 			}
-		}
+		};
+		let previous = self.environment.clone();
+		self.environment = Environment::new(Some(previous.clone()));
+		let result = self.evaluate_branch(branch);
+		self.environment = previous;
+		result
+	}
 
-		Err(Error::RuntimeError(0, "Attempted to index a non-array value.".to_string()))
+	fn visit_index(&mut self, index: &Index) -> Result<Self::Value, Error> {
+		let mut dimensions = 1;
+		let mut object = &index.object;
+		while let Expr::Index(inner) = object {
+			dimensions += 1;
+			object = &inner.object;
+		}
+		self.index_dimension(index, dimensions, dimensions)
 	}
 
-	// TODO, still doesn't properly assign the value in memory
-	fn visit_assign_index(&mut self, assign_index: &AssignIndex) -> Result<Self::Value, Error> {
-		let array_val = self.evaluate(&assign_index.object)?;
-		let index_val = self.evaluate(&assign_index.index)?;
-		let value_val = self.evaluate(&assign_index.value)?;
-
-		if let LiteralType::Array(mut elements) = array_val {
-			if let LiteralType::Number(n) = index_val {
-				let idx = n as usize;
-				if idx < elements.len() {
-					elements[idx] = value_val.clone();
-					return Ok(value_val);
-				} else {
-					return Err(Error::RuntimeError(0, "Array index out of bounds.".to_string()));
-				}
-			} else {
-				return Err(Error::RuntimeError(0, "Array index must be a number.".to_string()));
-			}
+	fn visit_slice(&mut self, slice: &Slice) -> Result<Self::Value, Error> {
+		let object = self.evaluate(&slice.object)?;
+		let start = slice.start.as_ref().map(|start| self.evaluate(start)).transpose()?;
+		let end = slice.end.as_ref().map(|end| self.evaluate(end)).transpose()?;
+		match object {
+			LiteralType::Array(elements) => {
+				let elements = elements.borrow();
+				let range = Self::slice_range(&slice.bracket, start, end, elements.len())?;
+				Ok(LiteralType::array(elements[range].to_vec()))
+			},
+			LiteralType::String(string) => {
+				let chars: Vec<char> = string.chars().collect();
+				let range = Self::slice_range(&slice.bracket, start, end, chars.len())?;
+				Ok(LiteralType::String(chars[range].iter().collect()))
+			},
+			_ => Err(Error::RuntimeError(
+				slice.bracket.span(),
+				format!("Only arrays and strings can be sliced, not a {}.", object.type_name()),
+			)),
 		}
+	}
 
-		Err(Error::RuntimeError(0, "Attempted to index a non-array value.".to_string()))
+	fn visit_assign_index(&mut self, assign_index: &AssignIndex) -> Result<Self::Value, Error> {
+		Ok(self.assign_index(assign_index)?.1)
 	}
 
 	fn visit_literal(&mut self, literal: &Literal) -> Result<Self::Value, Error> {
 		Ok(literal.value.clone())
 	}
 
+	fn visit_postfix(&mut self, postfix: &Postfix) -> Result<Self::Value, Error> {
+		let previous = match &postfix.assign {
+			Expr::Set(set) => self.set_field(set)?.0,
+			Expr::AssignIndex(assign_index) => self.assign_index(assign_index)?.0,
+			// Reading a variable a second time can't run anything twice
+			assign => {
+				let value = self.evaluate(&postfix.target)?;
+				Self::check_step_operand(&postfix.operator, &value)?;
+				self.evaluate(assign)?;
+				Some(value)
+			},
+		};
+		Ok(previous.unwrap_or(LiteralType::Null))
+	}
+
 	fn visit_logical(&mut self, logical: &Logical) -> Result<Self::Value, Error> {
 		let left = self.evaluate(&logical.left)?;
 
@@ -397,34 +1897,64 @@ impl crate::expressions::Visitor for Interpreter {
 			if left.is_truthy() {
 				return Ok(left);
 			}
-		} else {
-			if !left.is_truthy() {
-				return Ok(left);
-			}
+		} else if !left.is_truthy() {
+			return Ok(left);
 		}
 
 		self.evaluate(&logical.right)
 	}
 
 	fn visit_super(&mut self, super_: &Super) -> Result<Self::Value, Error> {
-		todo!()
+		let span = super_.keyword.span();
+		let distance = *self.locals.get(&super_.keyword).ok_or_else(|| {
+			Error::RuntimeError(span, "Can't use 'super' outside of a subclass.".to_string())
+		})?;
+		let superclass = self.environment.borrow().get_at(distance, "super")?;
+		// "this" is always bound in the environment just inside the one holding "super"
+		let object = self.environment.borrow().get_at(distance - 1, "this")?;
+		let (
+			LiteralType::Callable(Callable::Class(superclass)),
+			LiteralType::Callable(Callable::Instance(instance)),
+		) = (superclass, object)
+		else {
+			return Err(Error::RuntimeError(
+				span,
+				"Can't use 'super' outside of a subclass.".to_string(),
+			));
+		};
+		match superclass.find_method(&super_.method.lexeme) {
+			Some(Callable::Function(method)) =>
+				Ok(LiteralType::Callable(Callable::Function(method.bind(instance)))),
+			Some(method) => Ok(LiteralType::Callable(method)),
+			None => Err(Error::RuntimeError(
+				super_.method.span(),
+				format!("Undefined property '{}'", super_.method.lexeme),
+			)),
+		}
 	}
 
 	fn visit_this(&mut self, this: &This) -> Result<Self::Value, Error> {
-		todo!()
+		self.look_up_variable(&this.keyword)
 	}
 
 	fn visit_unary(&mut self, unary: &Unary) -> Result<Self::Value, Error> {
 		let right: Self::Value = self.evaluate(&unary.right)?;
-		let line = unary.operator.line;
+		let span = unary.operator.span();
 
 		match &unary.operator.token_type {
-			TokenType::Minus => {
-				let right_num: f64 = right.try_into().map_err(|e| Error::RuntimeError(line, e))?;
-				Ok(LiteralType::Number(-right_num))
+			TokenType::Minus => match right {
+				LiteralType::Number(right_num) => Ok(LiteralType::Number(-right_num)),
+				_ => Err(Error::RuntimeError(
+					span,
+					format!(
+						"Operand of '{}' must be a number but has type {}.",
+						unary.operator.lexeme,
+						right.type_name()
+					),
+				)),
 			},
 			TokenType::Bang => Ok(LiteralType::Bool(!right.is_truthy())),
-			_ => Err(Error::RuntimeError(line, "Invalid unary operator.".to_string())),
+			_ => Err(Error::RuntimeError(span, "Invalid unary operator.".to_string())),
 		}
 	}
 
@@ -433,6 +1963,6 @@ impl crate::expressions::Visitor for Interpreter {
 		&mut self,
 		variable: &crate::expressions::Variable,
 	) -> Result<Self::Value, Error> {
-		self.look_up_variable(&variable.name, &Expr::Variable(variable.clone()))
+		self.look_up_variable(&variable.name)
 	}
 }
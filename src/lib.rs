@@ -1,97 +1,452 @@
 use crate::{
-	environment::{EnvRef, Environment},
+	deps::Dependencies,
 	error_handler::ErrorHandler,
-	interpreter::Interpreter,
-	native_functions::NativeFunctions,
+	interpreter::{Input, Interpreter, Limits, MissingField, Output, PrivateAccess},
+	native_functions::LogLevel,
 	resolver::Resolver,
+	state::State,
+	token::LiteralType,
 };
 use parser::Parser;
 use scanner::Scanner;
-use std::{cell::RefCell, fs::File, io, io::prelude::*, path::Path, process, rc::Rc};
-
-mod callable;
-mod environment;
-mod error_handler;
-mod expressions;
-mod interpreter;
-mod native_functions;
-mod parser;
-mod resolver;
-mod scanner;
-mod statements;
-mod token;
+use std::{
+	cell::RefCell,
+	env, fs, io,
+	path::{Path, PathBuf},
+	process,
+	rc::Rc,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, OnceLock,
+	},
+};
 
-// Start the REPL and handle incoming prompts
-pub fn run_prompt() {
-	let error_handler = Rc::new(RefCell::new(ErrorHandler::new()));
-	let mut interpreter = Interpreter::new(Rc::clone(&error_handler));
-	loop {
-		print!("==> ");
-		let mut line = String::new();
-		let _ = io::stdout().flush();
-		io::stdin().read_line(&mut line).unwrap();
-		run(line.as_bytes().to_vec(), &mut interpreter);
-		error_handler.borrow_mut().reset();
+pub mod analysis;
+pub mod callable;
+pub mod debugger;
+pub mod deps;
+pub mod environment;
+pub mod error_handler;
+pub mod expressions;
+pub mod interpreter;
+pub mod native_functions;
+pub mod parser;
+pub mod program;
+pub mod resolver;
+pub mod rng;
+pub mod scanner;
+pub mod state;
+pub mod statements;
+pub mod token;
+
+/// Options passed through from the command line
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+	// Print the scanner's tokens instead of executing the script
+	pub token_dump: bool,
+	// Print the files the script imports instead of executing it
+	pub emit_deps: bool,
+	// Print the resolver's variable binding decisions instead of executing the script
+	pub resolve_dump: bool,
+	// Minimum level for the log_* natives, falls back to the JASN_LOG_LEVEL environment variable
+	pub log_level: Option<LogLevel>,
+	// Print the interpreter's execution statistics after running a file
+	pub stats: bool,
+	// Load global data values from this file before running and save them back afterwards
+	pub state: Option<String>,
+	// Disable natives that can reach outside of the interpreter
+	pub sandbox: bool,
+	// Read missing instance fields as null instead of reporting an error
+	pub missing_fields_null: bool,
+	// Make using an underscore field or method from outside its class an error, not a warning
+	pub strict_private: bool,
+	// Lines to pause at, in the given file or the script being run when there is none
+	pub breakpoints: Vec<(Option<PathBuf>, u32)>,
+	// Resource limits to run with instead of the defaults, see Limits::preset
+	pub limits: Option<Limits>,
+	// The arguments after the script's path, which the script reads with args()
+	pub args: Vec<String>,
+	// Run the script as a test suite, reporting how many assertions passed and failing if one
+	// didn't
+	pub check_only: bool,
+}
+
+/// Parse a breakpoint given as `line` or `file:line`
+pub fn parse_breakpoint(text: &str) -> Option<(Option<PathBuf>, u32)> {
+	match text.rsplit_once(':') {
+		Some((file, line)) => Some((Some(PathBuf::from(file)), line.parse().ok()?)),
+		None => Some((None, text.parse().ok()?)),
+	}
+}
+
+// The interrupt flag of the interpreter that Ctrl-C should abort
+static INTERRUPT_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+#[cfg(unix)]
+extern "C" fn handle_interrupt(_signal: libc::c_int) {
+	if let Some(flag) = INTERRUPT_FLAG.get() {
+		flag.store(true, Ordering::Relaxed);
 	}
 }
 
-// Load and run a file, reading the entire contents into a buffer
-pub fn run_file(path: &str) -> io::Result<()> {
-	let ext = Path::new(path).extension();
-	match ext {
-		Some(e) =>
-			if e != "jasn" {
-				println!("Invalid file extension. Please provide a .jasn file.");
+// Make Ctrl-C abort the running script instead of killing the process. Only the first
+// interpreter to call this is connected to the signal
+fn install_interrupt_handler(flag: Arc<AtomicBool>) {
+	if INTERRUPT_FLAG.set(flag).is_ok() {
+		#[cfg(unix)]
+		// Safety: the handler only performs an atomic store
+		unsafe {
+			libc::signal(libc::SIGINT, handle_interrupt as libc::sighandler_t);
+		}
+	}
+}
+
+/// Everything needed to run JASN code: an interpreter, the error handler it reports to and
+/// where input comes from and output goes. The REPL and the file runner are both built on this,
+/// and giving it in-memory IO lets a whole session be scripted
+pub struct Session {
+	pub interpreter: Interpreter,
+	pub error_handler: Rc<RefCell<ErrorHandler>>,
+	options: Options,
+	input: Input,
+	output: Output,
+	// Where errors, warnings and statistics go
+	errors: Output,
+	// The file most recently run with :load, where `:break line` sets breakpoints
+	loaded: Option<PathBuf>,
+}
+
+impl Session {
+	/// A session reading standard input, printing to standard output and reporting errors on
+	/// standard error
+	pub fn new(options: &Options) -> Self {
+		let input: Input = Rc::new(RefCell::new(io::stdin().lock()));
+		let output: Output = Rc::new(RefCell::new(io::stdout()));
+		let errors: Output = Rc::new(RefCell::new(io::stderr()));
+		Self::build(options, input, output, errors, ErrorHandler::new())
+	}
+
+	/// A session that reads from input and writes everything, errors included, to output
+	pub fn with_io(options: &Options, input: Input, output: Output) -> Self {
+		let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+		Self::build(options, input, Rc::clone(&output), output, error_handler)
+	}
+
+	fn build(
+		options: &Options,
+		input: Input,
+		output: Output,
+		errors: Output,
+		error_handler: ErrorHandler,
+	) -> Self {
+		let error_handler = Rc::new(RefCell::new(error_handler));
+		let missing_field =
+			if options.missing_fields_null { MissingField::Null } else { MissingField::Error };
+		let mut interpreter = Interpreter::builder(Rc::clone(&error_handler))
+			.sandbox(options.sandbox)
+			.limits(options.limits.unwrap_or_default())
+			.missing_field(missing_field)
+			.private_access(if options.strict_private {
+				PrivateAccess::Error
+			} else {
+				PrivateAccess::Warn
+			})
+			.args(options.args.clone())
+			.input(Rc::clone(&input))
+			.output(Rc::clone(&output))
+			.build();
+		let env_level = env::var("JASN_LOG_LEVEL").ok().and_then(|level| LogLevel::parse(&level));
+		if let Some(level) = options.log_level.or(env_level) {
+			interpreter.log_level = level;
+		}
+		Self {
+			interpreter,
+			error_handler,
+			options: options.clone(),
+			input,
+			output,
+			errors,
+			loaded: None,
+		}
+	}
+
+	/// Read lines from the input and run each one until the input ends (Ctrl-D) or a line
+	/// calls exit()
+	pub fn repl(&mut self) -> io::Result<()> {
+		loop {
+			write!(self.output.borrow_mut(), "==> ")?;
+			self.output.borrow_mut().flush()?;
+			let mut line = String::new();
+			if self.input.borrow_mut().read_line(&mut line)? == 0 {
+				writeln!(self.output.borrow_mut())?;
+				return Ok(());
+			}
+			self.run_line(&line)?;
+			if self.interpreter.exit_code().is_some() {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Run one line typed at the REPL. Errors are reported and then forgotten so the next line
+	/// starts clean. Lines starting with ':' are commands instead of code:
+	/// - `:help` lists these commands
+	/// - `:vars` lists the globals
+	/// - `:load file` runs a file in this session
+	/// - `:break line` or `:break file:line` pauses at a line of the loaded file or the given one
+	/// - `:breaks` lists the breakpoints and `:clear` removes them all
+	pub fn run_line(&mut self, line: &str) -> io::Result<()> {
+		// Ignore any Ctrl-C pressed while waiting at the prompt
+		self.interpreter.interrupt_flag().store(false, Ordering::Relaxed);
+		self.error_handler.borrow_mut().reset();
+		let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+		match (command, argument.trim()) {
+			(":help", _) => {
+				writeln!(
+					self.output.borrow_mut(),
+					"Commands:\n  :help              show this list\n  :vars              list the globals\n  :load file         run a file in this session\n  :break [file:]line pause at a line of the loaded file or the given one\n  :breaks            list the breakpoints\n  :clear             remove every breakpoint\nCall help() to list the native functions and help(\"name\") to describe one."
+				)?;
 				return Ok(());
 			},
-		None => {
-			println!("Invalid file extension. Please provide a .jasn file.");
-			return Ok(());
-		},
+			(":vars", _) => {
+				let global = self.interpreter.global.borrow();
+				for (name, value) in global.entries() {
+					let value = value.render(&self.interpreter.render_limits);
+					match global.defined_line(&name) {
+						Some(line) => writeln!(
+							self.output.borrow_mut(),
+							"{} = {} (line {})",
+							name,
+							value,
+							line
+						)?,
+						None => writeln!(self.output.borrow_mut(), "{} = {}", name, value)?,
+					}
+				}
+				return Ok(());
+			},
+			(":load", path) => {
+				let path = PathBuf::from(path);
+				if let Err(e) = self.run_path(&path) {
+					writeln!(self.errors.borrow_mut(), "Can't load '{}': {}", path.display(), e)?;
+				}
+				self.loaded = Some(path);
+				return Ok(());
+			},
+			(":break", breakpoint) => {
+				let added = match parse_breakpoint(breakpoint) {
+					Some((Some(file), line)) => Some((file, line)),
+					Some((None, line)) => self.loaded.clone().map(|file| (file, line)),
+					None => {
+						writeln!(
+							self.errors.borrow_mut(),
+							"Usage: :break line or :break file:line"
+						)?;
+						return Ok(());
+					},
+				};
+				match added {
+					Some((file, line)) =>
+						if let Err(e) = self.interpreter.debugger.add_breakpoint(&file, line) {
+							writeln!(
+								self.errors.borrow_mut(),
+								"Can't break in '{}': {}",
+								file.display(),
+								e
+							)?;
+						},
+					None => writeln!(
+						self.errors.borrow_mut(),
+						"No file has been loaded, use :break file:line or :load a file first"
+					)?,
+				}
+				return Ok(());
+			},
+			(":breaks", _) => {
+				let mut breakpoints = self.interpreter.debugger.breakpoints().peekable();
+				if breakpoints.peek().is_none() {
+					writeln!(self.output.borrow_mut(), "No breakpoints.")?;
+				}
+				for (file, line) in breakpoints {
+					writeln!(self.output.borrow_mut(), "{}:{}", file.display(), line)?;
+				}
+				return Ok(());
+			},
+			(":clear", _) => {
+				self.interpreter.debugger.clear_breakpoints();
+				return Ok(());
+			},
+			_ => (),
+		}
+		if self.options.resolve_dump || self.options.token_dump {
+			return self.run_source(line);
+		}
+		// Run the line as a snippet so the value of a trailing expression can be echoed
+		let result = self.interpreter.run_snippet(line);
+		write!(self.output.borrow_mut(), "{}", result.output)?;
+		if result.value != LiteralType::Null {
+			writeln!(
+				self.output.borrow_mut(),
+				"{}",
+				result.value.render(&self.interpreter.render_limits)
+			)?;
+		}
+		self.output.borrow_mut().flush()
 	}
-	let error_handler = Rc::new(RefCell::new(ErrorHandler::new()));
-	let mut interpreter = Interpreter::new(Rc::clone(&error_handler));
-	let mut file = File::open(path)?;
-	let mut buffer = Vec::new();
-	file.read_to_end(&mut buffer)?;
-	run(buffer, &mut interpreter);
-	Ok(())
-}
 
-// Actually run the interpreter
-fn run(source: Vec<u8>, interpreter: &mut Interpreter) {
-	// let start_time = std::time::Instant::now();
-	// Create a re-usable error handler
-	let error_handler = Rc::new(RefCell::new(ErrorHandler::new()));
-
-	// Scan the input text and convert to a list of tokens
-	let mut scanner = Scanner::new(source, Rc::clone(&error_handler));
-	scanner.scan_tokens();
-	// We don't want to continue if there was an error scanning the tokens
-	if error_handler.borrow().had_error {
-		return;
+	/// Scan, parse, resolve and run a piece of source code in this session
+	pub fn run_source(&mut self, source: &str) -> io::Result<()> {
+		self.run(source.as_bytes().to_vec())
 	}
-	// let scan_time = std::time::Instant::now();
-	// println!("Scanning took: {:?}", scan_time.duration_since(start_time));
-
-	// Parse the token stream
-	let mut parser = Parser::new(scanner.tokens, Rc::clone(&error_handler));
-	let statements = parser.parse();
-	// Stop if there was a parsing error
-	if error_handler.borrow().had_error {
-		return;
+
+	fn run(&mut self, source: Vec<u8>) -> io::Result<()> {
+		let error_handler = Rc::clone(&self.error_handler);
+
+		// Scan the input text and convert to a list of tokens
+		let mut scanner = Scanner::new(source, Rc::clone(&error_handler));
+		scanner.scan_tokens();
+		// We don't want to continue if there was an error scanning the tokens
+		if error_handler.borrow().had_syntax_error {
+			return Ok(());
+		}
+		if self.options.token_dump {
+			for token in &scanner.tokens {
+				writeln!(self.output.borrow_mut(), "{}", token)?;
+			}
+			return Ok(());
+		}
+
+		// Parse the token stream
+		let mut parser = Parser::new(scanner.tokens, Rc::clone(&error_handler))
+			.max_nesting(self.interpreter.max_nesting);
+		let statements = parser.parse();
+		// Stop if there was a parsing error
+		if error_handler.borrow().had_syntax_error {
+			return Ok(());
+		}
+
+		let mut resolver = Resolver::new(&mut self.interpreter, Rc::clone(&error_handler));
+		resolver.resolve_block(&statements);
+		// Code the resolver rejected never runs, in the REPL the next line starts clean
+		if error_handler.borrow().had_resolve_error {
+			return Ok(());
+		}
+		if self.options.resolve_dump {
+			for binding in &resolver.bindings {
+				writeln!(self.output.borrow_mut(), "{}", binding)?;
+			}
+			return Ok(());
+		}
+		self.interpreter.warn_unused_breakpoints(&statements);
+
+		// Execute the parsed statements
+		self.interpreter.interpret(statements);
+		self.output.borrow_mut().flush()
 	}
-	// let parse_time = std::time::Instant::now();
-	// println!("Parsing took: {:?}", parse_time.duration_since(scan_time));
 
-	// Execute the parsed statements
-	let mut resolver = Resolver::new(interpreter, Rc::clone(&error_handler));
-	resolver.resolve_block(&statements);
+	/// Print every file a script imports, one canonical path per line, without running
+	/// anything. Returns 65 if an import is missing, part of a cycle or doesn't parse
+	fn emit_deps(&mut self, path: &Path) -> io::Result<i32> {
+		let dependencies = Dependencies::find(path, &self.error_handler.borrow())?;
+		for file in &dependencies.files {
+			writeln!(self.output.borrow_mut(), "{}", file.display())?;
+		}
+		for diagnostic in &dependencies.diagnostics {
+			writeln!(self.errors.borrow_mut(), "{}", diagnostic)?;
+		}
+		Ok(if dependencies.diagnostics.is_empty() { 0 } else { 65 })
+	}
 
-	if error_handler.borrow().had_error {
-		return;
+	/// Load and run a file, along with the state file and statistics the options ask for.
+	/// Returns the exit code for the process: the one the script gave exit(), or 130 if the
+	/// script was interrupted
+	pub fn run_path(&mut self, path: &Path) -> io::Result<i32> {
+		if path.extension().map_or(true, |extension| extension != "jasn") {
+			writeln!(
+				self.output.borrow_mut(),
+				"Invalid file extension. Please provide a .jasn file."
+			)?;
+			return Ok(0);
+		}
+		if self.options.emit_deps {
+			return self.emit_deps(path);
+		}
+		self.interpreter.set_script_path(path);
+		for (file, line) in self.options.breakpoints.clone() {
+			let file = file.unwrap_or_else(|| path.to_path_buf());
+			if let Err(e) = self.interpreter.debugger.add_breakpoint(&file, line) {
+				writeln!(self.errors.borrow_mut(), "Can't break in '{}': {}", file.display(), e)?;
+			}
+		}
+		let source = fs::read(path)?;
+		let state = self.options.state.clone();
+		if let Some(state) = state.as_ref().map(Path::new) {
+			if state.exists() {
+				State::load(&mut self.interpreter, state)
+					.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+			}
+		}
+		self.run(source)?;
+		// Code typed at the REPL afterwards isn't part of the file
+		self.interpreter.current_file = None;
+		if let Some(state) = state.as_ref().map(Path::new) {
+			let skipped = State::save(&self.interpreter, state).map_err(io::Error::other)?;
+			if !skipped.is_empty() {
+				writeln!(
+					self.errors.borrow_mut(),
+					"Warning: callables and arrays or maps that contain themselves can't be saved to the state file: {}",
+					skipped.join(", ")
+				)?;
+			}
+		}
+		// Always say when a limit stopped the script, so it isn't mistaken for a bug in it
+		let limit = self.interpreter.limit_hit();
+		if self.options.stats || limit.is_some() {
+			let stats = self.interpreter.stats();
+			let mut errors = self.errors.borrow_mut();
+			if self.options.stats {
+				writeln!(errors, "{}", stats)?;
+			} else {
+				writeln!(errors, "Statements executed:   {}", stats.statements)?;
+				writeln!(errors, "Max call depth:        {}", stats.max_call_depth)?;
+			}
+			match limit {
+				Some(limit) => writeln!(errors, "Stopped by limit:      {}", limit)?,
+				None => writeln!(errors, "Stopped by limit:      none")?,
+			}
+		}
+		if self.error_handler.borrow().was_interrupted {
+			return Ok(130);
+		}
+		if self.options.check_only {
+			let passed = self.interpreter.stats().assertions_passed;
+			let mut errors = self.errors.borrow_mut();
+			if self.error_handler.borrow().had_runtime_error {
+				writeln!(errors, "Check failed, {} assertions passed before it stopped", passed)?;
+				return Ok(1);
+			}
+			writeln!(errors, "Check passed, {} assertions passed", passed)?;
+		}
+		Ok(self.interpreter.exit_code().unwrap_or(0))
 	}
-	interpreter.interpret(statements);
-	// let end_time = std::time::Instant::now();
-	// println!("Execution took: {:?}", end_time.duration_since(parse_time));
+}
+
+// Start the REPL and handle incoming prompts
+pub fn run_prompt(options: &Options) {
+	let mut session = Session::new(options);
+	install_interrupt_handler(session.interpreter.interrupt_flag());
+	if let Err(e) = session.repl() {
+		eprintln!("{}", e);
+	}
+	if let Some(code) = session.interpreter.exit_code() {
+		process::exit(code);
+	}
+}
+
+// Load and run a file.
+// Returns the exit code for the process, 130 if the script was interrupted
+pub fn run_file(path: &str, options: &Options) -> io::Result<i32> {
+	let mut session = Session::new(options);
+	install_interrupt_handler(session.interpreter.interrupt_flag());
+	session.run_path(Path::new(path))
 }
@@ -1,5 +1,12 @@
-use jasn::{run_file, run_prompt};
-use std::{env, process};
+use jasn::{
+	interpreter::Limits, native_functions::LogLevel, parse_breakpoint, run_file, run_prompt,
+	Options,
+};
+use std::{env, panic, process, thread};
+
+// Parsing and running a program recurses once per level of nesting, which uses far more stack
+// than the main thread gets in debug builds
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 // Throw an error and exit the process from within the interpreter
 fn handle_error(code: i32, err: &str) {
@@ -9,11 +16,72 @@ fn handle_error(code: i32, err: &str) {
 
 // Entry point for the Jasn AST Interpreter
 fn main() {
-	println!("Starting JASN-AST Interpreter...");
-	let args: Vec<String> = env::args().collect();
-	match args.len() {
-		1 => run_prompt(),
-		2 => run_file(&args[1]).map_err(|e| handle_error(64, &e.to_string())).unwrap(),
-		_ => handle_error(64, "Usage: jasn [script]"),
+	let interpreter = thread::Builder::new()
+		.stack_size(STACK_SIZE)
+		.spawn(run)
+		.expect("failed to start the interpreter thread");
+	if let Err(panic) = interpreter.join() {
+		panic::resume_unwind(panic);
+	}
+}
+
+fn run() {
+	let mut options = Options::default();
+	let mut script: Option<String> = None;
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		// Everything after the script's path is for the script, even if it looks like an option
+		if script.is_some() {
+			options.args.push(arg);
+			continue;
+		}
+		match arg.as_str() {
+			"--tokens" => options.token_dump = true,
+			"--emit-deps" => options.emit_deps = true,
+			"--resolve-dump" => options.resolve_dump = true,
+			"--stats" => options.stats = true,
+			"--sandbox" => options.sandbox = true,
+			"--missing-fields-null" => options.missing_fields_null = true,
+			"--strict-private" => options.strict_private = true,
+			"--check-only" => options.check_only = true,
+			"--state" => match args.next() {
+				Some(path) => options.state = Some(path),
+				None => handle_error(64, "Expected a file path after --state"),
+			},
+			"--limits" => match args.next().as_deref().and_then(Limits::preset) {
+				Some(limits) => options.limits = Some(limits),
+				None => handle_error(64, "Expected strict, relaxed or off after --limits"),
+			},
+			"--break" => match args.next().as_deref().and_then(parse_breakpoint) {
+				Some(breakpoint) => options.breakpoints.push(breakpoint),
+				None => handle_error(64, "Expected a line or file:line after --break"),
+			},
+			flag if flag.starts_with("--log-level=") => {
+				let level = &flag["--log-level=".len()..];
+				match LogLevel::parse(level) {
+					Some(level) => options.log_level = Some(level),
+					None => handle_error(64, &format!("Unknown log level '{}'", level)),
+				}
+			},
+			flag if flag.starts_with("--") => handle_error(
+				64,
+				&format!(
+					"Unknown option '{}'\nUsage: jasn [--tokens] [--emit-deps] [--resolve-dump] [--stats] [--sandbox] [--missing-fields-null] [--strict-private] [--check-only] [--log-level=level] [--state file] [--break [file:]line]... [--limits strict|relaxed|off] [script [args]...]",
+					flag
+				),
+			),
+			_ => script = Some(arg),
+		}
+	}
+	// Dependencies and tokens are read by other programs, so nothing else can go before them
+	if !options.emit_deps && !options.token_dump {
+		println!("Starting JASN-AST Interpreter...");
+	}
+	match script {
+		None => run_prompt(&options),
+		Some(script) => match run_file(&script, &options) {
+			Ok(code) => process::exit(code),
+			Err(e) => handle_error(64, &e.to_string()),
+		},
 	}
 }
@@ -1,103 +1,3151 @@
 use crate::{
-	callable::{Callable, NativeFunction},
-	environment::EnvRef,
+	callable::{Callable, JasnInstance, NativeDocs, NativeFn},
+	debugger,
 	error_handler::Error,
-	token::LiteralType,
+	interpreter::{Interpreter, Limit},
+	rng::{self, Xoshiro128},
+	token::{ArrayRef, LiteralType, RenderLimits, Span},
 };
+use std::{
+	cmp::Ordering,
+	collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+	env, fs,
+	hash::{Hash, Hasher},
+	io::Write,
+	num::IntErrorKind,
+	path::Path,
+	rc::Rc,
+	time::Instant,
+};
+
+/// The largest array a native function is allowed to build in one go, unless the interpreter's
+/// Limits say otherwise
+pub const MAX_ARRAY_LENGTH: usize = 10_000_000;
+
+/// The largest whole number an f64 can hold without losing precision (2^53 - 1)
+pub const MAX_SAFE_INTEGER: u64 = 9_007_199_254_740_991;
+
+/// Natives that can reach outside of the interpreter (filesystem, network, environment variables,
+/// processes). These are disabled when the interpreter is built in sandbox mode
+pub const SANDBOXED_NATIVES: [&str; 7] =
+	["read_file", "write_file", "append_file", "file_exists", "get_env", "args", "exit"];
+
+/// Natives that pure functions can't call, along with SANDBOXED_NATIVES: they read or change
+/// something outside of their arguments (IO, time, randomness, logging), change an argument in
+/// place or call functions they are given, which might not be pure
+pub const IMPURE_NATIVES: [&str; 48] = [
+	"breakpoint",
+	"help",
+	"clock",
+	"time_it",
+	"input",
+	"print",
+	"sleep",
+	"random",
+	"random_seed",
+	"seed_random",
+	"random_range",
+	"random_int",
+	"shuffle",
+	"set_log_level",
+	"log_debug",
+	"log_info",
+	"log_warn",
+	"log_error",
+	"fill_with",
+	"repeat_call",
+	"build_string",
+	"flat_map",
+	"map",
+	"filter",
+	"reduce",
+	"each",
+	"try_call",
+	"min_by",
+	"max_by",
+	"find",
+	"find_index",
+	"group_by",
+	"zip_with",
+	"partition",
+	"sort_by",
+	"globals",
+	"rng_next",
+	"rng_int",
+	"rng_shuffle",
+	"print_f",
+	"push",
+	"pop",
+	"insert",
+	"remove",
+	"swap",
+	"rotate",
+	"sort",
+	"reverse",
+];
+
+/// Severity of a message written by the log_* natives. Messages below the interpreter's level
+/// are dropped
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+	Debug,
+	Info,
+	Warn,
+	Error,
+}
+
+impl LogLevel {
+	pub fn parse(level: &str) -> Option<Self> {
+		match level.to_lowercase().as_str() {
+			"debug" => Some(Self::Debug),
+			"info" => Some(Self::Info),
+			"warn" => Some(Self::Warn),
+			"error" => Some(Self::Error),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Debug => "DEBUG",
+			Self::Info => "INFO",
+			Self::Warn => "WARN",
+			Self::Error => "ERROR",
+		}
+	}
+}
+
+/// Returns the time in seconds since the UNIX Epoch
+pub fn system_time() -> f64 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.unwrap()
+		.as_secs_f64()
+}
 
 pub struct NativeFunctions {}
 
+/// A native module packages related host functionality so it can be loaded into an interpreter
+/// in one go with Interpreter::load_module
+pub trait NativeModule {
+	/// The name of the module, loaded functions are prefixed with it (`db` gives `db_query`)
+	fn name(&self) -> &str;
+	/// Define the module's functions using Interpreter::define_native
+	fn register(&self, interpreter: &mut Interpreter);
+}
+
+/// The built in natives, loaded without a prefix into every interpreter
+impl NativeModule for NativeFunctions {
+	fn name(&self) -> &str {
+		"core"
+	}
+
+	fn register(&self, interpreter: &mut Interpreter) {
+		Self::define_native_functions(interpreter);
+	}
+}
+
+/// A native function that takes one number and gives back f of it, anything but a number is an
+/// error. Only for use inside NativeFunctions
+macro_rules! number_native {
+	($name:literal, $f:expr) => {
+		(|_env, args| {
+			let n = Self::expect_number($name, &args[0])?;
+			Ok(LiteralType::Number($f(n)))
+		}) as NativeFn
+	};
+}
+
 impl NativeFunctions {
-	// Define all the native functions in the environment
-	pub fn define_native_functions(environment: EnvRef) {
-		Self::define_clock(environment.clone());
-		Self::define_input(environment.clone());
-		Self::define_len(environment.clone());
-		Self::define_print(environment.clone());
-		Self::define_sleep(environment.clone());
-		Self::define_random(environment.clone());
+	// Define all the native functions in the interpreter
+	fn define_native_functions(interpreter: &mut Interpreter) {
+		Self::define_clock(interpreter);
+		Self::define_input(interpreter);
+		Self::define_len(interpreter);
+		Self::define_keys(interpreter);
+		Self::define_print(interpreter);
+		Self::define_sleep(interpreter);
+		Self::define_random(interpreter);
+		Self::define_random_seed(interpreter);
+		Self::define_random_range(interpreter);
+		Self::define_random_int(interpreter);
+		Self::define_fill(interpreter);
+		Self::define_fill_with(interpreter);
+		Self::define_to_radix(interpreter);
+		Self::define_from_radix(interpreter);
+		Self::define_log(interpreter);
+		Self::define_set_log_level(interpreter);
+		Self::define_flatten(interpreter);
+		Self::define_flat_map(interpreter);
+		Self::define_inspect_full(interpreter);
+		Self::define_try_call(interpreter);
+		Self::define_min_max_by(interpreter);
+		Self::define_find(interpreter);
+		Self::define_get_or(interpreter);
+		Self::define_unique(interpreter);
+		Self::define_count_of(interpreter);
+		Self::define_group_by(interpreter);
+		Self::define_swap(interpreter);
+		Self::define_rotate(interpreter);
+		Self::define_shuffle(interpreter);
+		Self::define_breakpoint(interpreter);
+		Self::define_help(interpreter);
+		Self::define_time_it(interpreter);
+		Self::define_globals(interpreter);
+		Self::define_starts_ends_with(interpreter);
+		Self::define_windows(interpreter);
+		Self::define_zip_with(interpreter);
+		Self::define_add_arrays(interpreter);
+		Self::define_scale(interpreter);
+		Self::define_chunk(interpreter);
+		Self::define_partition(interpreter);
+		Self::define_cmp(interpreter);
+		Self::define_sort(interpreter);
+		Self::define_sort_by(interpreter);
+		Self::define_math(interpreter);
+		Self::define_min_max(interpreter);
+		Self::define_trig(interpreter);
+		Self::define_strings(interpreter);
+		Self::define_conversions(interpreter);
+		Self::define_index_of(interpreter);
+		Self::define_push_pop(interpreter);
+		Self::define_map_filter(interpreter);
+		Self::define_join(interpreter);
+		Self::define_range(interpreter);
+		Self::define_files(interpreter);
+		Self::define_process(interpreter);
+		Self::define_json_get(interpreter);
+		Self::define_assert(interpreter);
+		Self::define_rng(interpreter);
+		Self::define_format(interpreter);
+	}
+
+	/// Check that an argument is an array, returning a snapshot of its elements so callbacks are
+	/// free to change the array while they run
+	fn expect_array(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &LiteralType,
+	) -> Result<Vec<LiteralType>, Error> {
+		Ok(Self::expect_array_ref(interpreter, name, value)?.borrow().clone())
+	}
+
+	/// Check that an argument is an array, returning the array itself for natives that change it
+	fn expect_array_ref<'a>(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &'a LiteralType,
+	) -> Result<&'a ArrayRef, Error> {
+		match value {
+			LiteralType::Array(values) => Ok(values),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects an array but found {}",
+					name,
+					value.render(&interpreter.render_limits)
+				),
+			)),
+		}
+	}
+
+	/// Order two keys returned by a key function, they must both be numbers or both be strings
+	fn compare_keys(
+		interpreter: &Interpreter,
+		name: &str,
+		a: &LiteralType,
+		b: &LiteralType,
+	) -> Result<Ordering, Error> {
+		match (a, b) {
+			(LiteralType::Number(a), LiteralType::Number(b)) => a.partial_cmp(b).ok_or_else(|| {
+				Error::RuntimeError(Span::default(), format!("{} can't compare NaN keys", name))
+			}),
+			(LiteralType::String(a), LiteralType::String(b)) => Ok(a.cmp(b)),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects keys that are all numbers or all strings but found {} and {}",
+					name,
+					a.render(&interpreter.render_limits),
+					b.render(&interpreter.render_limits)
+				),
+			)),
+		}
+	}
+
+	/// Find the element with the smallest or largest key, the first one wins a tie
+	fn extreme_by(
+		interpreter: &mut Interpreter,
+		name: &str,
+		args: &[LiteralType],
+		wanted: Ordering,
+	) -> Result<LiteralType, Error> {
+		let values = Self::expect_array(interpreter, name, &args[0])?;
+		let key_function = Self::expect_callable(interpreter, name, &args[1], 1)?;
+		let mut best: Option<(LiteralType, LiteralType)> = None;
+		for value in values {
+			let key = key_function.call(interpreter, vec![value.clone()])?;
+			let better = match &best {
+				Some((_, best_key)) =>
+					Self::compare_keys(interpreter, name, &key, best_key)? == wanted,
+				None => true,
+			};
+			if better {
+				best = Some((value, key));
+			}
+		}
+		Ok(best.map_or(LiteralType::Null, |(value, _)| value))
+	}
+
+	/// Check that an argument is a callable taking the given number of arguments
+	fn expect_callable<'a>(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &'a LiteralType,
+		arity: u8,
+	) -> Result<&'a Callable, Error> {
+		let LiteralType::Callable(function) = value else {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects a function but found {}",
+					name,
+					value.render(&interpreter.render_limits)
+				),
+			));
+		};
+		if !function.accepts(arity as usize) {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects a function taking {} argument(s) but it takes {}",
+					name,
+					arity,
+					function.expected_arguments()
+				),
+			));
+		}
+		Ok(function)
+	}
+
+	/// Write a log line where errors go if the level is at or above the interpreter's log level
+	fn log(interpreter: &Interpreter, level: LogLevel, message: &LiteralType) {
+		if level >= interpreter.log_level {
+			interpreter.error_handler.borrow().write_line(format_args!(
+				"[{} {:.3}] {}",
+				level.name(),
+				(interpreter.time_source)(),
+				message.render(&interpreter.render_limits)
+			));
+		}
+	}
+
+	/// Convert a number argument into a whole number that can be safely represented in an f64
+	fn safe_integer(name: &str, value: &LiteralType) -> Result<u64, Error> {
+		let LiteralType::Number(n) = value else {
+			return Err(Error::RuntimeError(Span::default(), format!("{} expects a number", name)));
+		};
+		if *n < 0.0 || n.fract() != 0.0 {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects a whole, non-negative number but found {}", name, n),
+			));
+		}
+		if *n > MAX_SAFE_INTEGER as f64 {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} can't convert {} without losing precision", name, n),
+			));
+		}
+		Ok(*n as u64)
+	}
+
+	/// Parse a string of digits in the given radix, ignoring an optional prefix like 0x.
+	/// Returns null if the string has invalid digits, a sign counts as one
+	fn parse_radix(
+		name: &str,
+		value: &LiteralType,
+		prefixes: &[&str],
+		radix: u32,
+	) -> Result<LiteralType, Error> {
+		let LiteralType::String(s) = value else {
+			return Err(Error::RuntimeError(Span::default(), format!("{} expects a string", name)));
+		};
+		let digits = prefixes.iter().find_map(|prefix| s.strip_prefix(prefix)).unwrap_or(s);
+		// from_str_radix would take a leading + as part of the number
+		if digits.starts_with(['+', '-']) {
+			return Ok(LiteralType::Null);
+		}
+		let imprecise = || {
+			Error::RuntimeError(
+				Span::default(),
+				format!("{} can't convert '{}' without losing precision", name, s),
+			)
+		};
+		match u64::from_str_radix(digits, radix) {
+			Ok(n) if n > MAX_SAFE_INTEGER => Err(imprecise()),
+			Ok(n) => Ok(LiteralType::Number(n as f64)),
+			Err(e) if *e.kind() == IntErrorKind::PosOverflow => Err(imprecise()),
+			Err(_) => Ok(LiteralType::Null),
+		}
+	}
+
+	/// Convert a number argument into an array length, rejecting negative, fractional and
+	/// absurdly large sizes
+	fn array_length(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &LiteralType,
+	) -> Result<usize, Error> {
+		let LiteralType::Number(n) = value else {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects a number as the length", name),
+			));
+		};
+		if *n < 0.0 || n.fract() != 0.0 {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects a whole, non-negative length but found {}", name, n),
+			));
+		}
+		if let Some(max) = interpreter.limits().max_array_length {
+			if *n > max as f64 {
+				return Err(interpreter.exceeded(
+					0,
+					Limit::ArrayLength(max),
+					format!("{} can't create an array of {} elements (limit {})", name, n, max),
+				));
+			}
+		}
+		Ok(*n as usize)
+	}
+
+	/// Check that an argument is a whole number
+	fn expect_whole_number(
+		interpreter: &Interpreter,
+		name: &str,
+		what: &str,
+		value: &LiteralType,
+	) -> Result<f64, Error> {
+		match value {
+			LiteralType::Number(n) if n.fract() == 0.0 => Ok(*n),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects a whole number as the {} but found {}",
+					name,
+					what,
+					value.render(&interpreter.render_limits)
+				),
+			)),
+		}
+	}
+
+	/// Check that an argument is an index into an array of length, counting back from the end
+	/// when it is negative like indexing does
+	fn expect_index(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &LiteralType,
+		length: usize,
+	) -> Result<usize, Error> {
+		let n = Self::expect_whole_number(interpreter, name, "index", value)?;
+		let index = if n < 0.0 { n + length as f64 } else { n };
+		if index < 0.0 || index >= length as f64 {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} index {} is out of bounds for length {}", name, n, length),
+			));
+		}
+		Ok(index as usize)
 	}
 
 	/// The clock function will return the time in seconds since the UNIX Epoch
-	fn define_clock(environment: EnvRef) {
-		let clock = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 0,
-			function: |_, _| {
-				Ok(LiteralType::Number(
-					std::time::SystemTime::now()
-						.duration_since(std::time::UNIX_EPOCH)
-						.unwrap()
-						.as_secs_f64(),
-				))
-			},
-		}));
-		environment.borrow_mut().define("clock".to_string(), clock);
+	fn define_clock(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"clock",
+			0,
+			NativeDocs::new("time", "clock()", "Seconds since the UNIX epoch, as a fraction"),
+			|interpreter, _| Ok(LiteralType::Number((interpreter.time_source)())),
+		);
 	}
 
 	/// The input function will read a line from the standard input
-	fn define_input(environment: EnvRef) {
-		let input = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 0,
-			function: |_, _| {
+	fn define_input(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"input",
+			0,
+			NativeDocs::new(
+				"io",
+				"input()",
+				"Read a line from the input, as a number if it is one and a string otherwise",
+			),
+			|interpreter, _| {
 				let mut input = String::new();
-				std::io::stdin().read_line(&mut input).unwrap();
+				interpreter.input.borrow_mut().read_line(&mut input).map_err(|e| {
+					Error::RuntimeError(
+						Span::default(),
+						format!("input couldn't read a line: {}", e),
+					)
+				})?;
 				// Attempt to convert to a number, otherwise return a string
 				return match input.trim().parse() {
 					Ok(num) => Ok(LiteralType::Number(num)),
 					Err(_) => Ok(LiteralType::String(input)),
 				};
 			},
-		}));
-		environment.borrow_mut().define("input".to_string(), input);
+		);
 	}
 
 	/// Print will print the argument, eventually replacing the print statement
-	fn define_print(environment: EnvRef) {
-		let print = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 1,
-			function: |_, args| {
-				println!("{:?}", args[0]);
+	fn define_print(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"print",
+			1,
+			NativeDocs::new("io", "print(value)", "Write a value on its own line, quoting strings"),
+			|interpreter, args| {
+				writeln!(interpreter.output.borrow_mut(), "{:?}", args[0]).map_err(|e| {
+					Error::RuntimeError(Span::default(), format!("print couldn't write: {}", e))
+				})?;
 				Ok(LiteralType::Null)
 			},
-		}));
-		environment.borrow_mut().define("print".to_string(), print);
+		);
 	}
 
-	fn define_len(environment: EnvRef) {
-		let len = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 1,
-			function: |_env, args| match &args[0] {
-				LiteralType::String(s) => Ok(LiteralType::Number(s.len() as f64)),
-				LiteralType::Array(a) => Ok(LiteralType::Number(a.len() as f64)),
-				LiteralType::Callable(c) => Ok(LiteralType::Number(c.arity() as f64)),
-				_ => Ok(LiteralType::Null),
+	/// len(value) counts the characters of a string, the elements of an array or the entries of
+	/// a map. Instances are asked through their size() method
+	fn define_len(interpreter: &mut Interpreter) {
+		interpreter.define_native("len", 1, NativeDocs::new("values", "len(value)", "Count the characters of a string, elements of an array or entries of a map, instances are asked through their size() method"), |interpreter, args| match &args[0] {
+			LiteralType::String(s) => Ok(LiteralType::Number(s.chars().count() as f64)),
+			LiteralType::Array(a) => Ok(LiteralType::Number(a.borrow().len() as f64)),
+			LiteralType::Map(m) => Ok(LiteralType::Number(m.borrow().len() as f64)),
+			LiteralType::Callable(Callable::Instance(instance)) => {
+				let class_name = instance.borrow().class.name.clone();
+				match JasnInstance::lookup(instance, "size") {
+					Some(LiteralType::Callable(size)) if size.arity() == 0 =>
+						size.call(interpreter, vec![]),
+					_ => Err(Error::RuntimeError(
+						Span::default(),
+						format!("len needs {} to have a size() method", class_name),
+					)),
+				}
 			},
-		}));
-		environment.borrow_mut().define("len".to_string(), len);
+			LiteralType::Callable(_) => Err(Error::RuntimeError(
+				Span::default(),
+				"len can't measure a function, use arity() to get its number of parameters"
+					.to_string(),
+			)),
+			value => Err(Error::RuntimeError(
+				Span::default(),
+				format!("len expects a string, array, map or instance but found {}", value.type_name()),
+			)),
+		});
+
+		interpreter.define_native(
+			"arity",
+			1,
+			NativeDocs::new(
+				"functions",
+				"arity(function)",
+				"How many parameters a function or class's init takes",
+			),
+			|_env, args| match &args[0] {
+				LiteralType::Callable(callable) if !matches!(callable, Callable::Instance(_)) =>
+					Ok(LiteralType::Number(callable.arity() as f64)),
+				value => Err(Error::RuntimeError(
+					Span::default(),
+					format!("arity expects a function or class but found {}", value.type_name()),
+				)),
+			},
+		);
 	}
 
-	fn define_sleep(environment: EnvRef) {
-		let sleep = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 1,
-			function: |_env, args| {
+	/// keys(map) lists the keys of a map in order, including ones set like fields
+	fn define_keys(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"keys",
+			1,
+			NativeDocs::new(
+				"values",
+				"keys(map)",
+				"The keys of a map as an array of strings, in order",
+			),
+			|_env, args| match &args[0] {
+				LiteralType::Map(map) => Ok(LiteralType::array(
+					map.borrow().keys().map(|key| LiteralType::String(key.clone())).collect(),
+				)),
+				value => Err(Error::RuntimeError(
+					Span::default(),
+					format!("keys expects a map but found {}", value.type_name()),
+				)),
+			},
+		);
+	}
+
+	fn define_sleep(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"sleep",
+			1,
+			NativeDocs::new("time", "sleep(seconds)", "Pause the script for a number of seconds"),
+			|_env, args| {
 				let LiteralType::Number(secs) = args[0] else {
 					return Err(Error::RuntimeError(
-						0,
+						Span::default(),
 						"sleep only accepts a number as an argument".to_string(),
 					));
 				};
 				std::thread::sleep(std::time::Duration::from_secs_f64(secs));
 				Ok(LiteralType::Null)
 			},
-		}));
-		environment.borrow_mut().define("sleep".to_string(), sleep);
+		);
+	}
+
+	fn define_random(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"random",
+			0,
+			NativeDocs::new(
+				"random",
+				"random()",
+				"A random number from 0 up to but not including 1",
+			),
+			|interpreter, _args| Ok(LiteralType::Number(interpreter.rng.next_f64())),
+		);
+	}
+
+	/// random_seed(seed) restarts random numbers from seed, so a script gets the same ones every
+	/// time it runs. seed_random(seed) is the same native under the name the other random
+	/// natives would suggest
+	fn define_random_seed(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, NativeFn); 2] = [
+			("random_seed", "random_seed(seed)", |interpreter, args| {
+				let seed = Self::expect_whole_number(interpreter, "random_seed", "seed", &args[0])?;
+				interpreter.rng = Xoshiro128::seed(seed as i64 as u64);
+				Ok(LiteralType::Null)
+			}),
+			("seed_random", "seed_random(seed)", |interpreter, args| {
+				let seed = Self::expect_whole_number(interpreter, "seed_random", "seed", &args[0])?;
+				interpreter.rng = Xoshiro128::seed(seed as i64 as u64);
+				Ok(LiteralType::Null)
+			}),
+		];
+		for (name, signature, function) in natives {
+			interpreter.define_native(
+				name,
+				1,
+				NativeDocs::new(
+					"random",
+					signature,
+					"Restart random numbers from a whole number seed so they repeat each run",
+				),
+				function,
+			);
+		}
+	}
+
+	/// random_range(min, max) returns a random number from min up to but not including max, or
+	/// min itself when the two are equal
+	fn define_random_range(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"random_range",
+			2,
+			NativeDocs::new(
+				"random",
+				"random_range(min, max)",
+				"A random number from min up to but not including max",
+			),
+			|interpreter, args| {
+				let min = Self::expect_number("random_range", &args[0])?;
+				let max = Self::expect_number("random_range", &args[1])?;
+				Self::check_random_bounds("random_range", min, max)?;
+				if min == max {
+					return Ok(LiteralType::Number(min));
+				}
+				Ok(LiteralType::Number(Self::random_between(&mut interpreter.rng, min, max)))
+			},
+		);
+	}
+
+	/// random_int(min, max) returns a random whole number from min to max, both included
+	fn define_random_int(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"random_int",
+			2,
+			NativeDocs::new(
+				"random",
+				"random_int(min, max)",
+				"A random whole number from min to max, both included",
+			),
+			|interpreter, args| {
+				let (min, max) = Self::random_int_bounds(interpreter, "random_int", &args)?;
+				Ok(LiteralType::Number(interpreter.rng.int_between(min, max) as f64))
+			},
+		);
+	}
+
+	// A random number from min up to but not including max, which must be further apart than
+	// the same number. Weighting the bounds rather than the gap keeps huge ranges finite
+	fn random_between(rng: &mut Xoshiro128, min: f64, max: f64) -> f64 {
+		let f = rng.next_f64();
+		let n = min * (1.0 - f) + max * f;
+		// Rounding can land on max when f is just below 1
+		if n < max {
+			n
+		} else {
+			min
+		}
+	}
+
+	// The bounds in args of a random whole number, both included
+	fn random_int_bounds(
+		interpreter: &Interpreter,
+		name: &str,
+		args: &[LiteralType],
+	) -> Result<(i64, i64), Error> {
+		let min = Self::expect_whole_number(interpreter, name, "min", &args[0])?;
+		let max = Self::expect_whole_number(interpreter, name, "max", &args[1])?;
+		Self::check_random_bounds(name, min, max)?;
+		for n in [min, max] {
+			if n.abs() > MAX_SAFE_INTEGER as f64 {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					format!("{} can't use {} without losing precision", name, n),
+				));
+			}
+		}
+		Ok((min as i64, max as i64))
+	}
+
+	// The bounds of a random range have to be finite with min no bigger than max
+	fn check_random_bounds(name: &str, min: f64, max: f64) -> Result<(), Error> {
+		if !min.is_finite() || !max.is_finite() {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects finite bounds but found {} and {}", name, min, max),
+			));
+		}
+		if min > max {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects min to be at most max but found {} and {}", name, min, max),
+			));
+		}
+		Ok(())
+	}
+
+	/// fill(n, value) returns an array of n copies of value. An array or map fill value is deep
+	/// copied for every element, so changing one element doesn't change the others
+	fn define_fill(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"fill",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"fill(n, value)",
+				"An array of n copies of value, arrays and maps are copied for every element",
+			),
+			|interpreter, args| {
+				let length = Self::array_length(interpreter, "fill", &args[0])?;
+				let values = match &args[1] {
+					LiteralType::Array(_) | LiteralType::Map(_) =>
+						(0..length).map(|_| args[1].deep_copy()).collect(),
+					value => vec![value.clone(); length],
+				};
+				Ok(LiteralType::array(values))
+			},
+		);
+	}
+
+	/// Call f with each index from 0 up to n, collecting what it returns. Every call counts
+	/// towards the limits
+	fn call_for_indices(
+		interpreter: &mut Interpreter,
+		name: &str,
+		args: &[LiteralType],
+	) -> Result<Vec<LiteralType>, Error> {
+		let length = Self::array_length(interpreter, name, &args[0])?;
+		let function = Self::expect_callable(interpreter, name, &args[1], 1)?;
+		let mut values = Vec::with_capacity(length);
+		for i in 0..length {
+			interpreter.native_iteration()?;
+			values.push(function.call(interpreter, vec![LiteralType::Number(i as f64)])?);
+		}
+		Ok(values)
+	}
+
+	/// fill_with(n, f) returns an array of n elements where each element is f(index).
+	/// repeat_call(n, f) is the same native under the name that says it calls f n times
+	fn define_fill_with(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"fill_with",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"fill_with(n, f)",
+				"An array of n elements where each one is f(index)",
+			),
+			|interpreter, args| {
+				Ok(LiteralType::array(Self::call_for_indices(interpreter, "fill_with", &args)?))
+			},
+		);
+
+		interpreter.define_native(
+			"repeat_call",
+			2,
+			NativeDocs::new(
+				"functions",
+				"repeat_call(n, f)",
+				"Call f(index) n times, for indices 0 up to n, and collect what it returns",
+			),
+			|interpreter, args| {
+				Ok(LiteralType::array(Self::call_for_indices(interpreter, "repeat_call", &args)?))
+			},
+		);
+
+		interpreter.define_native(
+			"build_string",
+			2,
+			NativeDocs::new(
+				"strings",
+				"build_string(n, f)",
+				"Join what f(index) returns for indices 0 up to n into one string",
+			),
+			|interpreter, args| {
+				let count = Self::safe_integer("build_string", &args[0])?;
+				let function = Self::expect_callable(interpreter, "build_string", &args[1], 1)?;
+				// One buffer grows for the whole string instead of a new string every time round
+				let mut built = String::new();
+				for i in 0..count {
+					interpreter.native_iteration()?;
+					match function.call(interpreter, vec![LiteralType::Number(i as f64)])? {
+						LiteralType::String(s) => built.push_str(&s),
+						value => built.push_str(&value.to_string()),
+					}
+					interpreter.check_string_length(0, built.len())?;
+				}
+				Ok(LiteralType::String(built))
+			},
+		);
+	}
+
+	/// to_hex(n) and to_bin(n) format a whole number as lowercase digits without a prefix
+	fn define_to_radix(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"to_hex",
+			1,
+			NativeDocs::new(
+				"numbers",
+				"to_hex(n)",
+				"Write a whole number as lowercase hexadecimal digits",
+			),
+			|_env, args| {
+				Ok(LiteralType::String(format!("{:x}", Self::safe_integer("to_hex", &args[0])?)))
+			},
+		);
+
+		interpreter.define_native(
+			"to_bin",
+			1,
+			NativeDocs::new("numbers", "to_bin(n)", "Write a whole number as binary digits"),
+			|_env, args| {
+				Ok(LiteralType::String(format!("{:b}", Self::safe_integer("to_bin", &args[0])?)))
+			},
+		);
+	}
+
+	/// from_hex(s) and from_bin(s) parse digits with or without a 0x/0b prefix
+	fn define_from_radix(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"from_hex",
+			1,
+			NativeDocs::new(
+				"numbers",
+				"from_hex(s)",
+				"Read hexadecimal digits, with or without a 0x prefix",
+			),
+			|_env, args| Self::parse_radix("from_hex", &args[0], &["0x", "0X"], 16),
+		);
+
+		interpreter.define_native(
+			"from_bin",
+			1,
+			NativeDocs::new(
+				"numbers",
+				"from_bin(s)",
+				"Read binary digits, with or without a 0b prefix",
+			),
+			|_env, args| Self::parse_radix("from_bin", &args[0], &["0b", "0B"], 2),
+		);
+	}
+
+	/// log_debug, log_info, log_warn and log_error write `[LEVEL timestamp] message` to stderr
+	fn define_log(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, NativeFn); 4] = [
+			("log_debug", "log_debug(message)", |interpreter, args| {
+				Self::log(interpreter, LogLevel::Debug, &args[0]);
+				Ok(LiteralType::Null)
+			}),
+			("log_info", "log_info(message)", |interpreter, args| {
+				Self::log(interpreter, LogLevel::Info, &args[0]);
+				Ok(LiteralType::Null)
+			}),
+			("log_warn", "log_warn(message)", |interpreter, args| {
+				Self::log(interpreter, LogLevel::Warn, &args[0]);
+				Ok(LiteralType::Null)
+			}),
+			("log_error", "log_error(message)", |interpreter, args| {
+				Self::log(interpreter, LogLevel::Error, &args[0]);
+				Ok(LiteralType::Null)
+			}),
+		];
+		for (name, signature, function) in natives {
+			let docs = NativeDocs::new(
+				"logging",
+				signature,
+				"Write a message where errors go, at this level",
+			);
+			interpreter.define_native(name, 1, docs, function);
+		}
+	}
+
+	/// set_log_level(level) hides log messages below "debug", "info", "warn" or "error"
+	fn define_set_log_level(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"set_log_level",
+			1,
+			NativeDocs::new(
+				"logging",
+				"set_log_level(level)",
+				"Hide log messages below \"debug\", \"info\", \"warn\" or \"error\"",
+			),
+			|interpreter, args| {
+				let level = match &args[0] {
+					LiteralType::String(s) => LogLevel::parse(s),
+					_ => None,
+				};
+				let Some(level) = level else {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"set_log_level expects one of \"debug\", \"info\", \"warn\" or \"error\" but found {}",
+							args[0].render(&interpreter.render_limits)
+						),
+					));
+				};
+				interpreter.log_level = level;
+				Ok(LiteralType::Null)
+			},
+		);
+	}
+
+	/// flatten(array) concatenates one level of nested arrays, other elements pass through.
+	/// flatten_deep(array) keeps going until no nested arrays remain, and errors on an array that
+	/// contains itself as that would never finish
+	fn define_flatten(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"flatten",
+			1,
+			NativeDocs::new("arrays", "flatten(array)", "Concatenate one level of nested arrays"),
+			|interpreter, args| {
+				let mut flattened = Vec::new();
+				for value in Self::expect_array(interpreter, "flatten", &args[0])? {
+					match value {
+						LiteralType::Array(nested) =>
+							flattened.extend(nested.borrow().iter().cloned()),
+						_ => flattened.push(value),
+					}
+				}
+				Ok(LiteralType::array(flattened))
+			},
+		);
+
+		interpreter.define_native(
+			"flatten_deep",
+			1,
+			NativeDocs::new(
+				"arrays",
+				"flatten_deep(array)",
+				"Concatenate nested arrays until none are left",
+			),
+			|interpreter, args| {
+				fn flatten_into(
+					array: &ArrayRef,
+					parents: &mut Vec<ArrayRef>,
+					flattened: &mut Vec<LiteralType>,
+				) -> Result<(), Error> {
+					if parents.iter().any(|parent| Rc::ptr_eq(parent, array)) {
+						return Err(Error::RuntimeError(
+							Span::default(),
+							"flatten_deep can't flatten an array that contains itself".to_string(),
+						));
+					}
+					parents.push(Rc::clone(array));
+					for value in array.borrow().iter() {
+						match value {
+							LiteralType::Array(nested) => flatten_into(nested, parents, flattened)?,
+							_ => flattened.push(value.clone()),
+						}
+					}
+					parents.pop();
+					Ok(())
+				}
+				let LiteralType::Array(array) = &args[0] else {
+					return Err(
+						Self::expect_array(interpreter, "flatten_deep", &args[0]).unwrap_err()
+					);
+				};
+				let mut flattened = Vec::new();
+				flatten_into(array, &mut Vec::new(), &mut flattened)?;
+				Ok(LiteralType::array(flattened))
+			},
+		);
+	}
+
+	/// flat_map(array, f) calls f on every element and flattens any arrays it returns
+	fn define_flat_map(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"flat_map",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"flat_map(array, f)",
+				"Call f on every element and flatten the arrays it returns",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "flat_map", &args[0])?;
+				let function = Self::expect_callable(interpreter, "flat_map", &args[1], 1)?;
+				let mut flattened = Vec::new();
+				for value in values {
+					match function.call(interpreter, vec![value.clone()])? {
+						LiteralType::Array(nested) =>
+							flattened.extend(nested.borrow().iter().cloned()),
+						result => flattened.push(result),
+					}
+				}
+				Ok(LiteralType::array(flattened))
+			},
+		);
+	}
+
+	/// inspect_full(value) renders the whole value as a string, ignoring the print limits
+	fn define_inspect_full(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"inspect_full",
+			1,
+			NativeDocs::new(
+				"values",
+				"inspect_full(value)",
+				"Render the whole value as a string, ignoring the print limits",
+			),
+			|_env, args| Ok(LiteralType::String(args[0].render(&RenderLimits::unlimited()))),
+		);
+	}
+
+	/// try_call(f, args) calls f with the arguments in the args array. It returns [yeah, result]
+	/// if the call worked or [nah, message] if it hit a runtime error, the error isn't reported
+	fn define_try_call(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"try_call",
+			2,
+			NativeDocs::new(
+				"functions",
+				"try_call(f, args)",
+				"Call f with the args array, giving [yeah, result] or [nah, error message]",
+			),
+			|interpreter, args| {
+				let LiteralType::Callable(function) = &args[0] else {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"try_call expects a function but found {}",
+							args[0].render(&interpreter.render_limits)
+						),
+					));
+				};
+				let arguments = Self::expect_array(interpreter, "try_call", &args[1])?;
+				if !function.accepts(arguments.len()) {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"try_call expected {} arguments but found {}.",
+							function.expected_arguments(),
+							arguments.len()
+						),
+					));
+				}
+				match function.call(interpreter, arguments.clone()) {
+					Ok(value) => Ok(LiteralType::array(vec![LiteralType::Bool(true), value])),
+					Err(Error::RuntimeError(_, message)) => Ok(LiteralType::array(vec![
+						LiteralType::Bool(false),
+						LiteralType::String(message),
+					])),
+					// Anything else, like an interrupt, keeps unwinding
+					Err(error) => Err(error),
+				}
+			},
+		);
+	}
+
+	/// min_by(array, key) and max_by(array, key) return the element whose key is smallest or
+	/// largest, or null for an empty array. Keys must be all numbers or all strings
+	fn define_min_max_by(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"min_by",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"min_by(array, key)",
+				"The element with the smallest key, or null for an empty array",
+			),
+			|interpreter, args| Self::extreme_by(interpreter, "min_by", &args, Ordering::Less),
+		);
+
+		interpreter.define_native(
+			"max_by",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"max_by(array, key)",
+				"The element with the largest key, or null for an empty array",
+			),
+			|interpreter, args| Self::extreme_by(interpreter, "max_by", &args, Ordering::Greater),
+		);
+	}
+
+	/// find(array, predicate) returns the first element the predicate is truthy for, or null.
+	/// find_index(array, predicate) returns its index instead, or -1
+	fn define_find(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"find",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"find(array, predicate)",
+				"The first element the predicate is truthy for, or null",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "find", &args[0])?;
+				let predicate = Self::expect_callable(interpreter, "find", &args[1], 1)?;
+				for value in values {
+					if predicate.call(interpreter, vec![value.clone()])?.is_truthy() {
+						return Ok(value.clone());
+					}
+				}
+				Ok(LiteralType::Null)
+			},
+		);
+
+		interpreter.define_native(
+			"find_index",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"find_index(array, predicate)",
+				"The index of the first element the predicate is truthy for, or -1",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "find_index", &args[0])?;
+				let predicate = Self::expect_callable(interpreter, "find_index", &args[1], 1)?;
+				for (i, value) in values.iter().enumerate() {
+					if predicate.call(interpreter, vec![value.clone()])?.is_truthy() {
+						return Ok(LiteralType::Number(i as f64));
+					}
+				}
+				Ok(LiteralType::Number(-1.0))
+			},
+		);
+	}
+
+	/// get_or(object, name, default) reads a field of an instance or a key of a map, returning
+	/// default instead of an error when it doesn't exist
+	fn define_get_or(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"get_or",
+			3,
+			NativeDocs::new(
+				"values",
+				"get_or(object, name, default)",
+				"Read a field of an instance or a key of a map, giving default when it doesn't exist",
+			),
+			|interpreter, args| {
+				let LiteralType::String(name) = &args[1] else {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"get_or expects a field name but found {}",
+							args[1].render(&interpreter.render_limits)
+						),
+					));
+				};
+				match &args[0] {
+					LiteralType::Callable(Callable::Instance(instance)) =>
+						Ok(JasnInstance::lookup(instance, name).unwrap_or_else(|| args[2].clone())),
+					LiteralType::Map(map) =>
+						Ok(map.borrow().get(name).cloned().unwrap_or_else(|| args[2].clone())),
+					other => Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"get_or expects an instance or a map but found {}",
+							other.render(&interpreter.render_limits)
+						),
+					)),
+				}
+			},
+		);
+	}
+
+	/// unique(array) returns the elements with later duplicates removed
+	fn define_unique(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"unique",
+			1,
+			NativeDocs::new(
+				"arrays",
+				"unique(array)",
+				"The elements with later duplicates removed",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "unique", &args[0])?;
+				// Equal values hash the same, so only values in the same bucket need comparing
+				let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+				let mut unique: Vec<LiteralType> = Vec::new();
+				for value in values {
+					let mut hasher = DefaultHasher::new();
+					value.hash(&mut hasher);
+					let bucket = seen.entry(hasher.finish()).or_default();
+					if !bucket.iter().any(|&i| unique[i] == value) {
+						bucket.push(unique.len());
+						unique.push(value);
+					}
+				}
+				Ok(LiteralType::array(unique))
+			},
+		);
+	}
+
+	/// count_of(array, value) counts the elements equal to value
+	fn define_count_of(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"count_of",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"count_of(array, value)",
+				"Count the elements equal to value",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "count_of", &args[0])?;
+				let count = values.iter().filter(|value| **value == args[1]).count();
+				Ok(LiteralType::Number(count as f64))
+			},
+		);
+	}
+
+	/// group_by(array, key_function) groups the elements by the string form of their key. The
+	/// groups come back as a map from each key to its elements, which keep their original order
+	fn define_group_by(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"group_by",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"group_by(array, key)",
+				"A map from the string form of each element's key to the elements with that key",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "group_by", &args[0])?;
+				let key_function = Self::expect_callable(interpreter, "group_by", &args[1], 1)?;
+				let mut groups: BTreeMap<String, Vec<LiteralType>> = BTreeMap::new();
+				for value in values {
+					let key = key_function.call(interpreter, vec![value.clone()])?.to_string();
+					groups.entry(key).or_default().push(value);
+				}
+				let groups = groups
+					.into_iter()
+					.map(|(key, values)| (key, LiteralType::array(values)))
+					.collect();
+				Ok(LiteralType::map(groups))
+			},
+		);
+	}
+
+	/// swap(array, i, j) swaps two elements in place and returns the array
+	fn define_swap(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"swap",
+			3,
+			NativeDocs::new(
+				"arrays",
+				"swap(array, i, j)",
+				"Swap two elements in place and return the array",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "swap", &args[0])?;
+				let length = array.borrow().len();
+				let i = Self::expect_index(interpreter, "swap", &args[1], length)?;
+				let j = Self::expect_index(interpreter, "swap", &args[2], length)?;
+				array.borrow_mut().swap(i, j);
+				Ok(args[0].clone())
+			},
+		);
+	}
+
+	/// rotate(array, n) moves every element n places to the right in place, wrapping around the
+	/// end. A negative n rotates left. Returns the array
+	fn define_rotate(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"rotate",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"rotate(array, n)",
+				"Move every element n places right in place, wrapping around, and return the array",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "rotate", &args[0])?;
+				let n = Self::expect_whole_number(interpreter, "rotate", "amount", &args[1])?;
+				let length = array.borrow().len();
+				if length > 0 {
+					let places = n.rem_euclid(length as f64) as usize;
+					array.borrow_mut().rotate_right(places);
+				}
+				Ok(args[0].clone())
+			},
+		);
+	}
+
+	/// shuffle(array) puts the elements in a random order in place and returns the array. The
+	/// order comes from the same generator as random, so random_seed makes it repeatable
+	fn define_shuffle(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"shuffle",
+			1,
+			NativeDocs::new(
+				"random",
+				"shuffle(array)",
+				"Put the elements in a random order in place and return the array",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "shuffle", &args[0])?;
+				interpreter.rng.shuffle(&mut array.borrow_mut());
+				Ok(args[0].clone())
+			},
+		);
+	}
+
+	/// breakpoint() pauses the script into the debug prompt
+	fn define_breakpoint(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"breakpoint",
+			0,
+			NativeDocs::new("debugging", "breakpoint()", "Pause the script into the debug prompt"),
+			|interpreter, _args| {
+				debugger::prompt(interpreter, None)?;
+				Ok(LiteralType::Null)
+			},
+		);
+	}
+
+	/// globals() names every global the script can see, natives and the prelude included, so a
+	/// script can look at itself. Sorted so it is the same every run
+	fn define_globals(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"globals",
+			0,
+			NativeDocs::new(
+				"debugging",
+				"globals()",
+				"The names of every global variable, function, class and native, sorted",
+			),
+			|interpreter, _args| {
+				let names = interpreter.global.borrow().names();
+				Ok(LiteralType::array(names.into_iter().map(LiteralType::String).collect()))
+			},
+		);
+	}
+
+	/// help() lists every native by category and help(name) describes one of them
+	fn define_help(interpreter: &mut Interpreter) {
+		let docs = NativeDocs::new(
+			"debugging",
+			"help(name?)",
+			"List the natives, or describe the one called name",
+		);
+		interpreter.define_native_range("help", 0..=1, docs, |interpreter, args| {
+			let mut text = String::new();
+			match args.first() {
+				None => {
+					let mut categories: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+					for native in interpreter.natives() {
+						categories.entry(native.docs.category).or_default().push(&native.name);
+					}
+					text.push_str("Natives by category, help(\"name\") describes one:\n");
+					for (category, names) in categories {
+						text.push_str(&format!("  {}: {}\n", category, names.join(", ")));
+					}
+				},
+				Some(LiteralType::String(name)) => {
+					let native = interpreter.natives().find(|native| native.name == *name);
+					let Some(native) = native else {
+						return Err(Error::RuntimeError(
+							Span::default(),
+							format!("help doesn't know a native called '{}'", name),
+						));
+					};
+					text.push_str(&format!(
+						"{}\n  {}\n",
+						native.docs.signature, native.docs.description
+					));
+				},
+				Some(value) => Err(Error::RuntimeError(
+					Span::default(),
+					format!("help expects the name of a native but found {}", value.type_name()),
+				))?,
+			}
+			write!(interpreter.output.borrow_mut(), "{}", text).map_err(|e| {
+				Error::RuntimeError(Span::default(), format!("help couldn't write: {}", e))
+			})?;
+			Ok(LiteralType::Null)
+		});
+	}
+
+	/// time_it(f, args, times) calls f with the arguments in the args array, or none, and returns
+	/// [milliseconds, result]. Called more than once it returns the fastest time and the last
+	/// result, which is steadier for timing small functions
+	fn define_time_it(interpreter: &mut Interpreter) {
+		let docs = NativeDocs::new(
+			"time",
+			"time_it(f, args?, times?)",
+			"Call f with the args array and give [milliseconds taken, result], the fastest of times calls",
+		);
+		interpreter.define_native_range("time_it", 1..=3, docs, |interpreter, args| {
+			let LiteralType::Callable(function) = &args[0] else {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					format!("time_it expects a function but found {}", args[0].type_name()),
+				));
+			};
+			let arguments = match args.get(1) {
+				Some(arguments) => Self::expect_array(interpreter, "time_it", arguments)?,
+				None => Vec::new(),
+			};
+			if !function.accepts(arguments.len()) {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					format!(
+						"time_it expected {} arguments but found {}.",
+						function.expected_arguments(),
+						arguments.len()
+					),
+				));
+			}
+			let times = match args.get(2) {
+				Some(times) =>
+					Self::expect_whole_number(interpreter, "time_it", "number of times", times)?,
+				None => 1.0,
+			};
+			if times < 1.0 {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					format!(
+						"time_it needs to call the function at least once, not {} times",
+						times
+					),
+				));
+			}
+			let mut fastest = f64::INFINITY;
+			let mut result = LiteralType::Null;
+			for _ in 0..times as u64 {
+				let start = Instant::now();
+				result = function.call(interpreter, arguments.clone())?;
+				fastest = fastest.min(start.elapsed().as_secs_f64() * 1000.0);
+			}
+			Ok(LiteralType::array(vec![LiteralType::Number(fastest), result]))
+		});
+	}
+
+	/// starts_with(value, prefix) and ends_with(value, suffix) work on two strings or on two
+	/// arrays, where the elements have to be equal one by one
+	fn define_starts_ends_with(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"starts_with",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"starts_with(value, prefix)",
+				"Whether a string or array begins with the given string or array",
+			),
+			|_env, args| {
+				let starts = match (&args[0], &args[1]) {
+					(LiteralType::String(s), LiteralType::String(prefix)) => s.starts_with(prefix),
+					(LiteralType::Array(a), LiteralType::Array(prefix)) =>
+						a.borrow().starts_with(&prefix.borrow()),
+					_ => return Err(Self::sequence_pair_error("starts_with", &args)),
+				};
+				Ok(LiteralType::Bool(starts))
+			},
+		);
+
+		interpreter.define_native(
+			"ends_with",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"ends_with(value, suffix)",
+				"Whether a string or array finishes with the given string or array",
+			),
+			|_env, args| {
+				let ends = match (&args[0], &args[1]) {
+					(LiteralType::String(s), LiteralType::String(suffix)) => s.ends_with(suffix),
+					(LiteralType::Array(a), LiteralType::Array(suffix)) =>
+						a.borrow().ends_with(&suffix.borrow()),
+					_ => return Err(Self::sequence_pair_error("ends_with", &args)),
+				};
+				Ok(LiteralType::Bool(ends))
+			},
+		);
+	}
+
+	fn sequence_pair_error(name: &str, args: &[LiteralType]) -> Error {
+		Error::RuntimeError(
+			Span::default(),
+			format!(
+				"{} expects two strings or two arrays but found {} and {}",
+				name,
+				args[0].type_name(),
+				args[1].type_name()
+			),
+		)
+	}
+
+	/// windows(array, n) returns every run of n neighbouring elements, overlapping, in order.
+	/// There are none when n is longer than the array
+	fn define_windows(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"windows",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"windows(array, n)",
+				"Every run of n neighbouring elements, as arrays that overlap",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "windows", &args[0])?;
+				let size = Self::expect_whole_number(interpreter, "windows", "size", &args[1])?;
+				if size < 1.0 {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!("windows needs a size of at least 1 but found {}", size),
+					));
+				}
+				let windows = array
+					.borrow()
+					.windows(size.min(usize::MAX as f64) as usize)
+					.map(|window| LiteralType::array(window.to_vec()))
+					.collect();
+				Ok(LiteralType::array(windows))
+			},
+		);
+	}
+
+	/// zip_with(a, b, f) calls f with the elements at each index of a and b, stopping at the end
+	/// of the shorter array
+	fn define_zip_with(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"zip_with",
+			3,
+			NativeDocs::new(
+				"arrays",
+				"zip_with(a, b, f)",
+				"f(a[i], b[i]) for each index, up to the length of the shorter array",
+			),
+			|interpreter, args| {
+				let a = Self::expect_array(interpreter, "zip_with", &args[0])?;
+				let b = Self::expect_array(interpreter, "zip_with", &args[1])?;
+				let function = Self::expect_callable(interpreter, "zip_with", &args[2], 2)?;
+				let mut values = Vec::with_capacity(a.len().min(b.len()));
+				for (x, y) in a.into_iter().zip(b) {
+					values.push(function.call(interpreter, vec![x, y])?);
+				}
+				Ok(LiteralType::array(values))
+			},
+		);
+	}
+
+	/// Check that an argument is an array of numbers, naming the index of the first element
+	/// that isn't one
+	fn expect_numbers(
+		interpreter: &Interpreter,
+		name: &str,
+		value: &LiteralType,
+	) -> Result<Vec<f64>, Error> {
+		Self::expect_array_ref(interpreter, name, value)?
+			.borrow()
+			.iter()
+			.enumerate()
+			.map(|(i, element)| match element {
+				LiteralType::Number(n) => Ok(*n),
+				_ => Err(Error::RuntimeError(
+					Span::default(),
+					format!(
+						"{} expects an array of numbers but found {} at index {}",
+						name,
+						element.type_name(),
+						i
+					),
+				)),
+			})
+			.collect()
+	}
+
+	/// add_arrays(a, b) adds two arrays of numbers of the same length element by element, without
+	/// calling back into the interpreter for each one like zip_with does
+	fn define_add_arrays(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"add_arrays",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"add_arrays(a, b)",
+				"a[i] + b[i] for each index of two arrays of numbers with the same length",
+			),
+			|interpreter, args| {
+				let a = Self::expect_numbers(interpreter, "add_arrays", &args[0])?;
+				let b = Self::expect_numbers(interpreter, "add_arrays", &args[1])?;
+				if a.len() != b.len() {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"add_arrays expects arrays of the same length but found {} and {}",
+							a.len(),
+							b.len()
+						),
+					));
+				}
+				let sums = a.iter().zip(&b).map(|(x, y)| LiteralType::Number(x + y)).collect();
+				Ok(LiteralType::array(sums))
+			},
+		);
+	}
+
+	/// scale(a, k) multiplies every element of an array of numbers by k
+	fn define_scale(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"scale",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"scale(a, k)",
+				"a[i] * k for each element of an array of numbers",
+			),
+			|interpreter, args| {
+				let values = Self::expect_numbers(interpreter, "scale", &args[0])?;
+				let LiteralType::Number(k) = args[1] else {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"scale expects a number to scale by but found {}",
+							args[1].type_name()
+						),
+					));
+				};
+				Ok(LiteralType::array(values.iter().map(|n| LiteralType::Number(n * k)).collect()))
+			},
+		);
+	}
+
+	/// chunk(array, n) splits an array into consecutive arrays of n elements, the last one has
+	/// whatever is left over
+	fn define_chunk(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"chunk",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"chunk(array, n)",
+				"Split into consecutive arrays of n elements, the last one can be shorter",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "chunk", &args[0])?;
+				let size = Self::expect_whole_number(interpreter, "chunk", "size", &args[1])?;
+				if size < 1.0 {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!("chunk needs a size of at least 1 but found {}", size),
+					));
+				}
+				let chunks = array
+					.borrow()
+					.chunks(size.min(usize::MAX as f64) as usize)
+					.map(|chunk| LiteralType::array(chunk.to_vec()))
+					.collect();
+				Ok(LiteralType::array(chunks))
+			},
+		);
+	}
+
+	/// partition(array, predicate) splits an array into [matching, not_matching], calling the
+	/// predicate once for each element in order. Both keep the original order
+	fn define_partition(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"partition",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"partition(array, predicate)",
+				"[elements the predicate is truthy for, the rest], both in order",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "partition", &args[0])?;
+				let predicate = Self::expect_callable(interpreter, "partition", &args[1], 1)?;
+				let (mut matching, mut rest) = (Vec::new(), Vec::new());
+				for value in values {
+					if predicate.call(interpreter, vec![value.clone()])?.is_truthy() {
+						matching.push(value);
+					} else {
+						rest.push(value);
+					}
+				}
+				Ok(LiteralType::array(vec![LiteralType::array(matching), LiteralType::array(rest)]))
+			},
+		);
+	}
+
+	/// The order sort and cmp use: numbers from -inf to inf with NaN after all of them, or strings
+	/// by their characters. Anything else, or a number against a string, can't be ordered
+	fn total_order(name: &str, a: &LiteralType, b: &LiteralType) -> Result<Ordering, Error> {
+		match (a, b) {
+			(LiteralType::Number(a), LiteralType::Number(b)) =>
+				Ok(match (a.is_nan(), b.is_nan()) {
+					(true, true) => Ordering::Equal,
+					(true, false) => Ordering::Greater,
+					(false, true) => Ordering::Less,
+					(false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+				}),
+			(LiteralType::String(a), LiteralType::String(b)) => Ok(a.cmp(b)),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} can only order two numbers or two strings but found {} and {}",
+					name,
+					a.type_name(),
+					b.type_name()
+				),
+			)),
+		}
+	}
+
+	/// A stable merge sort that stops at the first error from compare. Unlike the standard
+	/// library's sorts it copes with a comparator that isn't consistent, which a script's can be
+	fn merge_sort(
+		values: Vec<LiteralType>,
+		compare: &mut impl FnMut(&LiteralType, &LiteralType) -> Result<Ordering, Error>,
+	) -> Result<Vec<LiteralType>, Error> {
+		if values.len() <= 1 {
+			return Ok(values);
+		}
+		let mut left = values;
+		let right = left.split_off(left.len() / 2);
+		let left = Self::merge_sort(left, compare)?;
+		let right = Self::merge_sort(right, compare)?;
+		let mut merged = Vec::with_capacity(left.len() + right.len());
+		let mut right = right.into_iter().peekable();
+		for value in left {
+			// Equal elements from the left half go first so the sort is stable
+			while let Some(next) = right.peek() {
+				if compare(next, &value)? != Ordering::Less {
+					break;
+				}
+				merged.extend(right.next());
+			}
+			merged.push(value);
+		}
+		merged.extend(right);
+		Ok(merged)
+	}
+
+	/// cmp(a, b) gives -1, 0 or 1 for the order sort puts two numbers or two strings in
+	fn define_cmp(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"cmp",
+			2,
+			NativeDocs::new(
+				"values",
+				"cmp(a, b)",
+				"-1, 0 or 1 as a goes before, with or after b when sorted, NaN goes last",
+			),
+			|_env, args| {
+				let order = Self::total_order("cmp", &args[0], &args[1])?;
+				Ok(LiteralType::Number(order as i8 as f64))
+			},
+		);
+	}
+
+	/// sort(array) sorts numbers or strings in place and returns the array. Numbers go from
+	/// smallest to largest with NaN at the end, strings go by their characters
+	fn define_sort(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"sort",
+			1,
+			NativeDocs::new(
+				"arrays",
+				"sort(array)",
+				"Sort numbers or strings in place and return the array, NaN goes last",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "sort", &args[0])?;
+				let values = array.borrow().clone();
+				let sorted = Self::merge_sort(values, &mut |a, b| Self::total_order("sort", a, b))?;
+				*array.borrow_mut() = sorted;
+				Ok(args[0].clone())
+			},
+		);
+	}
+
+	/// sort_by(array, comparator) sorts in place using comparator(a, b), which returns a negative
+	/// number when a goes first, a positive one when b does and 0 when it doesn't matter. It
+	/// has to be a finite number. Returns the array
+	fn define_sort_by(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"sort_by",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"sort_by(array, comparator)",
+				"Sort in place by comparator(a, b): negative if a goes first, positive if b does",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "sort_by", &args[0])?;
+				let comparator = Self::expect_callable(interpreter, "sort_by", &args[1], 2)?;
+				let values = array.borrow().clone();
+				let sorted = Self::merge_sort(values, &mut |a, b| match comparator
+					.call(interpreter, vec![a.clone(), b.clone()])?
+				{
+					LiteralType::Number(n) if n.is_finite() =>
+						Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+					result => Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+								"sort_by expects the comparator to return a finite number but it returned {} for {} and {}",
+								result.render(&interpreter.render_limits),
+								a.render(&interpreter.render_limits),
+								b.render(&interpreter.render_limits)
+							),
+					)),
+				})?;
+				*array.borrow_mut() = sorted;
+				Ok(args[0].clone())
+			},
+		);
+	}
+
+	/// Check that an argument is a number
+	fn expect_number(name: &str, value: &LiteralType) -> Result<f64, Error> {
+		match value {
+			LiteralType::Number(n) => Ok(*n),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects a number but found {}", name, value.type_name()),
+			)),
+		}
+	}
+
+	/// The math natives: sqrt, abs, pow, floor, ceil and round, along with the PI and E
+	/// constants. sqrt of a negative number is an error rather than NaN so the mistake shows up
+	/// where it happens
+	fn define_math(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, &str, NativeFn); 5] = [
+			("sqrt", "sqrt(n)", "The square root of a number that isn't negative", |_env, args| {
+				let n = Self::expect_number("sqrt", &args[0])?;
+				if n < 0.0 {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"sqrt can't take the square root of a negative number ({}), it has no real answer",
+							n
+						),
+					));
+				}
+				Ok(LiteralType::Number(n.sqrt()))
+			}),
+			("abs", "abs(n)", "The distance of a number from 0", number_native!("abs", f64::abs)),
+			(
+				"floor",
+				"floor(n)",
+				"The largest whole number at or below n",
+				number_native!("floor", f64::floor),
+			),
+			(
+				"ceil",
+				"ceil(n)",
+				"The smallest whole number at or above n",
+				number_native!("ceil", f64::ceil),
+			),
+			(
+				"round",
+				"round(n)",
+				"The nearest whole number, halves round away from 0",
+				number_native!("round", f64::round),
+			),
+		];
+		for (name, signature, description, function) in natives {
+			interpreter.define_native(
+				name,
+				1,
+				NativeDocs::new("numbers", signature, description),
+				function,
+			);
+		}
+
+		interpreter.define_native(
+			"pow",
+			2,
+			NativeDocs::new(
+				"numbers",
+				"pow(base, exponent)",
+				"base raised to the power of exponent",
+			),
+			|_env, args| {
+				let base = Self::expect_number("pow", &args[0])?;
+				let exponent = Self::expect_number("pow", &args[1])?;
+				Ok(LiteralType::Number(base.powf(exponent)))
+			},
+		);
+
+		let mut global = interpreter.global.borrow_mut();
+		global.define("PI".to_string(), LiteralType::Number(std::f64::consts::PI));
+		global.define("E".to_string(), LiteralType::Number(std::f64::consts::E));
+	}
+
+	/// min(a, b) and max(a, b) pick the smaller or larger of two numbers, min(array) and
+	/// max(array) the smallest or largest number in an array. NaN is skipped unless there is
+	/// nothing else
+	fn define_min_max(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, &str, NativeFn); 2] = [
+			(
+				"min",
+				"min(a, b) or min(array)",
+				"The smaller of two numbers or the smallest number in an array",
+				|interpreter, args| Self::extreme(interpreter, "min", &args, f64::min),
+			),
+			(
+				"max",
+				"max(a, b) or max(array)",
+				"The larger of two numbers or the largest number in an array",
+				|interpreter, args| Self::extreme(interpreter, "max", &args, f64::max),
+			),
+		];
+		for (name, signature, description, function) in natives {
+			let docs = NativeDocs::new("numbers", signature, description);
+			interpreter.define_native_range(name, 1..=2, docs, function);
+		}
+	}
+
+	// Pick between two numbers or all the numbers of an array with pick
+	fn extreme(
+		interpreter: &Interpreter,
+		name: &str,
+		args: &[LiteralType],
+		pick: fn(f64, f64) -> f64,
+	) -> Result<LiteralType, Error> {
+		let numbers = match args {
+			[array] => Self::expect_numbers(interpreter, name, array)?,
+			[a, b] => vec![Self::expect_number(name, a)?, Self::expect_number(name, b)?],
+			_ => unreachable!("the arity is checked before the call"),
+		};
+		let Some(first) = numbers.first() else {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} of an empty array has no answer", name),
+			));
+		};
+		Ok(LiteralType::Number(numbers[1..].iter().fold(*first, |best, n| pick(best, *n))))
+	}
+
+	/// The trig, logarithm and exponential natives. Angles are in radians. These follow the
+	/// usual floating point rules, so asin(2) or log(-1) give NaN
+	fn define_trig(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, &str, NativeFn); 9] = [
+			("sin", "sin(x)", "The sine of an angle in radians", number_native!("sin", f64::sin)),
+			("cos", "cos(x)", "The cosine of an angle in radians", number_native!("cos", f64::cos)),
+			(
+				"tan",
+				"tan(x)",
+				"The tangent of an angle in radians",
+				number_native!("tan", f64::tan),
+			),
+			(
+				"asin",
+				"asin(x)",
+				"The angle in radians whose sine is x, NaN outside -1 to 1",
+				number_native!("asin", f64::asin),
+			),
+			(
+				"acos",
+				"acos(x)",
+				"The angle in radians whose cosine is x, NaN outside -1 to 1",
+				number_native!("acos", f64::acos),
+			),
+			(
+				"atan",
+				"atan(x)",
+				"The angle in radians whose tangent is x",
+				number_native!("atan", f64::atan),
+			),
+			(
+				"log",
+				"log(x)",
+				"The natural logarithm of x, NaN when x is negative",
+				number_native!("log", f64::ln),
+			),
+			(
+				"log10",
+				"log10(x)",
+				"The base 10 logarithm of x, NaN when x is negative",
+				number_native!("log10", f64::log10),
+			),
+			("exp", "exp(x)", "E raised to the power of x", number_native!("exp", f64::exp)),
+		];
+		for (name, signature, description, function) in natives {
+			interpreter.define_native(
+				name,
+				1,
+				NativeDocs::new("numbers", signature, description),
+				function,
+			);
+		}
+
+		interpreter.define_native(
+			"atan2",
+			2,
+			NativeDocs::new(
+				"numbers",
+				"atan2(y, x)",
+				"The angle in radians from the x axis to the point (x, y)",
+			),
+			|_env, args| {
+				let y = Self::expect_number("atan2", &args[0])?;
+				let x = Self::expect_number("atan2", &args[1])?;
+				Ok(LiteralType::Number(y.atan2(x)))
+			},
+		);
+	}
+
+	/// Check that an argument is a string, what names the argument in the error unless it is
+	/// the string being worked on
+	fn expect_string<'a>(name: &str, what: &str, value: &'a LiteralType) -> Result<&'a str, Error> {
+		match value {
+			LiteralType::String(s) => Ok(s),
+			_ if what.is_empty() => Err(Error::RuntimeError(
+				Span::default(),
+				format!("{} expects a string but found {}", name, value.type_name()),
+			)),
+			_ => Err(Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects a string as the {} but found {}",
+					name,
+					what,
+					value.type_name()
+				),
+			)),
+		}
+	}
+
+	/// The string natives: upper, lower, trim, split, replace and contains, along with
+	/// starts_with, ends_with and index_of which work on arrays too
+	fn define_strings(interpreter: &mut Interpreter) {
+		let natives: [(&str, &str, &str, NativeFn); 3] = [
+			("upper", "upper(s)", "s with every letter in upper case", |_env, args| {
+				let s = Self::expect_string("upper", "", &args[0])?;
+				Ok(LiteralType::String(s.to_uppercase()))
+			}),
+			("lower", "lower(s)", "s with every letter in lower case", |_env, args| {
+				let s = Self::expect_string("lower", "", &args[0])?;
+				Ok(LiteralType::String(s.to_lowercase()))
+			}),
+			("trim", "trim(s)", "s without whitespace at the start or end", |_env, args| {
+				let s = Self::expect_string("trim", "", &args[0])?;
+				Ok(LiteralType::String(s.trim().to_string()))
+			}),
+		];
+		for (name, signature, description, function) in natives {
+			interpreter.define_native(
+				name,
+				1,
+				NativeDocs::new("strings", signature, description),
+				function,
+			);
+		}
+
+		let natives: [(&str, &str, &str, NativeFn); 2] = [
+			(
+				"split",
+				"split(s, separator)",
+				"The parts of s between each separator, or its characters when separator is empty",
+				|_env, args| {
+					let s = Self::expect_string("split", "", &args[0])?;
+					let separator = Self::expect_string("split", "separator", &args[1])?;
+					let parts: Vec<LiteralType> = if separator.is_empty() {
+						s.chars().map(|c| LiteralType::String(c.to_string())).collect()
+					} else {
+						s.split(separator)
+							.map(|part| LiteralType::String(part.to_string()))
+							.collect()
+					};
+					Ok(LiteralType::array(parts))
+				},
+			),
+			("contains", "contains(s, needle)", "Whether needle appears in s", |_env, args| {
+				let s = Self::expect_string("contains", "", &args[0])?;
+				let needle = Self::expect_string("contains", "needle", &args[1])?;
+				Ok(LiteralType::Bool(s.contains(needle)))
+			}),
+		];
+		for (name, signature, description, function) in natives {
+			interpreter.define_native(
+				name,
+				2,
+				NativeDocs::new("strings", signature, description),
+				function,
+			);
+		}
+
+		interpreter.define_native(
+			"replace",
+			3,
+			NativeDocs::new("strings", "replace(s, from, to)", "s with every from replaced by to"),
+			|interpreter, args| {
+				let s = Self::expect_string("replace", "", &args[0])?;
+				let from = Self::expect_string("replace", "text to replace", &args[1])?;
+				let to = Self::expect_string("replace", "replacement", &args[2])?;
+				if from.is_empty() {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						"replace can't replace an empty string".to_string(),
+					));
+				}
+				// Check the length before building a string that is too long
+				let count = s.matches(from).count();
+				interpreter
+					.check_string_length(0, s.len() - count * from.len() + count * to.len())?;
+				Ok(LiteralType::String(s.replace(from, to)))
+			},
+		);
+	}
+
+	/// to_number(value) reads a number from a string, to_string(value) writes any value the way
+	/// print does and type_of(value) names the type of a value
+	fn define_conversions(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"to_number",
+			1,
+			NativeDocs::new(
+				"values",
+				"to_number(value)",
+				"The number written in a string like 1.5, 2e-9, inf or NaN, or null when it doesn't hold one",
+			),
+			|_env, args| match &args[0] {
+				LiteralType::Number(n) => Ok(LiteralType::Number(*n)),
+				LiteralType::String(s) => {
+					// The spellings to_string writes, Rust also reads words like infinity that
+					// JASN never writes
+					let s = s.trim();
+					let numeric = s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+					match s {
+						"inf" | "+inf" => Ok(LiteralType::Number(f64::INFINITY)),
+						"-inf" => Ok(LiteralType::Number(f64::NEG_INFINITY)),
+						"NaN" => Ok(LiteralType::Number(f64::NAN)),
+						_ => match s.parse() {
+							Ok(n) if numeric => Ok(LiteralType::Number(n)),
+							_ => Ok(LiteralType::Null),
+						},
+					}
+				},
+				value => Err(Error::RuntimeError(
+					Span::default(),
+					format!("to_number expects a string or number but found {}", value.type_name()),
+				)),
+			},
+		);
+
+		interpreter.define_native(
+			"to_string",
+			1,
+			NativeDocs::new(
+				"values",
+				"to_string(value)",
+				"The value written out the way print shows it",
+			),
+			|interpreter, args| {
+				let s = args[0].to_string();
+				interpreter.check_string_length(0, s.len())?;
+				Ok(LiteralType::String(s))
+			},
+		);
+
+		interpreter.define_native(
+			"type_of",
+			1,
+			NativeDocs::new(
+				"values",
+				"type_of(value)",
+				"The type of a value: number, string, bool, array, map, function, class, instance or null",
+			),
+			|_env, args| Ok(LiteralType::String(args[0].type_name().to_string())),
+		);
+	}
+
+	/// index_of(value, needle) finds a string in a string, counting characters like indexing
+	/// does rather than bytes, or an element in an array
+	fn define_index_of(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"index_of",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"index_of(value, needle)",
+				"Where needle first appears in a string or array, -1 when it isn't there",
+			),
+			|_env, args| {
+				let index = match (&args[0], &args[1]) {
+					(LiteralType::String(s), LiteralType::String(needle)) =>
+						s.find(needle.as_str()).map(|byte| s[..byte].chars().count()),
+					(LiteralType::String(_), needle) =>
+						return Err(Error::RuntimeError(
+							Span::default(),
+							format!(
+								"index_of can only find a string in a string but found {}",
+								needle.type_name()
+							),
+						)),
+					(LiteralType::Array(values), needle) =>
+						values.borrow().iter().position(|value| value == needle),
+					(value, _) =>
+						return Err(Error::RuntimeError(
+							Span::default(),
+							format!(
+								"index_of expects a string or array but found {}",
+								value.type_name()
+							),
+						)),
+				};
+				Ok(LiteralType::Number(index.map_or(-1.0, |index| index as f64)))
+			},
+		);
+	}
+
+	/// Check that an array can grow by one element, it isn't allowed past max_array_length
+	fn check_growth(interpreter: &Interpreter, name: &str, length: usize) -> Result<(), Error> {
+		match interpreter.limits().max_array_length {
+			Some(max) if length >= max => Err(interpreter.exceeded(
+				0,
+				Limit::ArrayLength(max),
+				format!("{} can't grow an array past {} elements", name, max),
+			)),
+			_ => Ok(()),
+		}
+	}
+
+	/// push, pop, insert and remove change the array they are given. Arrays are shared, so every
+	/// variable holding the same array sees the change, slice it with array[:] first to change
+	/// a copy instead. pop and remove both report an error when there is nothing to take out
+	fn define_push_pop(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"push",
+			2,
+			NativeDocs::new("arrays", "push(array, value)", "Add value to the end of an array"),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "push", &args[0])?;
+				Self::check_growth(interpreter, "push", array.borrow().len())?;
+				array.borrow_mut().push(args[1].clone());
+				Ok(LiteralType::Null)
+			},
+		);
+
+		interpreter.define_native(
+			"pop",
+			1,
+			NativeDocs::new(
+				"arrays",
+				"pop(array)",
+				"Take the last element off an array and return it",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "pop", &args[0])?;
+				let value = array.borrow_mut().pop();
+				value.ok_or_else(|| {
+					Error::RuntimeError(
+						Span::default(),
+						"pop can't take an element from an empty array".to_string(),
+					)
+				})
+			},
+		);
+
+		interpreter.define_native(
+			"insert",
+			3,
+			NativeDocs::new(
+				"arrays",
+				"insert(array, index, value)",
+				"Put value at index, moving the elements from there along by one",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "insert", &args[0])?;
+				let length = array.borrow().len();
+				// Negative indices count back from the end like indexing, and inserting at the
+				// length adds to the end
+				let n = Self::expect_whole_number(interpreter, "insert", "index", &args[1])?;
+				let index = if n < 0.0 { n + length as f64 } else { n };
+				if index < 0.0 || index > length as f64 {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!("insert index {} is out of bounds for length {}", n, length),
+					));
+				}
+				Self::check_growth(interpreter, "insert", length)?;
+				array.borrow_mut().insert(index as usize, args[2].clone());
+				Ok(LiteralType::Null)
+			},
+		);
+
+		interpreter.define_native(
+			"remove",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"remove(array, index)",
+				"Take the element at index out of an array and return it",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "remove", &args[0])?;
+				let index =
+					Self::expect_index(interpreter, "remove", &args[1], array.borrow().len())?;
+				let value = array.borrow_mut().remove(index);
+				Ok(value)
+			},
+		);
+	}
+
+	/// map, filter, reduce and each call a function on every element of an array, in order.
+	/// They go through a copy of the array, so the function changing it doesn't change which
+	/// elements are visited
+	fn define_map_filter(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"map",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"map(array, f)",
+				"A new array of what f returns for each element",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "map", &args[0])?;
+				let function = Self::expect_callable(interpreter, "map", &args[1], 1)?;
+				let mut mapped = Vec::with_capacity(values.len());
+				for value in values {
+					mapped.push(function.call(interpreter, vec![value])?);
+				}
+				Ok(LiteralType::array(mapped))
+			},
+		);
+
+		interpreter.define_native(
+			"filter",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"filter(array, predicate)",
+				"A new array of the elements predicate returns something truthy for",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "filter", &args[0])?;
+				let predicate = Self::expect_callable(interpreter, "filter", &args[1], 1)?;
+				let mut kept = Vec::new();
+				for value in values {
+					if predicate.call(interpreter, vec![value.clone()])?.is_truthy() {
+						kept.push(value);
+					}
+				}
+				Ok(LiteralType::array(kept))
+			},
+		);
+
+		interpreter.define_native(
+			"reduce",
+			3,
+			NativeDocs::new(
+				"arrays",
+				"reduce(array, initial, f)",
+				"Combine the elements from the left with f(total, element), starting from initial",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "reduce", &args[0])?;
+				let function = Self::expect_callable(interpreter, "reduce", &args[2], 2)?;
+				let mut total = args[1].clone();
+				for value in values {
+					total = function.call(interpreter, vec![total, value])?;
+				}
+				Ok(total)
+			},
+		);
+
+		interpreter.define_native(
+			"each",
+			2,
+			NativeDocs::new("arrays", "each(array, f)", "Call f on every element for what it does"),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "each", &args[0])?;
+				let function = Self::expect_callable(interpreter, "each", &args[1], 1)?;
+				for value in values {
+					function.call(interpreter, vec![value])?;
+				}
+				Ok(LiteralType::Null)
+			},
+		);
+	}
+
+	/// join(array, separator) writes every element the way print does with separator between
+	/// them, reverse(array) reverses in place like sort and returns the array, and concat(a, b)
+	/// leaves both arrays alone and returns a new one
+	fn define_join(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"join",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"join(array, separator)",
+				"The elements written out like print does, with separator between each pair",
+			),
+			|interpreter, args| {
+				let values = Self::expect_array(interpreter, "join", &args[0])?;
+				let separator = Self::expect_string("join", "separator", &args[1])?;
+				let mut joined = String::new();
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						joined.push_str(separator);
+					}
+					match value {
+						LiteralType::String(s) => joined.push_str(s),
+						value => joined.push_str(&value.to_string()),
+					}
+					interpreter.check_string_length(0, joined.len())?;
+				}
+				Ok(LiteralType::String(joined))
+			},
+		);
+
+		interpreter.define_native(
+			"reverse",
+			1,
+			NativeDocs::new(
+				"arrays",
+				"reverse(array)",
+				"Reverse the order of the elements in place and return the array",
+			),
+			|interpreter, args| {
+				Self::expect_array_ref(interpreter, "reverse", &args[0])?.borrow_mut().reverse();
+				Ok(args[0].clone())
+			},
+		);
+
+		interpreter.define_native(
+			"concat",
+			2,
+			NativeDocs::new(
+				"arrays",
+				"concat(a, b)",
+				"A new array with the elements of a followed by the elements of b",
+			),
+			|interpreter, args| {
+				let mut values = Self::expect_array(interpreter, "concat", &args[0])?;
+				let rest = Self::expect_array(interpreter, "concat", &args[1])?;
+				if let Some(max) = interpreter.limits().max_array_length {
+					if values.len() + rest.len() > max {
+						return Err(interpreter.exceeded(
+							0,
+							Limit::ArrayLength(max),
+							format!("concat can't make an array of more than {} elements", max),
+						));
+					}
+				}
+				values.extend(rest);
+				Ok(LiteralType::array(values))
+			},
+		);
+	}
+
+	/// range(end), range(start, end) and range(start, end, step) count from start, 0 if it isn't
+	/// given, up to but not including end in steps of step, 1 if it isn't given. A negative
+	/// step counts down. The whole array is made up front, so it is held to the array length
+	/// limit
+	fn define_range(interpreter: &mut Interpreter) {
+		let docs = NativeDocs::new(
+			"arrays",
+			"range(end) or range(start, end, step)",
+			"The numbers from start (default 0) up to but not including end, step (default 1) apart",
+		);
+		interpreter.define_native_range("range", 1..=3, docs, |interpreter, args| {
+			let mut numbers = Vec::with_capacity(args.len());
+			for arg in &args {
+				let n = Self::expect_number("range", arg)?;
+				if !n.is_finite() {
+					return Err(Error::RuntimeError(
+						Span::default(),
+						format!("range expects finite numbers but found {}", n),
+					));
+				}
+				numbers.push(n);
+			}
+			let (start, end, step) = match numbers[..] {
+				[end] => (0.0, end, 1.0),
+				[start, end] => (start, end, 1.0),
+				[start, end, step] => (start, end, step),
+				_ => unreachable!(),
+			};
+			if step == 0.0 {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					"range can't count in steps of 0".to_string(),
+				));
+			}
+			let count = ((end - start) / step).ceil().max(0.0);
+			let count = Self::array_length(interpreter, "range", &LiteralType::Number(count))?;
+			let mut values = Vec::new();
+			values.try_reserve_exact(count).map_err(|_| {
+				Error::RuntimeError(
+					Span::default(),
+					format!(
+						"range can't make an array of {} elements, it needs too much memory",
+						count
+					),
+				)
+			})?;
+			// Multiplying rather than adding up the steps keeps fractional steps from drifting
+			values.extend((0..count).map(|i| LiteralType::Number(start + i as f64 * step)));
+			Ok(LiteralType::array(values))
+		});
+	}
+
+	/// read_file, write_file, append_file and file_exists work with files, relative paths are
+	/// relative to where jasn was started rather than the script. Sandbox mode disables all of
+	/// them, see SANDBOXED_NATIVES
+	fn define_files(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"read_file",
+			1,
+			NativeDocs::new("io", "read_file(path)", "The contents of a file as a string"),
+			|interpreter, args| {
+				let path = Self::expect_string("read_file", "path", &args[0])?;
+				let contents = fs::read_to_string(path).map_err(|e| {
+					Error::RuntimeError(
+						Span::default(),
+						format!("read_file couldn't read '{}': {}", path, e),
+					)
+				})?;
+				interpreter.check_string_length(0, contents.len())?;
+				Ok(LiteralType::String(contents))
+			},
+		);
+
+		let natives: [(&str, &str, &str, NativeFn); 2] = [
+			(
+				"write_file",
+				"write_file(path, contents)",
+				"Replace the contents of a file, creating it if it doesn't exist",
+				|_env, args| Self::write_file("write_file", &args, false),
+			),
+			(
+				"append_file",
+				"append_file(path, contents)",
+				"Add to the end of a file, creating it if it doesn't exist",
+				|_env, args| Self::write_file("append_file", &args, true),
+			),
+		];
+		for (name, signature, description, function) in natives {
+			interpreter.define_native(
+				name,
+				2,
+				NativeDocs::new("io", signature, description),
+				function,
+			);
+		}
+
+		interpreter.define_native(
+			"file_exists",
+			1,
+			NativeDocs::new(
+				"io",
+				"file_exists(path)",
+				"Whether there is a file or directory at path",
+			),
+			|_env, args| {
+				let path = Self::expect_string("file_exists", "path", &args[0])?;
+				Ok(LiteralType::Bool(Path::new(path).exists()))
+			},
+		);
 	}
 
-	fn define_random(environment: EnvRef) {
-		let random = LiteralType::Callable(Callable::NativeFunction(NativeFunction {
-			arity: 0,
-			function: |_env, _args| Ok(LiteralType::Number(rand::random::<f64>())),
-		}));
-		environment.borrow_mut().define("random".to_string(), random);
+	/// Write a string to the file at a path, replacing what was there or adding to the end.
+	/// Gives yeah, or an error with the reason it couldn't
+	fn write_file(name: &str, args: &[LiteralType], append: bool) -> Result<LiteralType, Error> {
+		let path = Self::expect_string(name, "path", &args[0])?;
+		let contents = Self::expect_string(name, "contents", &args[1])?;
+		fs::OpenOptions::new()
+			.create(true)
+			.write(true)
+			.append(append)
+			.truncate(!append)
+			.open(path)
+			.and_then(|mut file| file.write_all(contents.as_bytes()))
+			.map_err(|e| {
+				Error::RuntimeError(
+					Span::default(),
+					format!("{} couldn't write to '{}': {}", name, path, e),
+				)
+			})?;
+		Ok(LiteralType::Bool(true))
 	}
+
+	/// args() gives the arguments the script was run with, get_env(name) reads an environment
+	/// variable and exit(code) stops the script. Sandbox mode disables all of them
+	fn define_process(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"args",
+			0,
+			NativeDocs::new("io", "args()", "The arguments given after the script's path"),
+			|interpreter, _args| {
+				let args = interpreter.args.iter().cloned().map(LiteralType::String).collect();
+				Ok(LiteralType::array(args))
+			},
+		);
+
+		interpreter.define_native(
+			"get_env",
+			1,
+			NativeDocs::new(
+				"io",
+				"get_env(name)",
+				"The value of an environment variable, or null when it isn't set",
+			),
+			|_env, args| {
+				let name = Self::expect_string("get_env", "name", &args[0])?;
+				Ok(env::var(name).map_or(LiteralType::Null, LiteralType::String))
+			},
+		);
+
+		let docs = NativeDocs::new(
+			"io",
+			"exit(code?)",
+			"Stop the script, jasn exits with code (default 0) once it has cleaned up",
+		);
+		interpreter.define_native_range("exit", 0..=1, docs, |interpreter, args| {
+			let code = match args.first() {
+				Some(code) => Self::expect_whole_number(interpreter, "exit", "exit code", code)?,
+				None => 0.0,
+			};
+			if !(0.0..=255.0).contains(&code) {
+				return Err(Error::RuntimeError(
+					Span::default(),
+					format!("exit expects an exit code from 0 to 255 but found {}", code),
+				));
+			}
+			// Unwinding rather than exiting here lets the state file be saved on the way out
+			Err(Error::Exit(code as i32))
+		});
+	}
+
+	/// json_get(value, path) follows a path like "user.addresses[0].city" through maps, arrays
+	/// and instances. The path can also be an array of segments like ["user", "addresses", 0],
+	/// which skips reading the string. Anything missing on the way gives null, a path that
+	/// can't be read is an error
+	fn define_json_get(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"json_get",
+			2,
+			NativeDocs::new(
+				"values",
+				"json_get(value, path)",
+				"Follow a path like \"a.b[0]\" or [\"a\", \"b\", 0] into value, null if it isn't there",
+			),
+			|interpreter, args| {
+				let segments = match &args[1] {
+					LiteralType::String(path) => Self::parse_path(path)?,
+					LiteralType::Array(segments) => segments
+						.borrow()
+						.iter()
+						.map(|segment| match segment {
+							LiteralType::String(key) => Ok(PathSegment::Key(key.clone())),
+							LiteralType::Number(n) if n.fract() == 0.0 =>
+								Ok(PathSegment::Index(*n)),
+							segment => Err(Error::RuntimeError(
+								Span::default(),
+								format!(
+									"json_get expects path segments to be strings or whole numbers but found {}",
+									segment.render(&interpreter.render_limits)
+								),
+							)),
+						})
+						.collect::<Result<_, _>>()?,
+					path => Err(Error::RuntimeError(
+						Span::default(),
+						format!(
+							"json_get expects a string or array as the path but found {}",
+							path.type_name()
+						),
+					))?,
+				};
+				let mut value = args[0].clone();
+				for segment in segments {
+					value = match (&value, segment) {
+						(LiteralType::Map(map), PathSegment::Key(key)) =>
+							map.borrow().get(&key).cloned(),
+						(LiteralType::Array(array), PathSegment::Index(index)) => {
+							let array = array.borrow();
+							// Negative indices count back from the end like indexing
+							let index = if index < 0.0 { index + array.len() as f64 } else { index };
+							(index >= 0.0).then(|| array.get(index as usize).cloned()).flatten()
+						},
+						(LiteralType::Callable(Callable::Instance(instance)), PathSegment::Key(key)) =>
+							JasnInstance::lookup(instance, &key),
+						_ => None,
+					}
+					.unwrap_or(LiteralType::Null);
+				}
+				Ok(value)
+			},
+		);
+	}
+
+	/// Split a path like "user.addresses[0].city" into its keys and indices
+	fn parse_path(path: &str) -> Result<Vec<PathSegment>, Error> {
+		let malformed = |segment: &str, reason: &str| {
+			Error::RuntimeError(
+				Span::default(),
+				format!("json_get can't read '{}' in the path '{}', {}", segment, path, reason),
+			)
+		};
+		if path.is_empty() {
+			return Err(Error::RuntimeError(
+				Span::default(),
+				"json_get can't follow an empty path".to_string(),
+			));
+		}
+		let mut segments = Vec::new();
+		let mut rest = path;
+		// Whether a key has to come next, at the start and after a dot
+		let mut needs_key = true;
+		while !rest.is_empty() || needs_key {
+			if let Some(after) = rest.strip_prefix('[') {
+				let Some(end) = after.find(']') else {
+					return Err(malformed(rest, "the [ is never closed"));
+				};
+				let index = &after[..end];
+				match index.parse::<f64>() {
+					Ok(n) if n.fract() == 0.0 && !index.contains(['.', 'e', 'E', '+']) =>
+						segments.push(PathSegment::Index(n)),
+					_ =>
+						return Err(malformed(&rest[..end + 2], "indices have to be whole numbers")),
+				}
+				rest = &after[end + 1..];
+				needs_key = false;
+			} else if let Some(after) = rest.strip_prefix('.') {
+				if needs_key {
+					return Err(malformed(rest, "there is no key before the dot"));
+				}
+				rest = after;
+				needs_key = true;
+			} else if needs_key {
+				let end = rest.find(['.', '[', ']']).unwrap_or(rest.len());
+				if end == 0 {
+					let segment = if rest.is_empty() { path } else { &rest[..1] };
+					return Err(malformed(segment, "a key is missing"));
+				}
+				segments.push(PathSegment::Key(rest[..end].to_string()));
+				rest = &rest[end..];
+				needs_key = false;
+			} else {
+				return Err(malformed(rest, "expected a dot or [ after a key"));
+			}
+		}
+		Ok(segments)
+	}
+
+	/// assert(condition, message?) is an error when condition is falsey and does nothing
+	/// otherwise. The error is on the line of the call like any other native's, and the ones
+	/// that pass are counted in the stats so `--check-only` can report them
+	fn define_assert(interpreter: &mut Interpreter) {
+		let docs = NativeDocs::new(
+			"debugging",
+			"assert(condition, message?)",
+			"An error saying message when condition is falsey, nothing otherwise",
+		);
+		interpreter.define_native_range("assert", 1..=2, docs, |interpreter, args| {
+			if args[0].is_truthy() {
+				interpreter.record_assertion();
+				return Ok(LiteralType::Null);
+			}
+			let message = match args.get(1) {
+				Some(LiteralType::String(message)) => message.clone(),
+				Some(message) => message.render(&interpreter.render_limits),
+				None => format!("{} is falsey", args[0].render(&interpreter.render_limits)),
+			};
+			Err(Error::RuntimeError(Span::default(), format!("Assertion failed: {}", message)))
+		});
+	}
+
+	/// rng_new(seed) makes a random number stream of its own. rng_next, rng_int and rng_shuffle
+	/// take one and only move that stream on, so a seeded stream gives the same numbers however
+	/// many other streams, or calls to random, are mixed in with it
+	fn define_rng(interpreter: &mut Interpreter) {
+		interpreter.define_native(
+			"rng_new",
+			1,
+			NativeDocs::new(
+				"random",
+				"rng_new(seed)",
+				"A random number stream of its own that starts from a whole number seed",
+			),
+			|interpreter, args| {
+				let seed = Self::expect_whole_number(interpreter, "rng_new", "seed", &args[0])?;
+				Ok(Self::stream_value(Xoshiro128::seed(seed as i64 as u64)))
+			},
+		);
+		interpreter.define_native(
+			"rng_next",
+			1,
+			NativeDocs::new(
+				"random",
+				"rng_next(stream)",
+				"The stream's next number from 0 up to but not including 1",
+			),
+			|interpreter, args| {
+				Self::with_stream(interpreter, "rng_next", &args[0], |rng| {
+					Ok(LiteralType::Number(rng.next_f64()))
+				})
+			},
+		);
+		interpreter.define_native(
+			"rng_int",
+			3,
+			NativeDocs::new(
+				"random",
+				"rng_int(stream, min, max)",
+				"The stream's next whole number from min to max, both included",
+			),
+			|interpreter, args| {
+				let (min, max) = Self::random_int_bounds(interpreter, "rng_int", &args[1..])?;
+				Self::with_stream(interpreter, "rng_int", &args[0], |rng| {
+					Ok(LiteralType::Number(rng.int_between(min, max) as f64))
+				})
+			},
+		);
+		interpreter.define_native(
+			"rng_shuffle",
+			2,
+			NativeDocs::new(
+				"random",
+				"rng_shuffle(stream, array)",
+				"Put the elements in an order from the stream in place and return the array",
+			),
+			|interpreter, args| {
+				let array = Self::expect_array_ref(interpreter, "rng_shuffle", &args[1])?;
+				Self::with_stream(interpreter, "rng_shuffle", &args[0], |rng| {
+					rng.shuffle(&mut array.borrow_mut());
+					Ok(args[1].clone())
+				})
+			},
+		);
+	}
+
+	// A stream is a map holding the generator's state as whole numbers, so it prints, copies
+	// and saves like any other value
+	fn stream_value(rng: Xoshiro128) -> LiteralType {
+		LiteralType::map(BTreeMap::from([
+			("rng".to_string(), LiteralType::String(rng::ALGORITHM.to_string())),
+			("state".to_string(), Self::state_value(rng)),
+		]))
+	}
+
+	fn state_value(rng: Xoshiro128) -> LiteralType {
+		LiteralType::array(
+			rng.state().iter().map(|&word| LiteralType::Number(word as f64)).collect(),
+		)
+	}
+
+	// Run f on the generator a stream holds and store where it got to back in the stream
+	fn with_stream(
+		interpreter: &Interpreter,
+		name: &str,
+		stream: &LiteralType,
+		f: impl FnOnce(&mut Xoshiro128) -> Result<LiteralType, Error>,
+	) -> Result<LiteralType, Error> {
+		let not_a_stream = || {
+			Error::RuntimeError(
+				Span::default(),
+				format!(
+					"{} expects a stream made by rng_new but found {}",
+					name,
+					stream.render(&interpreter.render_limits)
+				),
+			)
+		};
+		let LiteralType::Map(map) = stream else {
+			return Err(not_a_stream());
+		};
+		let state = match map.borrow().get("state") {
+			Some(LiteralType::Array(words)) => {
+				let words: Vec<u32> = words
+					.borrow()
+					.iter()
+					.map_while(|word| match word {
+						LiteralType::Number(n)
+							if n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(n) =>
+							Some(*n as u32),
+						_ => None,
+					})
+					.collect();
+				<[u32; 4]>::try_from(words).ok()
+			},
+			_ => None,
+		};
+		let mut rng = state.and_then(Xoshiro128::from_state).ok_or_else(not_a_stream)?;
+		let result = f(&mut rng)?;
+		map.borrow_mut().insert("state".to_string(), Self::state_value(rng));
+		Ok(result)
+	}
+
+	/// format(template, values...) fills each `{}` in template with the next value, or `{n}`
+	/// with value n. After a colon a placeholder can ask for
+	/// `[[fill]align][+][0][width][.precision]` like `{:>8}` or `{:.2}`, and `{{` and `}}` are
+	/// written as braces. print_f(template, values...) writes the same string on its own line
+	fn define_format(interpreter: &mut Interpreter) {
+		interpreter.define_native_variadic(
+			"format",
+			1,
+			NativeDocs::new(
+				"strings",
+				"format(template, values...)",
+				"Fill the {} placeholders in template with values, {:.2} fixes the decimals and {:>8} pads",
+			),
+			|interpreter, args| Ok(LiteralType::String(Self::format("format", interpreter, &args)?)),
+		);
+		interpreter.define_native_variadic(
+			"print_f",
+			1,
+			NativeDocs::new(
+				"io",
+				"print_f(template, values...)",
+				"Write template with its placeholders filled like format does, on its own line",
+			),
+			|interpreter, args| {
+				let formatted = Self::format("print_f", interpreter, &args)?;
+				writeln!(interpreter.output.borrow_mut(), "{}", formatted).map_err(|e| {
+					Error::RuntimeError(Span::default(), format!("print_f couldn't write: {}", e))
+				})?;
+				Ok(LiteralType::Null)
+			},
+		);
+	}
+
+	// Fill the placeholders in the template args starts with from the values after it. Every
+	// value has to be used and every placeholder needs a value
+	fn format(
+		name: &str,
+		interpreter: &Interpreter,
+		args: &[LiteralType],
+	) -> Result<String, Error> {
+		let template = Self::expect_string(name, "template", &args[0])?;
+		let values = &args[1..];
+		let error =
+			|message: String| Error::RuntimeError(Span::default(), format!("{} {}", name, message));
+		let mut formatted = String::new();
+		let mut used = vec![false; values.len()];
+		let mut next = 0;
+		let mut rest = template;
+		while let Some(brace) = rest.find(['{', '}']) {
+			formatted.push_str(&rest[..brace]);
+			let after = &rest[brace + 1..];
+			if rest[brace..].starts_with("{{") || rest[brace..].starts_with("}}") {
+				formatted.push_str(&rest[brace..brace + 1]);
+				rest = &after[1..];
+				continue;
+			}
+			if rest[brace..].starts_with('}') {
+				return Err(error(
+					"has a '}' with no '{' before it, write '}}' for a brace".to_string(),
+				));
+			}
+			let Some(end) = after.find('}') else {
+				return Err(error(
+					"has a '{' that is never closed, write '{{' for a brace".to_string(),
+				));
+			};
+			let placeholder = &after[..end];
+			let (index, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+			let bad_placeholder =
+				|| error(format!("can't understand the placeholder '{{{}}}'", placeholder));
+			let index = if index.is_empty() {
+				next += 1;
+				next - 1
+			} else {
+				index.parse::<usize>().map_err(|_| bad_placeholder())?
+			};
+			let spec = FormatSpec::parse(spec).ok_or_else(bad_placeholder)?;
+			let Some(value) = values.get(index) else {
+				return Err(error(format!(
+					"has placeholders for {} values but was only given {}",
+					index + 1,
+					values.len()
+				)));
+			};
+			used[index] = true;
+			// Padding and decimals are written all at once, so check they fit before asking
+			interpreter.check_string_length(
+				0,
+				formatted.len() + spec.width.max(spec.precision.unwrap_or(0)),
+			)?;
+			spec.write(&mut formatted, value).map_err(|()| {
+				error(format!(
+					"can only give numbers a sign, zero padding or decimals, not {} in '{{{}}}'",
+					value.type_name(),
+					placeholder
+				))
+			})?;
+			interpreter.check_string_length(0, formatted.len())?;
+			rest = &after[end + 1..];
+		}
+		formatted.push_str(rest);
+		interpreter.check_string_length(0, formatted.len())?;
+		let used = used.iter().filter(|&&used| used).count();
+		if used < values.len() {
+			return Err(error(format!(
+				"was given {} values but its placeholders only use {}",
+				values.len(),
+				used
+			)));
+		}
+		Ok(formatted)
+	}
+}
+
+/// How a format placeholder lays out its value, from the part after the colon in
+/// `{:*^+08.2}`
+#[derive(Debug, Default)]
+struct FormatSpec {
+	fill: Option<char>,
+	align: Option<Align>,
+	sign: bool,
+	zero: bool,
+	width: usize,
+	precision: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Align {
+	Left,
+	Right,
+	Center,
+}
+
+impl FormatSpec {
+	/// None if spec isn't `[[fill]align][+][0][width][.precision]`
+	fn parse(spec: &str) -> Option<Self> {
+		let align = |c| match c {
+			'<' => Some(Align::Left),
+			'>' => Some(Align::Right),
+			'^' => Some(Align::Center),
+			_ => None,
+		};
+		let mut format_spec = Self::default();
+		let mut chars = spec.chars();
+		let mut rest = spec;
+		match (chars.next(), chars.next().and_then(align)) {
+			(Some(fill), Some(second)) => {
+				format_spec.fill = Some(fill);
+				format_spec.align = Some(second);
+				rest = chars.as_str();
+			},
+			(Some(first), _) if align(first).is_some() => {
+				format_spec.align = align(first);
+				rest = &spec[1..];
+			},
+			_ => {},
+		}
+		if let Some(after) = rest.strip_prefix('+') {
+			format_spec.sign = true;
+			rest = after;
+		}
+		if let Some(after) = rest.strip_prefix('0') {
+			format_spec.zero = true;
+			rest = after;
+		}
+		let (width, precision) = match rest.split_once('.') {
+			Some((width, precision)) => (width, Some(precision)),
+			None => (rest, None),
+		};
+		let number = |digits: &str| {
+			if digits.bytes().all(|b| b.is_ascii_digit()) {
+				digits.parse::<usize>().ok()
+			} else {
+				None
+			}
+		};
+		if !width.is_empty() {
+			format_spec.width = number(width)?;
+		}
+		if let Some(precision) = precision {
+			format_spec.precision = Some(number(precision)?);
+		}
+		Some(format_spec)
+	}
+
+	/// Write value laid out by the spec. Err if the spec asks a value that isn't a number for
+	/// something only numbers have
+	fn write(&self, formatted: &mut String, value: &LiteralType) -> Result<(), ()> {
+		let text = match value {
+			LiteralType::Number(n) => {
+				let mut text = match self.precision {
+					Some(precision) if n.is_finite() => format!("{:.*}", precision, n),
+					_ => value.to_string(),
+				};
+				if self.sign && !n.is_sign_negative() && !n.is_nan() {
+					text.insert(0, '+');
+				}
+				if self.zero && n.is_finite() {
+					// Zeros go between the sign and the digits, so fill and align don't apply
+					let digits = text.find(|c: char| c.is_ascii_digit()).unwrap_or(0);
+					let padding = self.width.saturating_sub(text.chars().count());
+					text.insert_str(digits, &"0".repeat(padding));
+				}
+				text
+			},
+			_ if self.sign || self.zero || self.precision.is_some() => return Err(()),
+			LiteralType::String(s) => s.clone(),
+			value => value.to_string(),
+		};
+		let padding = self.width.saturating_sub(text.chars().count());
+		let fill = self.fill.unwrap_or(' ');
+		let align = match value {
+			LiteralType::Number(_) => self.align.unwrap_or(Align::Right),
+			_ => self.align.unwrap_or(Align::Left),
+		};
+		let (before, after) = match align {
+			Align::Left => (0, padding),
+			Align::Right => (padding, 0),
+			Align::Center => (padding / 2, padding - padding / 2),
+		};
+		formatted.extend(std::iter::repeat(fill).take(before));
+		formatted.push_str(&text);
+		formatted.extend(std::iter::repeat(fill).take(after));
+		Ok(())
+	}
+}
+
+/// One step of a json_get path
+enum PathSegment {
+	Key(String),
+	Index(f64),
 }
@@ -9,17 +9,33 @@ use crate::{
 };
 use std::{cell::RefCell, rc::Rc};
 
+/// How deeply expressions can nest before they are rejected. Every level of parentheses, unary
+/// operator, binary operator chain and call chain counts. Each level recurses through the parser,
+/// resolver and interpreter, so without a limit a hostile input overflows the stack
+pub const DEFAULT_MAX_NESTING: usize = 256;
+
 /// The parser struct handles incoming token streams and converts them into statements and
 /// expressions
 pub struct Parser {
 	tokens: Vec<Token>,
 	current: usize,
 	error_handler: Rc<RefCell<ErrorHandler>>,
+	// How deeply nested the expression being parsed is
+	depth: usize,
+	max_nesting: usize,
 }
 
 impl Parser {
-	pub fn new(tokens: Vec<Token>, error_handler: Rc<RefCell<ErrorHandler>>) -> Self {
-		Self { tokens, current: 0, error_handler }
+	pub fn new(mut tokens: Vec<Token>, error_handler: Rc<RefCell<ErrorHandler>>) -> Self {
+		// Comments and whitespace don't mean anything to the parser
+		tokens.retain(|token| !token.token_type.is_trivia());
+		Self { tokens, current: 0, error_handler, depth: 0, max_nesting: DEFAULT_MAX_NESTING }
+	}
+
+	/// Reject expressions nested more deeply than max_nesting
+	pub fn max_nesting(mut self, max_nesting: usize) -> Self {
+		self.max_nesting = max_nesting;
+		self
 	}
 
 	/// Method called to parse the tokens
@@ -30,6 +46,8 @@ impl Parser {
 				Ok(stmt) => statements.push(stmt),
 				Err(e) => {
 					error!(self, e);
+					// The error could have come from any depth
+					self.depth = 0;
 					self.synchronize();
 				},
 			}
@@ -48,17 +66,41 @@ impl Parser {
 		if self.match_token(&[TokenType::Funk]) {
 			return Ok(Stmt::Function(self.function("function")?));
 		}
+		if self.match_token(&[TokenType::Pure]) {
+			self.consume(TokenType::Funk, "Expected 'funk' after 'pure'.")?;
+			let function = self.function("function")?;
+			return Ok(Stmt::Function(Function { pure: true, ..function }));
+		}
 		if self.match_token(&[TokenType::Class]) {
 			return self.class_declaration();
 		}
+		if self.match_token(&[TokenType::Import]) {
+			let keyword = self.previous();
+			let path = self.consume(TokenType::String, "Expected a file path after 'import'.")?;
+			self.check_statement_end()?;
+			return Ok(Stmt::Import(Import { keyword, path }));
+		}
 		self.statement()
 	}
 
 	/// Parse a statement
 	fn statement(&mut self) -> Result<Stmt, Error> {
+		if self.match_token(&[TokenType::Break]) {
+			let keyword = self.previous();
+			self.check_statement_end()?;
+			return Ok(Stmt::Break(Break { keyword }));
+		}
+		if self.match_token(&[TokenType::Continue]) {
+			let keyword = self.previous();
+			self.check_statement_end()?;
+			return Ok(Stmt::Continue(Continue { keyword }));
+		}
 		if self.match_token(&[TokenType::For]) {
 			return self.for_statement();
 		}
+		if self.match_token(&[TokenType::Global]) {
+			return self.global_statement();
+		}
 		if self.match_token(&[TokenType::If]) {
 			return self.if_statement();
 		}
@@ -81,7 +123,11 @@ impl Parser {
 
 	/// Parse a variable declaration
 	fn variable_declaration(&mut self) -> Result<Stmt, Error> {
-		let name = self.consume(TokenType::Identifier, "Expected variable name.")?;
+		let name = self.consume_name(
+			"variable",
+			"Expected variable name.",
+			&[TokenType::Equal, TokenType::Semicolon],
+		)?;
 		let initializer =
 			if self.match_token(&[TokenType::Equal]) { Some(self.expression()?) } else { None };
 		self.check_statement_end()?;
@@ -90,18 +136,27 @@ impl Parser {
 
 	/// Parse a function declaration
 	fn function(&mut self, kind: &str) -> Result<Function, Error> {
-		let name = self.consume(TokenType::Identifier, &format!("Expected {} name.", kind))?;
+		let name =
+			self.consume_name(kind, &format!("Expected {} name.", kind), &[TokenType::LeftParen])?;
 		self.consume(TokenType::LeftParen, &format!("Expected '(' after {} name.", kind))?;
 		let mut parameters = Vec::new();
 		if !self.check(&TokenType::RightParen) {
 			loop {
-				if parameters.len() > 255 {
-					return Err(Error::ParseError(
-						self.peek(),
-						"Cannot have more than 255 parameters.".to_string(),
-					));
+				// Report this without stopping, the rest of the declaration still parses fine
+				if parameters.len() == 255 {
+					error!(
+						self,
+						Error::ParseError(
+							self.peek(),
+							"Cannot have more than 255 parameters.".to_string(),
+						)
+					);
 				}
-				parameters.push(self.consume(TokenType::Identifier, "Expected parameter name.")?);
+				parameters.push(self.consume_name(
+					"parameter",
+					"Expected parameter name.",
+					&[TokenType::Comma, TokenType::RightParen],
+				)?);
 				if !self.match_token(&[TokenType::Comma]) {
 					break;
 				}
@@ -111,25 +166,71 @@ impl Parser {
 		self.consume(TokenType::LeftBrace, &format!("Expected '{{' before {} body.", kind))?;
 		let body = self.block()?;
 
-		Ok(statements::Function { name, params: parameters, body })
+		Ok(statements::Function { name, params: parameters, body, pure: false })
 	}
 
 	fn class_declaration(&mut self) -> Result<Stmt, Error> {
-		let name = self.consume(TokenType::Identifier, "Expected class name.")?;
+		let name = self.consume_name(
+			"class",
+			"Expected class name.",
+			&[TokenType::LeftBrace, TokenType::Less],
+		)?;
+		let superclass = if self.match_token(&[TokenType::Less]) {
+			let name = self.consume(TokenType::Identifier, "Expected superclass name.")?;
+			Some(expressions::Variable { name })
+		} else {
+			None
+		};
 		self.consume(TokenType::LeftBrace, "Expected '{' before class body.")?;
 		let mut methods = Vec::new();
 		while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
 			methods.push(self.function("method")?);
 		}
 		self.consume(TokenType::RightBrace, "Expected '}' after class body.")?;
-		Ok(Stmt::Class(Class { name, methods }))
+		Ok(Stmt::Class(Class { name, superclass, methods }))
+	}
+
+	/// Parse an if used as a value, `if (x) { a } else { b }`. Statements starting with `if` are
+	/// still parsed as if statements, this is only reached where an expression is expected
+	fn if_expression(&mut self) -> Result<Expr, Error> {
+		let keyword = self.previous();
+		let condition = self.condition("if")?;
+		let then_branch = self.branch()?;
+		let else_branch = if !self.match_token(&[TokenType::Else]) {
+			None
+		} else if self.match_token(&[TokenType::If]) {
+			Some(Branch { statements: Vec::new(), value: Some(self.if_expression()?) })
+		} else {
+			Some(self.branch()?)
+		};
+		Ok(Expr::IfExpr(Box::new(IfExpr { keyword, condition, then_branch, else_branch })))
+	}
+
+	/// Parse the block of an if expression branch. The last expression in it, written without a
+	/// semicolon right before the '}', is the value of the branch
+	fn branch(&mut self) -> Result<Branch, Error> {
+		self.consume(TokenType::LeftBrace, "Expected '{' before if branch.")?;
+		let mut statements = Vec::new();
+		let mut value = None;
+		while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+			let (start, depth) = (self.current, self.depth);
+			if let Ok(expr) = self.expression() {
+				if self.check(&TokenType::RightBrace) {
+					value = Some(expr);
+					break;
+				}
+			}
+			self.current = start;
+			self.depth = depth;
+			statements.push(self.declaration()?);
+		}
+		self.consume(TokenType::RightBrace, "Expected '}' after if branch.")?;
+		Ok(Branch { statements, value })
 	}
 
 	/// Parse an if statement
 	fn if_statement(&mut self) -> Result<Stmt, Error> {
-		self.consume(TokenType::LeftParen, "Expected '(' after 'if'.")?;
-		let condition = self.expression()?;
-		self.consume(TokenType::RightParen, "Expected ')' after if condition.")?;
+		let condition = self.condition("if")?;
 		let then_branch = self.statement()?;
 		let else_branch =
 			if self.match_token(&[TokenType::Else]) { Some(self.statement()?) } else { None };
@@ -138,8 +239,12 @@ impl Parser {
 
 	/// Parse a for statement. We essentially craft a while loop with a declaration and an iterator
 	/// This is called "desugaring"
+	/// Like if and while the parentheses are optional, `for var i = 0; i < 3; i++ { ... }`, in
+	/// which case the body has to be a block. A clause list starting with '(' is always taken to
+	/// be the parenthesised form
 	fn for_statement(&mut self) -> Result<Stmt, Error> {
-		self.consume(TokenType::LeftParen, "Expected '(' after 'for'.")?;
+		let keyword = self.previous();
+		let parenthesised = self.match_token(&[TokenType::LeftParen]);
 
 		// Check for initializer, if it has been omitted, we will set it to None
 		let initializer: Option<Stmt> = if self.match_token(&[TokenType::Semicolon]) {
@@ -159,22 +264,23 @@ impl Parser {
 		self.consume(TokenType::Semicolon, "Expected ';' after loop condition.")?;
 
 		// Check for increment, if it has been omitted, we will set it to None
-		let increment =
-			if !self.check(&TokenType::RightParen) { Some(self.expression()?) } else { None };
-		self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
+		let clauses_end = if parenthesised { TokenType::RightParen } else { TokenType::LeftBrace };
+		let increment = if !self.check(&clauses_end) { Some(self.expression()?) } else { None };
+		if parenthesised {
+			self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
+		} else if !self.check(&TokenType::LeftBrace) {
+			return Err(Error::ParseError(
+				self.peek(),
+				"Expected '{' after for clauses without parentheses.".to_string(),
+			));
+		}
 
 		// Parse the body of the for loop
-		let mut body = self.statement()?;
-
-		// If there is an increment, we will add it to the end of the body
-		if let Some(increment) = increment {
-			body = Stmt::Block(Box::new(Block {
-				statements: vec![body, Stmt::Expression(Expression { expression: increment })],
-			}));
-		}
+		let body = self.statement()?;
 
-		// Add in the while condition
-		body = Stmt::While(Box::new(While { condition, body }));
+		// Add in the while condition. The increment stays separate from the body so that
+		// continue doesn't skip it
+		let mut body = Stmt::While(Box::new(While { keyword, condition, body, increment }));
 
 		// If there is an initializer, we will add it to the beginning of the body
 		if let Some(initializer) = initializer {
@@ -184,11 +290,22 @@ impl Parser {
 		Ok(body)
 	}
 
+	/// Parse `global name = value`, which assigns a global from anywhere
+	fn global_statement(&mut self) -> Result<Stmt, Error> {
+		let keyword = self.previous();
+		let name = self.consume(TokenType::Identifier, "Expected variable name after 'global'.")?;
+		self.consume(TokenType::Equal, "Expected '=' after the global's name.")?;
+		let value = self.expression()?;
+		self.check_statement_end()?;
+		Ok(Stmt::Global(Global { keyword, name, value }))
+	}
+
 	/// Parse a print statement
 	fn print_statement(&mut self) -> Result<Stmt, Error> {
+		let keyword = self.previous();
 		let expression = self.expression()?;
 		self.check_statement_end()?;
-		Ok(Stmt::Print(Print { expression }))
+		Ok(Stmt::Print(Print { keyword, expression }))
 	}
 
 	fn return_statement(&mut self) -> Result<Stmt, Error> {
@@ -203,11 +320,83 @@ impl Parser {
 
 	/// Parse a while statement
 	fn while_statement(&mut self) -> Result<Stmt, Error> {
-		self.consume(TokenType::LeftParen, "Expected '(' after 'while'.")?;
-		let condition = self.expression()?;
-		self.consume(TokenType::RightParen, "Expected ')' after while condition.")?;
+		let keyword = self.previous();
+		let condition = self.condition("while")?;
 		let body = self.statement()?;
-		Ok(Stmt::While(Box::new(While { condition, body })))
+		Ok(Stmt::While(Box::new(While { keyword, condition, body, increment: None })))
+	}
+
+	/// Parse the condition of an if or while. The parentheses are optional, but without them the
+	/// body has to be a block so it's clear where the condition stops: `if x > 1 { ... }`.
+	/// A leading '(' doesn't always wrap the whole condition, eg `if (a + b) * c { ... }`, so the
+	/// condition is read as one expression from the '(' either way. If that ends at a '{' it is
+	/// the whole condition. Otherwise the parentheses alone are the condition, which allows a
+	/// body without braces like `if (x) print x;` or `if (x) -y;`. Nothing is parsed twice, an if
+	/// expression nested in the condition would otherwise be read twice at every level
+	fn condition(&mut self, keyword: &str) -> Result<Expr, Error> {
+		let start = self.current;
+		let parenthesised = self.check(&TokenType::LeftParen);
+		let depth = self.depth;
+		let condition = self.expression();
+		self.depth = depth;
+		let condition = condition?;
+		if self.check(&TokenType::LeftBrace) {
+			return Ok(match condition {
+				Expr::Grouping(grouping) => grouping.expression,
+				condition => condition,
+			});
+		}
+		if !parenthesised {
+			return Err(Error::ParseError(
+				self.peek(),
+				format!("Expected '{{' after {} condition without parentheses.", keyword),
+			));
+		}
+		if let Expr::Grouping(grouping) = condition {
+			return Ok(grouping.expression);
+		}
+		// The expression carried on past the ')' into the body, its leftmost operand is the
+		// parenthesised condition and the body starts again after the ')'
+		match Self::leftmost_grouping(condition) {
+			Some(condition) => {
+				self.current = self.after_closing_paren(start);
+				Ok(condition)
+			},
+			None => Err(Error::ParseError(
+				self.tokens[start].clone(),
+				format!("Expected ')' after {} condition.", keyword),
+			)),
+		}
+	}
+
+	// The expression inside the parentheses an expression starts with, if it does
+	fn leftmost_grouping(expr: Expr) -> Option<Expr> {
+		match expr {
+			Expr::Grouping(grouping) => Some(grouping.expression),
+			Expr::Binary(binary) => Self::leftmost_grouping(binary.left),
+			Expr::Logical(logical) => Self::leftmost_grouping(logical.left),
+			Expr::Call(call) => Self::leftmost_grouping(call.callee),
+			Expr::Get(get) => Self::leftmost_grouping(get.object),
+			Expr::Set(set) => Self::leftmost_grouping(set.object),
+			Expr::Index(index) => Self::leftmost_grouping(index.object),
+			Expr::Slice(slice) => Self::leftmost_grouping(slice.object),
+			Expr::AssignIndex(assign) => Self::leftmost_grouping(assign.object),
+			_ => None,
+		}
+	}
+
+	// The position just past the ')' matching the '(' at open
+	fn after_closing_paren(&self, open: usize) -> usize {
+		let mut depth = 0;
+		for (i, token) in self.tokens.iter().enumerate().skip(open) {
+			match token.token_type {
+				TokenType::LeftParen => depth += 1,
+				TokenType::RightParen if depth == 1 => return i + 1,
+				TokenType::RightParen => depth -= 1,
+				_ => {},
+			}
+		}
+		self.tokens.len() - 1
 	}
 
 	/// Return a list of statements between curly braces.
@@ -225,10 +414,34 @@ impl Parser {
 	/// Parse an expression statement
 	fn expression_statement(&mut self) -> Result<Stmt, Error> {
 		let expression = self.expression()?;
+		if self.check(&TokenType::Comma) {
+			return self.multi_assignment(expression);
+		}
 		self.check_statement_end()?;
 		Ok(Stmt::Expression(Expression { expression }))
 	}
 
+	/// Parse the rest of `a, b = b, a` once the first target has been read
+	fn multi_assignment(&mut self, first: Expr) -> Result<Stmt, Error> {
+		let mut targets = vec![first];
+		while self.match_token(&[TokenType::Comma]) {
+			targets.push(self.or()?);
+		}
+		let equals = self.consume(TokenType::Equal, "Expected '=' after assignment targets.")?;
+		for target in &targets {
+			if !matches!(target, Expr::Variable(_) | Expr::Get(_) | Expr::Index(_)) {
+				return Err(Error::ParseError(equals, "Invalid assignment target.".to_string()));
+			}
+		}
+
+		let mut values = vec![self.or()?];
+		while self.match_token(&[TokenType::Comma]) {
+			values.push(self.or()?);
+		}
+		self.check_statement_end()?;
+		Ok(Stmt::MultiAssign(MultiAssign { targets, equals, values }))
+	}
+
 	/// Check whether we are at the end of a statement. We accept both semi-colons and new lines
 	fn check_statement_end(&mut self) -> Result<(), Error> {
 		// Semi colon always ends a statement
@@ -236,19 +449,49 @@ impl Parser {
 			self.advance();
 			return Ok(());
 		}
-		// If we are at a new line, we can end the statement
+		// If we are at a new line or the end of the source, we can end the statement. The end
+		// is on the same line as the last token, so it needs checking by itself
 		let token: Token = self.peek();
-		if self.previous().line < token.line {
+		if self.previous().line < token.line || self.is_at_end() {
 			return Ok(());
 		}
-		Err(Error::ParseError(token, "Expected ';' or new line after expression.".to_string()))
+		let message = if token.token_type.starts_statement() {
+			"Statements on the same line must be separated by ';' (found start of a new statement)"
+				.to_string()
+		} else if token.token_type.is_operator() {
+			format!(
+				"Expected ';' or new line after expression (found operator '{}', is part of the expression mistyped?)",
+				token.lexeme
+			)
+		} else {
+			"Expected ';' or new line after expression.".to_string()
+		};
+		Err(Error::ParseError(token, message))
 	}
 
 	/// ================================  EXPRESSIONS
 
 	/// Parse an expression
 	fn expression(&mut self) -> Result<Expr, Error> {
-		self.assignment()
+		self.nested(Self::assignment)
+	}
+
+	/// Parse something one level deeper than the current expression
+	fn nested(&mut self, parse: fn(&mut Self) -> Result<Expr, Error>) -> Result<Expr, Error> {
+		self.deepen()?;
+		let expr = parse(self);
+		self.depth -= 1;
+		expr
+	}
+
+	/// Go one level deeper, each link in a chain of operators or calls nests the chain so far
+	/// inside the next link. Callers put depth back once they are done
+	fn deepen(&mut self) -> Result<(), Error> {
+		if self.depth >= self.max_nesting {
+			return Err(Error::ParseError(self.peek(), "Expression too deeply nested.".to_string()));
+		}
+		self.depth += 1;
+		Ok(())
 	}
 
 	fn assignment(&mut self) -> Result<Expr, Error> {
@@ -260,22 +503,10 @@ impl Parser {
 			TokenType::MinusEqual,
 			TokenType::StarEqual,
 			TokenType::SlashEqual,
-			TokenType::MinusMinus,
-			TokenType::PlusPlus,
 		]) {
 			let operator = self.previous();
 			let value = if operator.token_type == TokenType::Equal {
 				self.or()?
-			} else if operator.token_type == TokenType::MinusMinus ||
-				operator.token_type == TokenType::PlusPlus
-			{
-				// Value is ++ or --, so we will operate on 1
-				let right = Expr::Literal(Literal { value: LiteralType::Number(1.0) });
-				Expr::Binary(Box::new(Binary {
-					left: expr.clone(),
-					operator: operator.clone(),
-					right,
-				}))
 			} else {
 				// Value is an operation as well as an assignment (+= 1 etc)
 				let right = self.or()?;
@@ -285,82 +516,80 @@ impl Parser {
 					right,
 				}))
 			};
-
-			// Check if we are assigning a variable or a property on an instance
-			if let Expr::Variable(variable) = expr.clone() {
-				return Ok(Expr::Assign(Box::new(Assign { name: variable.name, value })));
-			} else if let Expr::Get(assign) = &expr {
-				return Ok(Expr::Set(Box::new(Set {
-					object: assign.object.clone(),
-					name: assign.name.clone(),
-					value,
-				})));
-			} else if let Expr::Index(index) = &expr {
-				// return Ok(Expr::Set(Box::new(Set {
-				// 	object: Expr::Index(index.clone()),
-				// 	name: Token {
-				// 		token_type: TokenType::String,
-				// 		lexeme: "LEXEME".to_string(),
-				// 		literal: LiteralType::String("Idk".to_string()),
-				// 		line: operator.line,
-				// 	},
-				// 	value,
-				// })));
-				// return Ok(Expr::Set(Box::new(Set {
-				// 	object: index.object.clone(),
-				// 	name: index.index.clone(),
-				// 	value,
-				// })));
-				return Err(Error::ParseError(
-					operator,
-					"Ruh roh, Jason hasn't implemented Array assignment.".to_string(),
-				));
-			}
-			return Err(Error::ParseError(operator, "Invalid assignment target.".to_string()));
+			return Self::assign_to(expr, value)
+				.ok_or(Error::ParseError(operator, "Invalid assignment target.".to_string()));
 		}
 		Ok(expr)
 	}
 
+	/// Build the expression that assigns a value to a variable, a property on an instance or an
+	/// array element. Returns None if the target can't be assigned to
+	fn assign_to(target: Expr, value: Expr) -> Option<Expr> {
+		match target {
+			Expr::Variable(variable) =>
+				Some(Expr::Assign(Box::new(Assign { name: variable.name, value }))),
+			Expr::Get(get) =>
+				Some(Expr::Set(Box::new(Set { object: get.object, name: get.name, value }))),
+			Expr::Index(index) => Some(Expr::AssignIndex(Box::new(AssignIndex {
+				object: index.object,
+				bracket: index.bracket,
+				index: index.index,
+				value,
+			}))),
+			_ => None,
+		}
+	}
+
 	fn or(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.and()?;
+		let depth = self.depth;
 
 		while self.match_token(&[TokenType::Or]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.and()?;
 			expr = Expr::Logical(Box::new(Logical { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
 	fn and(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.equality()?;
+		let depth = self.depth;
 
 		while self.match_token(&[TokenType::And]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.equality()?;
 			expr = Expr::Logical(Box::new(Logical { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
 	/// Not equal and equal
 	fn equality(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.comparison()?;
+		let depth = self.depth;
 
 		while self.match_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.comparison()?;
 			expr = Expr::Binary(Box::new(Binary { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
 	/// Greater than, greater than or equal, less than, less than or equal
 	fn comparison(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.term()?;
+		let depth = self.depth;
 
 		while self.match_token(&[
 			TokenType::Greater,
@@ -368,49 +597,45 @@ impl Parser {
 			TokenType::Less,
 			TokenType::LessEqual,
 		]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.term()?;
 			expr = Expr::Binary(Box::new(Binary { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
 	/// Addition and subtraction
 	fn term(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.factor()?;
+		let depth = self.depth;
 
 		while self.match_token(&[TokenType::Minus, TokenType::Plus]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.factor()?;
 			expr = Expr::Binary(Box::new(Binary { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
-	/// Multiplication and division
+	/// Multiplication, division and modulo
 	fn factor(&mut self) -> Result<Expr, Error> {
-		let mut expr = self.modulo()?;
-
-		while self.match_token(&[TokenType::Slash, TokenType::Star]) {
-			let operator = self.previous();
-			let right = self.unary()?;
-			expr = Expr::Binary(Box::new(Binary { left: expr, operator, right }));
-		}
-
-		Ok(expr)
-	}
-
-	fn modulo(&mut self) -> Result<Expr, Error> {
 		let mut expr = self.unary()?;
+		let depth = self.depth;
 
-		while self.match_token(&[TokenType::Modulo]) {
+		while self.match_token(&[TokenType::Slash, TokenType::Star, TokenType::Modulo]) {
+			self.deepen()?;
 			let operator = self.previous();
 			let right = self.unary()?;
 			expr = Expr::Binary(Box::new(Binary { left: expr, operator, right }));
 		}
 
+		self.depth = depth;
 		Ok(expr)
 	}
 
@@ -418,19 +643,69 @@ impl Parser {
 	fn unary(&mut self) -> Result<Expr, Error> {
 		if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
 			let operator = self.previous();
-			let right = self.unary()?;
+			let right = self.nested(Self::unary)?;
 			return Ok(Expr::Unary(Box::new(Unary { operator, right })));
 		}
 
-		self.call()
+		self.postfix()
+	}
+
+	/// Postfix increment and decrement, `i++` adds one to i but gives back the value from before
+	fn postfix(&mut self) -> Result<Expr, Error> {
+		let expr = self.call()?;
+
+		if self.match_token(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+			let operator = self.previous();
+			let step = Expr::Binary(Box::new(Binary {
+				left: expr.clone(),
+				operator: operator.clone(),
+				right: Expr::Literal(Literal { value: LiteralType::Number(1.0) }),
+			}));
+			let Some(assign) = Self::assign_to(expr.clone(), step) else {
+				return Err(Error::ParseError(
+					operator.clone(),
+					format!(
+						"Can only apply '{}' to a variable, property or array element.",
+						operator.lexeme
+					),
+				));
+			};
+			return Ok(Expr::Postfix(Box::new(Postfix { target: expr, operator, assign })));
+		}
+		Ok(expr)
 	}
 
 	fn call(&mut self) -> Result<Expr, Error> {
-		let mut expr = self.index_array()?;
+		let mut expr = self.primary()?;
+		let depth = self.depth;
 
+		// Calls, property access and indexing can be chained in any order, eg foo()[0].bar
 		loop {
+			if self.check(&TokenType::LeftParen) ||
+				self.check(&TokenType::LeftSquare) ||
+				self.check(&TokenType::Dot)
+			{
+				self.deepen()?;
+			}
 			if self.match_token(&[TokenType::LeftParen]) {
 				expr = self.finish_call(expr)?;
+			} else if self.match_token(&[TokenType::LeftSquare]) {
+				let bracket = self.previous();
+				let start =
+					if self.check(&TokenType::Colon) { None } else { Some(self.expression()?) };
+				if self.match_token(&[TokenType::Colon]) {
+					let end = if self.check(&TokenType::RightSquare) {
+						None
+					} else {
+						Some(self.expression()?)
+					};
+					self.consume(TokenType::RightSquare, "Expected ']' after slice.")?;
+					expr = Expr::Slice(Box::new(Slice { object: expr, bracket, start, end }));
+				} else {
+					self.consume(TokenType::RightSquare, "Expected ']' after index.")?;
+					let index = start.expect("an index without a colon always has an expression");
+					expr = Expr::Index(Box::new(Index { object: expr, bracket, index }));
+				}
 			} else if self.match_token(&[TokenType::Dot]) {
 				let name =
 					self.consume(TokenType::Identifier, "Expected property name after '.'.")?;
@@ -439,6 +714,7 @@ impl Parser {
 				break;
 			}
 		}
+		self.depth = depth;
 
 		Ok(expr)
 	}
@@ -447,11 +723,14 @@ impl Parser {
 		let mut arguments = Vec::with_capacity(255);
 		if !self.check(&TokenType::RightParen) {
 			loop {
-				if arguments.len() > 255 {
-					return Err(Error::ParseError(
-						self.peek(),
-						"Cannot have more than 255 arguments.".to_string(),
-					));
+				if arguments.len() == 255 {
+					error!(
+						self,
+						Error::ParseError(
+							self.peek(),
+							"Cannot have more than 255 arguments.".to_string()
+						)
+					);
 				}
 				arguments.push(self.expression()?);
 				if !self.match_token(&[TokenType::Comma]) {
@@ -463,23 +742,19 @@ impl Parser {
 		Ok(Expr::Call(Box::new(Call { callee, paren, arguments })))
 	}
 
-	fn index_array(&mut self) -> Result<Expr, Error> {
-		let mut expr = self.primary()?;
-
-		while self.match_token(&[TokenType::LeftSquare]) {
-			let index = self.expression()?;
-			self.consume(TokenType::RightSquare, "Expected ']' after index.")?;
-			expr = Expr::Index(Box::new(Index { object: expr, index }));
-		}
-
-		Ok(expr)
-	}
-
 	/// Primary expression
 	fn primary(&mut self) -> Result<Expr, Error> {
+		if self.match_token(&[TokenType::If]) {
+			return self.if_expression();
+		}
 		if self.match_token(&[TokenType::LeftSquare]) {
 			return self.array();
 		}
+		// A brace at the start of a statement is a block, anywhere an expression is expected it
+		// starts a map
+		if self.match_token(&[TokenType::LeftBrace]) {
+			return self.map();
+		}
 		if self.match_token(&[TokenType::False]) {
 			return Ok(Expr::Literal(Literal { value: LiteralType::Bool(false) }));
 		}
@@ -498,6 +773,17 @@ impl Parser {
 			return Ok(Expr::Variable(expressions::Variable { name: self.previous() }));
 		}
 
+		if self.match_token(&[TokenType::Super]) {
+			let keyword = self.previous();
+			self.consume(TokenType::Dot, "Expected '.' after 'super'.")?;
+			let method = self.consume(TokenType::Identifier, "Expected superclass method name.")?;
+			return Ok(Expr::Super(Super { keyword, method }));
+		}
+
+		if self.match_token(&[TokenType::This]) {
+			return Ok(Expr::This(This { keyword: self.previous() }));
+		}
+
 		if self.match_token(&[TokenType::LeftParen]) {
 			let expr = self.expression()?;
 			self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
@@ -512,7 +798,8 @@ impl Parser {
 		if !self.check(&TokenType::RightSquare) {
 			loop {
 				elements.push(self.expression()?);
-				if !self.match_token(&[TokenType::Comma]) {
+				// A trailing comma is allowed before the closing bracket
+				if !self.match_token(&[TokenType::Comma]) || self.check(&TokenType::RightSquare) {
 					break;
 				}
 			}
@@ -522,6 +809,25 @@ impl Parser {
 		Ok(Expr::Array(Box::new(Array { values: elements })))
 	}
 
+	fn map(&mut self) -> Result<Expr, Error> {
+		let brace = self.previous();
+		let mut entries = Vec::new();
+		if !self.check(&TokenType::RightBrace) {
+			loop {
+				let key = self.expression()?;
+				self.consume(TokenType::Colon, "Expected ':' after map key.")?;
+				entries.push((key, self.expression()?));
+				// A trailing comma is allowed before the closing brace
+				if !self.match_token(&[TokenType::Comma]) || self.check(&TokenType::RightBrace) {
+					break;
+				}
+			}
+		}
+
+		self.consume(TokenType::RightBrace, "Expected '}' after map entries.")?;
+		Ok(Expr::Map(Box::new(Map { brace, entries })))
+	}
+
 	/// Since we have thrown an error, we need to synchronize the parser to the next
 	/// statement boundary. We will catch the exception there and continue parsing
 	fn synchronize(&mut self) {
@@ -535,12 +841,17 @@ impl Parser {
 			match self.peek().token_type {
 				TokenType::Class |
 				TokenType::Funk |
+				TokenType::Pure |
 				TokenType::Var |
+				TokenType::Import |
 				TokenType::For |
+				TokenType::Global |
 				TokenType::If |
 				TokenType::While |
 				TokenType::Print |
-				TokenType::Return => return,
+				TokenType::Return |
+				TokenType::Break |
+				TokenType::Continue => return,
 				_ => {
 					let _ = self.advance();
 				},
@@ -565,7 +876,7 @@ impl Parser {
 		if self.is_at_end() {
 			return false;
 		}
-		return &self.peek().token_type == token_type;
+		&self.peek().token_type == token_type
 	}
 
 	/// Advance the current token and return the previous token
@@ -582,7 +893,37 @@ impl Parser {
 			return Ok(self.advance());
 		}
 
-		return Err(Error::ParseError(self.peek(), message.to_string()));
+		Err(Error::ParseError(self.peek(), message.to_string()))
+	}
+
+	/// Consume the name in a declaration. A keyword followed by something that could come after
+	/// the name, like `var class = 3`, is reported as a reserved word and then used as the name
+	/// anyway, so the rest of the declaration parses and later errors are still accurate
+	fn consume_name(
+		&mut self,
+		kind: &str,
+		message: &str,
+		follows: &[TokenType],
+	) -> Result<Token, Error> {
+		let token = self.peek();
+		let next = &self.tokens[(self.current + 1).min(self.tokens.len() - 1)];
+		if token.token_type.is_keyword() &&
+			(follows.contains(&next.token_type) || next.line > token.line)
+		{
+			error!(
+				self,
+				Error::ParseError(
+					token.clone(),
+					format!(
+						"'{}' is a reserved word and cannot be used as a {} name",
+						token.lexeme, kind
+					),
+				)
+			);
+			self.advance();
+			return Ok(Token { token_type: TokenType::Identifier, ..token });
+		}
+		self.consume(TokenType::Identifier, message)
 	}
 
 	/// Are we at the end of the list of tokens
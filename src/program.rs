@@ -0,0 +1,131 @@
+use crate::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, Output},
+	parser::Parser,
+	resolver::Resolver,
+	scanner::Scanner,
+	statements::Stmt,
+	token::{SourceId, Token},
+};
+use std::{cell::RefCell, collections::HashMap, io, rc::Rc};
+
+/// The source of the prelude, see prelude.jasn
+pub const PRELUDE_SOURCE: &str = include_str!("prelude.jasn");
+
+thread_local! {
+	// Compiled the first time an interpreter on this thread loads the prelude. Statements hold
+	// Rcs so they can't be shared between threads
+	static PRELUDE: Rc<CompiledProgram> = Rc::new(
+		compile_source(PRELUDE_SOURCE).unwrap_or_else(|e| panic!("the prelude doesn't compile: {:?}", e)),
+	);
+}
+
+/// The prelude, compiled once for this thread and shared by every interpreter on it
+pub(crate) fn prelude() -> Rc<CompiledProgram> {
+	PRELUDE.with(Rc::clone)
+}
+
+/// Source that has already been scanned, parsed and resolved, so it can be run by any number
+/// of interpreters with Interpreter::load_compiled without doing that work again. Embedders
+/// creating an interpreter per request can compile the modules they always load once up front.
+///
+/// The locals table holds how many scopes out from its use each local variable lives. That
+/// distance only depends on the scopes written in the source, never on where the program runs,
+/// as long as it runs in the global environment the resolver assumed. That is why a compiled
+/// program can only be loaded at the top level, and why it must never be resolved against
+/// scopes that exist at run time
+#[derive(Debug, Clone)]
+pub struct CompiledProgram {
+	pub(crate) statements: Rc<Vec<Stmt>>,
+	// The source every token in the statements was scanned from
+	pub(crate) source: SourceId,
+	pub(crate) locals: HashMap<Token, u64>,
+	pub(crate) private_uses: HashMap<Token, Option<String>>,
+}
+
+impl CompiledProgram {
+	pub fn statements(&self) -> &[Stmt] {
+		&self.statements
+	}
+}
+
+/// Scan, parse and resolve source as a top level program without running it. It is resolved
+/// against an interpreter that only has the natives, so it can't assign to globals that only
+/// the interpreter loading it would define. Returns the diagnostics if anything was rejected
+pub fn compile_source(source: &str) -> Result<CompiledProgram, Vec<String>> {
+	let sink: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = Rc::new(RefCell::new(ErrorHandler::with_output(Rc::clone(&sink))));
+	let diagnostics = |error_handler: &Rc<RefCell<ErrorHandler>>| {
+		std::mem::take(&mut error_handler.borrow_mut().diagnostics)
+	};
+
+	let mut scanner = Scanner::new(source.as_bytes().to_vec(), Rc::clone(&error_handler));
+	scanner.scan_tokens();
+	if error_handler.borrow().had_syntax_error {
+		return Err(diagnostics(&error_handler));
+	}
+	let source = scanner.source_id();
+	// Nothing runs, but the default input would lock standard input away from the caller
+	let mut interpreter = Interpreter::builder(Rc::clone(&error_handler))
+		.prelude(false)
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(Rc::clone(&sink))
+		.build();
+	let statements = Parser::new(scanner.tokens, Rc::clone(&error_handler))
+		.max_nesting(interpreter.max_nesting)
+		.parse();
+	if error_handler.borrow().had_syntax_error {
+		return Err(diagnostics(&error_handler));
+	}
+	Resolver::new(&mut interpreter, Rc::clone(&error_handler)).resolve_block(&statements);
+	if error_handler.borrow().had_resolve_error {
+		return Err(diagnostics(&error_handler));
+	}
+	Ok(CompiledProgram {
+		statements: Rc::new(statements),
+		source,
+		locals: interpreter.locals.take(source),
+		private_uses: interpreter.private_uses.take(source),
+	})
+}
+
+/// What the resolver noted about tokens, with a table of its own for each source. A token only
+/// knows where it is inside its source, so two sources can have tokens at the same place and
+/// their tables are never merged
+#[derive(Debug)]
+pub(crate) struct SourceTables<T> {
+	tables: HashMap<SourceId, HashMap<Token, T>>,
+}
+
+impl<T> Default for SourceTables<T> {
+	fn default() -> Self {
+		Self { tables: HashMap::new() }
+	}
+}
+
+// A token's literal can hold an array, but nothing changes it once the token is scanned
+#[allow(clippy::mutable_key_type)]
+impl<T> SourceTables<T> {
+	pub(crate) fn get(&self, token: &Token) -> Option<&T> {
+		self.tables.get(&token.source)?.get(token)
+	}
+
+	pub(crate) fn insert(&mut self, token: &Token, value: T) {
+		self.tables.entry(token.source).or_default().insert(token.clone(), value);
+	}
+
+	pub(crate) fn remove(&mut self, token: &Token) {
+		if let Some(table) = self.tables.get_mut(&token.source) {
+			table.remove(token);
+		}
+	}
+
+	/// Use a table made for source somewhere else, in place of any this already has for it
+	pub(crate) fn load(&mut self, source: SourceId, table: HashMap<Token, T>) {
+		self.tables.insert(source, table);
+	}
+
+	pub(crate) fn take(&mut self, source: SourceId) -> HashMap<Token, T> {
+		self.tables.remove(&source).unwrap_or_default()
+	}
+}
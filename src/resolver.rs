@@ -1,20 +1,97 @@
 use crate::{
-	callable::FunctionType,
+	callable::{Callable, ClassType, FunctionType},
 	error,
 	error_handler::{Error, ErrorHandler},
 	expressions,
 	expressions::*,
 	interpreter::Interpreter,
+	native_functions::{IMPURE_NATIVES, SANDBOXED_NATIVES},
 	statements,
 	statements::*,
-	token::{LiteralType, Token},
+	token::{LiteralType, Span, Token, TokenType},
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+	cell::RefCell,
+	collections::{HashMap, HashSet},
+	fmt,
+	rc::Rc,
+};
+
+/// A single decision made by the resolver about where a variable reference lives.
+/// A depth of None means the variable was left for the global environment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+	pub name: Token,
+	pub depth: Option<u64>,
+	pub definition: Definition,
+}
+
+/// What a variable reference refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Definition {
+	/// The name of the variable, parameter, function or class declaring it. `this` and `super`
+	/// point at the class and superclass names
+	Declared(Span),
+	/// A native function
+	Native,
+	/// One of the prelude's functions
+	Prelude,
+	/// Nothing in the source being resolved declares it. It may still be a global defined by an
+	/// import or an earlier run
+	Unresolved,
+}
+
+impl fmt::Display for Binding {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.depth {
+			Some(depth) =>
+				write!(f, "[line {}] {} -> depth {}", self.name.line, self.name.lexeme, depth),
+			None => write!(f, "[line {}] {} -> global", self.name.line, self.name.lexeme),
+		}
+	}
+}
+
+/// A variable declared in one of the resolver's scopes
+#[derive(Debug, Copy, Clone)]
+struct Local {
+	// False until the initializer has been resolved
+	ready: bool,
+	// Where it is declared
+	span: Span,
+}
 
 pub struct Resolver<'a> {
 	pub interpreter: &'a mut Interpreter,
-	scopes: Vec<HashMap<String, bool>>,
+	scopes: Vec<HashMap<String, Local>>,
 	current_function: FunctionType,
+	current_class: ClassType,
+	// The name of the class being resolved, its methods can use its underscore names
+	class_name: Option<String>,
+	// How many loops we are inside of in the current function
+	current_loop: u32,
+	// How deeply nested the expression being resolved is
+	depth: usize,
+	// Inside a pure function this is the index of its scope in scopes, anything declared in an
+	// earlier scope is outside of it
+	pure_scope: Option<usize>,
+	// The names in each scope that are pure functions, kept alongside scopes
+	pure_functions: Vec<HashSet<String>>,
+	// Whether each global declared while resolving is a pure function
+	global_functions: HashMap<String, bool>,
+	// Names declared at the top level or by a global statement anywhere in the code being
+	// resolved, assigning to a name that isn't one of these or a local is an error
+	globals: HashSet<String>,
+	// Where each of those globals is first declared
+	global_spans: HashMap<String, Span>,
+	// Modules define globals we can't see until they run, so code importing one can assign to
+	// names we don't know about
+	has_imports: bool,
+	// Every variable reference we have resolved, in the order we found them
+	pub bindings: Vec<Binding>,
+	// Natives defined when the resolver was created, declaring one of these names hides it
+	natives: HashSet<String>,
+	// The same for the functions the prelude defined
+	prelude: HashSet<String>,
 	// The error handler
 	pub error_handler: Rc<RefCell<ErrorHandler>>,
 }
@@ -24,16 +101,43 @@ impl Resolver<'_> {
 		interpreter: &mut Interpreter,
 		error_handler: Rc<RefCell<ErrorHandler>>,
 	) -> Resolver {
+		let natives = interpreter
+			.global
+			.borrow()
+			.entries()
+			.into_iter()
+			.filter(|(_, value)| {
+				matches!(value, LiteralType::Callable(Callable::NativeFunction(_)))
+			})
+			.map(|(name, _)| name)
+			.collect();
+		let prelude = interpreter.prelude_names().cloned().collect();
 		Resolver {
+			natives,
+			prelude,
 			interpreter,
 			scopes: vec![],
 			current_function: FunctionType::None,
+			current_class: ClassType::None,
+			class_name: None,
+			current_loop: 0,
+			depth: 0,
+			pure_scope: None,
+			pure_functions: vec![],
+			global_functions: HashMap::new(),
+			globals: HashSet::new(),
+			global_spans: HashMap::new(),
+			has_imports: false,
+			bindings: vec![],
 			error_handler,
 		}
 	}
 
 	/// Resolve a block of statements
 	pub(crate) fn resolve_block(&mut self, statements: &Vec<Stmt>) {
+		if self.scopes.is_empty() {
+			self.collect_globals(statements);
+		}
 		for statement in statements {
 			if let Err(e) = self.resolve_stmt(statement) {
 				error!(self, e);
@@ -41,6 +145,118 @@ impl Resolver<'_> {
 		}
 	}
 
+	/// Note every global the top level statements declare before resolving them, so a function can
+	/// assign to a global declared further down
+	fn collect_globals(&mut self, statements: &[Stmt]) {
+		let mut declare = |name: &Token| {
+			self.globals.insert(name.lexeme.clone());
+			self.global_spans.entry(name.lexeme.clone()).or_insert(name.span());
+		};
+		for statement in statements {
+			match statement {
+				Stmt::Variable(variable) => declare(&variable.name),
+				Stmt::Function(function) => declare(&function.name),
+				Stmt::Class(class) => declare(&class.name),
+				Stmt::Import(_) => self.has_imports = true,
+				_ => (),
+			}
+			statement.walk(&mut |stmt| {
+				if let Stmt::Global(global) = stmt {
+					declare(&global.name);
+				}
+			});
+		}
+	}
+
+	/// Assigning to a name that no scope declares only works if it is a global, catch the ones
+	/// that can't be before the code runs
+	fn check_declared(&self, name: &Token) -> Result<(), Error> {
+		if self.has_imports ||
+			self.declaring_scope(&name.lexeme).is_some() ||
+			self.globals.contains(&name.lexeme) ||
+			self.interpreter.global.borrow().lookup(&name.lexeme).is_some()
+		{
+			return Ok(());
+		}
+		Err(Error::ResolverError(
+			name.clone(),
+			format!(
+				"Assignment to undeclared variable '{}', did you mean 'var {} = ...'?",
+				name.lexeme, name.lexeme
+			),
+		))
+	}
+
+	/// Warn about loops that can obviously never stop: the condition can't change anything itself
+	/// and the body is empty or never assigns a variable the condition reads. Any call or return
+	/// in the body could end the loop, so those are given the benefit of the doubt
+	fn check_infinite_loop(&mut self, while_stmt: &While) {
+		let condition = &while_stmt.condition;
+		if condition.has_side_effects() {
+			return;
+		}
+		if let Expr::Literal(literal) = condition {
+			if !literal.value.is_truthy() {
+				return;
+			}
+		}
+
+		let mut read = Vec::new();
+		condition.walk(&mut |expr| {
+			if let Expr::Variable(variable) = expr {
+				if !read.contains(&variable.name.lexeme) {
+					read.push(variable.name.lexeme.clone());
+				}
+			}
+		});
+
+		let mut empty = while_stmt.increment.is_none();
+		let mut can_exit = false;
+		let mut expressions: Vec<&Expr> = while_stmt.increment.iter().collect();
+		let mut assigned = Vec::new();
+		while_stmt.body.walk(&mut |stmt| {
+			match stmt {
+				Stmt::Block(_) => return,
+				Stmt::Return(_) | Stmt::Break(_) => can_exit = true,
+				Stmt::MultiAssign(multi_assign) => assigned.extend(
+					multi_assign.targets.iter().filter_map(|target| target.root_variable()),
+				),
+				_ => (),
+			}
+			empty = false;
+			expressions.extend(stmt.expressions());
+		});
+		for expr in expressions {
+			expr.walk(&mut |expr| {
+				if let Expr::Call(_) = expr {
+					can_exit = true;
+				}
+				if let Some(name) = expr.assigned_variable() {
+					assigned.push(name);
+				}
+			});
+		}
+
+		let line = while_stmt.keyword.line;
+		if empty {
+			self.error_handler.borrow_mut().report_warning(
+				line,
+				"This loop has an empty body and nothing can change its condition, it will never stop.",
+			);
+		} else if !can_exit &&
+			!read.is_empty() &&
+			!read.iter().any(|name| assigned.iter().any(|assigned| &assigned.lexeme == name))
+		{
+			self.error_handler.borrow_mut().report_warning(
+				line,
+				&format!(
+					"Nothing in this loop assigns to {}, so its condition never changes and it will never stop.",
+					read.join(", ")
+				),
+			);
+		}
+	}
+
 	/// Resolve an individual statement by calling the accept method
 	fn resolve_stmt(&mut self, statement: &Stmt) -> Result<(), Error> {
 		statement.accept(self)
@@ -48,18 +264,105 @@ impl Resolver<'_> {
 
 	/// Resolve an expression by calling accept
 	fn resolve_expr(&mut self, expression: &Expr) -> Result<(), Error> {
-		expression.accept(self)?;
+		// The parser limits nesting too, this catches expressions built some other way
+		if self.depth >= self.interpreter.max_nesting {
+			let token = expression.token().cloned().unwrap_or_else(|| {
+				Token::new(TokenType::Eof, String::new(), LiteralType::Null, 0, 0, 0)
+			});
+			return Err(Error::ResolverError(token, "Expression too deeply nested.".to_string()));
+		}
+		self.depth += 1;
+		let result = expression.accept(self);
+		self.depth -= 1;
+		result?;
 		Ok(())
 	}
 
 	/// Begin a new scope, this pushes a Hashmap to the Vec pretending to be a stack
 	fn begin_scope(&mut self) {
 		self.scopes.push(HashMap::new());
+		self.pure_functions.push(HashSet::new());
 	}
 
 	/// End a scope by popping the last element from the stack
 	fn end_scope(&mut self) {
 		self.scopes.pop();
+		self.pure_functions.pop();
+	}
+
+	/// The index in scopes of the innermost scope declaring name, None for globals
+	fn declaring_scope(&self, name: &str) -> Option<usize> {
+		self.scopes.iter().rposition(|scope| scope.contains_key(name))
+	}
+
+	/// Whether name refers to a function that is known to be pure
+	fn is_pure_function(&self, name: &str) -> bool {
+		if let Some(i) = self.declaring_scope(name) {
+			return self.pure_functions[i].contains(name);
+		}
+		if let Some(pure) = self.global_functions.get(name) {
+			return *pure;
+		}
+		// Declared by an earlier run, or a native
+		match self.interpreter.global.borrow().lookup(name) {
+			Some(LiteralType::Callable(Callable::Function(function))) => function.declaration.pure,
+			Some(LiteralType::Callable(Callable::NativeFunction(_))) =>
+				!IMPURE_NATIVES.contains(&name) && !SANDBOXED_NATIVES.contains(&name),
+			_ => false,
+		}
+	}
+
+	/// Remember the class a use of an underscore name is written in, so the interpreter can check
+	/// it is allowed to. Through this it always is
+	fn note_private_use(&mut self, object: &Expr, name: &Token) {
+		if name.lexeme.starts_with('_') && !matches!(object, Expr::This(_)) {
+			self.interpreter.private_uses.insert(name, self.class_name.clone());
+		}
+	}
+
+	/// Inside a pure function, only variables declared within it can be assigned to
+	fn check_pure_assignment(&self, name: &Token) -> Result<(), Error> {
+		let Some(pure_scope) = self.pure_scope else {
+			return Ok(());
+		};
+		if self.declaring_scope(&name.lexeme).map_or(true, |i| i < pure_scope) {
+			return Err(Error::ResolverError(
+				name.clone(),
+				format!(
+					"A pure function can't assign to '{}' because it is declared outside of it.",
+					name.lexeme
+				),
+			));
+		}
+		Ok(())
+	}
+
+	/// Inside a pure function nothing can be changed in place. Arrays, maps and instances are
+	/// shared, so even one the function made itself might have come from its arguments
+	fn check_pure_field(&self, name: &Token) -> Result<(), Error> {
+		self.check_pure_mutation(
+			name,
+			format!(
+				"A pure function can't set '{}', the object might belong to its caller.",
+				name.lexeme
+			),
+		)
+	}
+
+	/// Like check_pure_field for an element of an array or map, bracket is its '['
+	fn check_pure_element(&self, bracket: &Token) -> Result<(), Error> {
+		self.check_pure_mutation(
+			bracket,
+			"A pure function can't change an element, the array or map might belong to its caller."
+				.to_string(),
+		)
+	}
+
+	fn check_pure_mutation(&self, token: &Token, message: String) -> Result<(), Error> {
+		match self.pure_scope {
+			Some(_) => Err(Error::ResolverError(token.clone(), message)),
+			None => Ok(()),
+		}
 	}
 
 	/// Declare a variable first, we need to check if we are inside the initializer so we split this
@@ -67,16 +370,47 @@ impl Resolver<'_> {
 	/// Declaration adds the variable to the inner most scope and shadows the outer one so we
 	/// know that it exists, but the false says it's not ready to use yet
 	fn declare(&mut self, name: &Token) -> Result<(), Error> {
+		// Shadowing is allowed, but calling the native afterwards fails in confusing ways
+		if self.natives.contains(&name.lexeme) {
+			self.error_handler.borrow_mut().report_warning(
+				name.line,
+				&format!(
+					"'{}' shadows the native function {}, which can't be called while it is hidden.",
+					name.lexeme, name.lexeme
+				),
+			);
+		}
+		if self.prelude.contains(&name.lexeme) {
+			self.error_handler.borrow_mut().report_warning(
+				name.line,
+				&format!(
+					"'{}' shadows the prelude function {}, which can't be called while it is hidden.",
+					name.lexeme, name.lexeme
+				),
+			);
+		}
+		// Whatever the name meant before, it isn't a pure function now
+		match self.pure_functions.last_mut() {
+			Some(pure_functions) => {
+				pure_functions.remove(&name.lexeme);
+			},
+			None => {
+				self.global_functions.insert(name.lexeme.clone(), false);
+			},
+		}
 		match self.scopes.last_mut() {
 			None => Ok(()), // Empty scopes
 			Some(scope) => {
-				if scope.contains_key(&name.lexeme) {
+				if let Some(previous) = scope.get(&name.lexeme) {
 					return Err(Error::ResolverError(
 						name.to_owned(),
-						"There's already a variable with this name in this scope.".to_string(),
+						format!(
+							"There's already a variable with this name in this scope.\n    '{}' was previously defined at line {}.",
+							name.lexeme, previous.span.line
+						),
 					))
 				}
-				scope.insert(name.lexeme.to_owned(), false);
+				scope.insert(name.lexeme.to_owned(), Local { ready: false, span: name.span() });
 				Ok(())
 			},
 		}
@@ -87,22 +421,46 @@ impl Resolver<'_> {
 	fn define(&mut self, name: &Token) {
 		match self.scopes.last_mut() {
 			None => return, // Empty scopes
-			Some(scope) => scope.insert(name.lexeme.to_owned(), true),
+			Some(scope) =>
+				scope.insert(name.lexeme.to_owned(), Local { ready: true, span: name.span() }),
 		};
 	}
 
 	/// Resolve a local variable by checking the scopes from inner to outer
-	fn resolve_local(&mut self, expr: &Expr, name: &Token) -> Result<(), Error> {
+	fn resolve_local(&mut self, name: &Token) -> Result<(), Error> {
 		for (i, scope) in self.scopes.iter_mut().rev().enumerate() {
-			if scope.contains_key(&name.lexeme) {
+			if let Some(local) = scope.get(&name.lexeme) {
+				let definition = Definition::Declared(local.span);
 				// Pass through the number of scopes between the variable and the innermost scope
-				self.interpreter.resolve(expr.clone(), i as u64)?;
+				self.interpreter.resolve(name, i as u64)?;
+				self.bindings.push(Binding {
+					name: name.clone(),
+					depth: Some(i as u64),
+					definition,
+				});
 				return Ok(());
 			}
 		}
+		// Not found in any scope, so it will be looked up in the globals at runtime
+		let definition = self.global_definition(&name.lexeme);
+		self.bindings.push(Binding { name: name.clone(), depth: None, definition });
 		Ok(())
 	}
 
+	/// What a name that isn't in any scope refers to
+	fn global_definition(&self, name: &str) -> Definition {
+		if let Some(span) = self.global_spans.get(name) {
+			return Definition::Declared(*span);
+		}
+		match self.interpreter.global.borrow().lookup(name) {
+			Some(LiteralType::Callable(Callable::NativeFunction(_))) => Definition::Native,
+			Some(LiteralType::Callable(Callable::Function(_)))
+				if self.interpreter.is_prelude(name) =>
+				Definition::Prelude,
+			_ => Definition::Unresolved,
+		}
+	}
+
 	fn resolve_function(
 		&mut self,
 		function: Function,
@@ -110,14 +468,25 @@ impl Resolver<'_> {
 	) -> Result<(), Error> {
 		let enclosing_function = self.current_function;
 		self.current_function = function_type;
+		// A loop around the function definition can't be broken out of from inside it
+		let enclosing_loop = std::mem::take(&mut self.current_loop);
+		// A function inside a pure one can do anything, it's calling it that isn't allowed
+		let enclosing_pure_scope =
+			std::mem::replace(&mut self.pure_scope, function.pure.then_some(self.scopes.len()));
 		self.begin_scope();
+		// A repeated parameter is reported but the body is still resolved, so everything above
+		// is put back the same way whatever goes wrong inside
 		for param in function.params.iter() {
-			self.declare(param)?;
+			if let Err(e) = self.declare(param) {
+				error!(self, e);
+			}
 			self.define(param);
 		}
 		self.resolve_block(&function.body);
 		self.end_scope();
 		self.current_function = enclosing_function;
+		self.current_loop = enclosing_loop;
+		self.pure_scope = enclosing_pure_scope;
 		Ok(())
 	}
 }
@@ -131,15 +500,55 @@ impl statements::Visitor for Resolver<'_> {
 	}
 
 	fn visit_class(&mut self, class: &Class) -> Result<(), Error> {
+		let enclosing_class = self.current_class;
+		self.current_class = ClassType::Class;
+		let enclosing_name = self.class_name.replace(class.name.lexeme.clone());
 		self.declare(&class.name)?;
 		self.define(&class.name);
 
-		for method in &class.methods {
-			let declaration = FunctionType::Method;
-			self.resolve_function(method.clone(), declaration)?;
+		if let Some(superclass) = &class.superclass {
+			if superclass.name.lexeme == class.name.lexeme {
+				self.current_class = enclosing_class;
+				self.class_name = enclosing_name;
+				return Err(Error::ResolverError(
+					superclass.name.clone(),
+					"A class can't inherit from itself.".to_string(),
+				));
+			}
+			self.current_class = ClassType::Subclass;
+			self.resolve_expr(&Expr::Variable(superclass.clone()))?;
+			// Methods of a subclass see "super" in a scope just outside "this"
+			self.begin_scope();
+			if let Some(scope) = self.scopes.last_mut() {
+				let span = superclass.name.span();
+				scope.insert("super".to_string(), Local { ready: true, span });
+			}
 		}
 
-		Ok(())
+		// Methods are bound to their instance in an environment holding just "this"
+		self.begin_scope();
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.insert("this".to_string(), Local { ready: true, span: class.name.span() });
+		}
+		let mut result = Ok(());
+		for method in &class.methods {
+			let declaration = if method.name.lexeme == "init" {
+				FunctionType::Initializer
+			} else {
+				FunctionType::Method
+			};
+			result = self.resolve_function(method.clone(), declaration);
+			if result.is_err() {
+				break;
+			}
+		}
+		self.end_scope();
+		if class.superclass.is_some() {
+			self.end_scope();
+		}
+		self.current_class = enclosing_class;
+		self.class_name = enclosing_name;
+		result
 	}
 
 	fn visit_expression(&mut self, expression: &Expression) -> Result<(), Error> {
@@ -147,13 +556,63 @@ impl statements::Visitor for Resolver<'_> {
 		Ok(())
 	}
 
+	fn visit_import(&mut self, import: &Import) -> Result<(), Error> {
+		// Modules define their names as globals, so importing one inside a scope would be
+		// misleading
+		if !self.scopes.is_empty() {
+			return Err(Error::ResolverError(
+				import.keyword.clone(),
+				"Imports have to be at the top level.".to_string(),
+			));
+		}
+		Ok(())
+	}
+
+	fn visit_multi_assign(&mut self, multi_assign: &MultiAssign) -> Result<(), Error> {
+		for value in &multi_assign.values {
+			self.resolve_expr(value)?;
+		}
+		for target in &multi_assign.targets {
+			match target {
+				Expr::Variable(variable) => {
+					self.check_pure_assignment(&variable.name)?;
+					self.check_declared(&variable.name)?;
+				},
+				Expr::Get(get) => self.check_pure_field(&get.name)?,
+				Expr::Index(index) => self.check_pure_element(&index.bracket)?,
+				_ => {},
+			}
+			self.resolve_expr(target)?;
+		}
+		Ok(())
+	}
+
 	fn visit_function(&mut self, function: &Function) -> Result<(), Error> {
 		self.declare(&function.name)?;
 		self.define(&function.name);
+		if function.pure {
+			match self.pure_functions.last_mut() {
+				Some(pure_functions) => {
+					pure_functions.insert(function.name.lexeme.clone());
+				},
+				None => {
+					self.global_functions.insert(function.name.lexeme.clone(), true);
+				},
+			}
+		}
 		self.resolve_function(function.clone(), FunctionType::Function)?;
 		Ok(())
 	}
 
+	fn visit_global(&mut self, global: &Global) -> Result<(), Error> {
+		self.check_pure_assignment(&global.name)?;
+		self.resolve_expr(&global.value)?;
+		let definition = self.global_definition(&global.name.lexeme);
+		self.bindings
+			.push(Binding { name: global.name.clone(), depth: None, definition });
+		Ok(())
+	}
+
 	fn visit_if(&mut self, if_stmt: &If) -> Result<(), Error> {
 		self.resolve_expr(&if_stmt.condition)?;
 		self.resolve_stmt(&if_stmt.then_branch)?;
@@ -164,10 +623,36 @@ impl statements::Visitor for Resolver<'_> {
 	}
 
 	fn visit_print(&mut self, print: &Print) -> Result<(), Error> {
+		if self.pure_scope.is_some() {
+			return Err(Error::ResolverError(
+				print.keyword.clone(),
+				"A pure function can't print.".to_string(),
+			));
+		}
 		self.resolve_expr(&print.expression)?;
 		Ok(())
 	}
 
+	fn visit_break(&mut self, break_stmt: &Break) -> Result<(), Error> {
+		if self.current_loop == 0 {
+			return Err(Error::ResolverError(
+				break_stmt.keyword.to_owned(),
+				"Can't use 'break' outside of a loop.".to_string(),
+			));
+		}
+		Ok(())
+	}
+
+	fn visit_continue(&mut self, continue_stmt: &Continue) -> Result<(), Error> {
+		if self.current_loop == 0 {
+			return Err(Error::ResolverError(
+				continue_stmt.keyword.to_owned(),
+				"Can't use 'continue' outside of a loop.".to_string(),
+			));
+		}
+		Ok(())
+	}
+
 	fn visit_return(&mut self, return_stmt: &Return) -> Result<(), Error> {
 		if self.current_function == FunctionType::None {
 			return Err(Error::ResolverError(
@@ -176,6 +661,12 @@ impl statements::Visitor for Resolver<'_> {
 			));
 		}
 		if let Some(value) = &return_stmt.value {
+			if self.current_function == FunctionType::Initializer {
+				return Err(Error::ResolverError(
+					return_stmt.keyword.to_owned(),
+					"Can't return a value from an initializer.".to_string(),
+				));
+			}
 			self.resolve_expr(value)?;
 		}
 		Ok(())
@@ -192,7 +683,14 @@ impl statements::Visitor for Resolver<'_> {
 
 	fn visit_while(&mut self, while_stmt: &While) -> Result<(), Error> {
 		self.resolve_expr(&while_stmt.condition)?;
-		self.resolve_stmt(&while_stmt.body)?;
+		self.current_loop += 1;
+		let body = self.resolve_stmt(&while_stmt.body);
+		self.current_loop -= 1;
+		body?;
+		if let Some(increment) = &while_stmt.increment {
+			self.resolve_expr(increment)?;
+		}
+		self.check_infinite_loop(while_stmt);
 		Ok(())
 	}
 }
@@ -201,8 +699,10 @@ impl expressions::Visitor for Resolver<'_> {
 	type Value = LiteralType;
 
 	fn visit_assign(&mut self, assign: &Assign) -> Result<Self::Value, Error> {
+		self.check_pure_assignment(&assign.name)?;
+		self.check_declared(&assign.name)?;
 		self.resolve_expr(&assign.value)?;
-		self.resolve_local(&Expr::Assign(Box::new(assign.clone())), &assign.name)?;
+		self.resolve_local(&assign.name)?;
 		Ok(LiteralType::Null)
 	}
 
@@ -213,17 +713,40 @@ impl expressions::Visitor for Resolver<'_> {
 	}
 
 	fn visit_call(&mut self, call: &Call) -> Result<Self::Value, Error> {
+		if self.pure_scope.is_some() {
+			match &call.callee {
+				Expr::Variable(variable) if !self.is_pure_function(&variable.name.lexeme) =>
+					return Err(Error::ResolverError(
+						variable.name.clone(),
+						format!(
+							"A pure function can't call '{}' because it isn't pure.",
+							variable.name.lexeme
+						),
+					)),
+				Expr::Variable(_) => (),
+				_ =>
+					return Err(Error::ResolverError(
+						call.paren.clone(),
+						"A pure function can only call pure functions by name.".to_string(),
+					)),
+			}
+		}
 		self.resolve_expr(&call.callee)?;
-		call.arguments.iter().for_each(|arg| self.resolve_expr(arg).unwrap());
+		for argument in &call.arguments {
+			self.resolve_expr(argument)?;
+		}
 		Ok(LiteralType::Null)
 	}
 
 	fn visit_get(&mut self, get: &Get) -> Result<Self::Value, Error> {
+		self.note_private_use(&get.object, &get.name);
 		self.resolve_expr(&get.object)?;
 		Ok(LiteralType::Null)
 	}
 
 	fn visit_set(&mut self, set: &Set) -> Result<Self::Value, Error> {
+		self.check_pure_field(&set.name)?;
+		self.note_private_use(&set.object, &set.name);
 		self.resolve_expr(&set.object)?;
 		self.resolve_expr(&set.value)?;
 		Ok(LiteralType::Null)
@@ -235,17 +758,62 @@ impl expressions::Visitor for Resolver<'_> {
 	}
 
 	fn visit_array(&mut self, array: &Array) -> Result<Self::Value, Error> {
-		array.values.iter().for_each(|val| self.resolve_expr(val).unwrap());
+		for value in &array.values {
+			self.resolve_expr(value)?;
+		}
+		Ok(LiteralType::Null)
+	}
+
+	fn visit_map(&mut self, map: &Map) -> Result<Self::Value, Error> {
+		for expr in map.expressions() {
+			self.resolve_expr(expr)?;
+		}
+		Ok(LiteralType::Null)
+	}
+
+	fn visit_if_expr(&mut self, if_expr: &IfExpr) -> Result<Self::Value, Error> {
+		if if_expr.else_branch.is_none() {
+			return Err(Error::ResolverError(
+				if_expr.keyword.clone(),
+				"An 'if' used as a value needs an 'else' branch.".to_string(),
+			));
+		}
+		self.resolve_expr(&if_expr.condition)?;
+		for branch in if_expr.branches() {
+			self.begin_scope();
+			let mut result = Ok(());
+			for statement in &branch.statements {
+				result = self.resolve_stmt(statement);
+				if result.is_err() {
+					break;
+				}
+			}
+			if let (Ok(()), Some(value)) = (&result, &branch.value) {
+				result = self.resolve_expr(value);
+			}
+			self.end_scope();
+			result?;
+		}
 		Ok(LiteralType::Null)
 	}
 
 	fn visit_index(&mut self, index: &Index) -> Result<Self::Value, Error> {
-		// self.resolve_expr(&index.object)?;
+		self.resolve_expr(&index.object)?;
 		self.resolve_expr(&index.index)?;
 		Ok(LiteralType::Null)
 	}
 
+	fn visit_slice(&mut self, slice: &Slice) -> Result<Self::Value, Error> {
+		self.resolve_expr(&slice.object)?;
+		for bound in slice.bounds() {
+			self.resolve_expr(bound)?;
+		}
+		Ok(LiteralType::Null)
+	}
+
 	fn visit_assign_index(&mut self, assign_index: &AssignIndex) -> Result<Self::Value, Error> {
+		self.check_pure_element(&assign_index.bracket)?;
+		self.resolve_expr(&assign_index.object)?;
 		self.resolve_expr(&assign_index.index)?;
 		self.resolve_expr(&assign_index.value)?;
 		Ok(LiteralType::Null)
@@ -255,6 +823,12 @@ impl expressions::Visitor for Resolver<'_> {
 		Ok(LiteralType::Null)
 	}
 
+	fn visit_postfix(&mut self, postfix: &Postfix) -> Result<Self::Value, Error> {
+		self.resolve_expr(&postfix.target)?;
+		self.resolve_expr(&postfix.assign)?;
+		Ok(LiteralType::Null)
+	}
+
 	fn visit_logical(&mut self, logical: &Logical) -> Result<Self::Value, Error> {
 		self.resolve_expr(&logical.left)?;
 		self.resolve_expr(&logical.right)?;
@@ -262,11 +836,32 @@ impl expressions::Visitor for Resolver<'_> {
 	}
 
 	fn visit_super(&mut self, super_: &Super) -> Result<Self::Value, Error> {
-		todo!()
+		match self.current_class {
+			ClassType::None =>
+				return Err(Error::ResolverError(
+					super_.keyword.clone(),
+					"Can't use 'super' outside of a class.".to_string(),
+				)),
+			ClassType::Class =>
+				return Err(Error::ResolverError(
+					super_.keyword.clone(),
+					"Can't use 'super' in a class with no superclass.".to_string(),
+				)),
+			ClassType::Subclass => (),
+		}
+		self.resolve_local(&super_.keyword)?;
+		Ok(LiteralType::Null)
 	}
 
 	fn visit_this(&mut self, this: &This) -> Result<Self::Value, Error> {
-		todo!()
+		if self.current_class == ClassType::None {
+			return Err(Error::ResolverError(
+				this.keyword.clone(),
+				"Can't use 'this' outside of a class.".to_string(),
+			));
+		}
+		self.resolve_local(&this.keyword)?;
+		Ok(LiteralType::Null)
 	}
 
 	fn visit_unary(&mut self, unary: &Unary) -> Result<Self::Value, Error> {
@@ -276,14 +871,14 @@ impl expressions::Visitor for Resolver<'_> {
 
 	fn visit_variable(&mut self, variable: &expressions::Variable) -> Result<Self::Value, Error> {
 		if let Some(scope) = self.scopes.last() {
-			if scope.get(&variable.name.lexeme) == Some(&false) {
+			if scope.get(&variable.name.lexeme).is_some_and(|local| !local.ready) {
 				return Err(Error::ResolverError(
 					variable.name.to_owned(),
 					"Can't read local variables in its own initializer.".to_string(),
 				));
 			}
 		};
-		self.resolve_local(&Expr::Variable(variable.clone()), &variable.name)?;
+		self.resolve_local(&variable.name)?;
 		Ok(LiteralType::Null)
 	}
 }
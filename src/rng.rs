@@ -0,0 +1,82 @@
+/// The algorithm behind random(), shuffle() and the rng_* streams. A seed gives the same
+/// numbers on every platform, but changing the algorithm changes every seeded sequence, so it
+/// only changes in a release that says so
+pub const ALGORITHM: &str = "xoshiro128** seeded with SplitMix64";
+
+/// A small, fast generator with 128 bits of state. The state fits in four whole numbers, so a
+/// stream can be kept in a JASN value and picked up again later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Xoshiro128 {
+	state: [u32; 4],
+}
+
+impl Xoshiro128 {
+	/// A generator whose numbers are decided by seed
+	pub fn seed(seed: u64) -> Self {
+		let mut splitmix = seed;
+		let mut next = || {
+			splitmix = splitmix.wrapping_add(0x9E37_79B9_7F4A_7C15);
+			let mut z = splitmix;
+			z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476C_E4E5_B9B9);
+			z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+			z ^ (z >> 31)
+		};
+		let (a, b) = (next(), next());
+		Self { state: [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32] }
+	}
+
+	/// Pick up a generator from its state, None for the all zero state it can never leave
+	pub fn from_state(state: [u32; 4]) -> Option<Self> {
+		(state != [0; 4]).then_some(Self { state })
+	}
+
+	pub fn state(&self) -> [u32; 4] {
+		self.state
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		let s = &mut self.state;
+		let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+		let t = s[1] << 9;
+		s[2] ^= s[0];
+		s[3] ^= s[1];
+		s[1] ^= s[2];
+		s[0] ^= s[3];
+		s[2] ^= t;
+		s[3] = s[3].rotate_left(11);
+		result
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		let high = self.next_u32() as u64;
+		(high << 32) | self.next_u32() as u64
+	}
+
+	/// A number from 0 up to but not including 1, using all 53 bits an f64 has
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// A whole number from min to max, both included. Every number is equally likely
+	pub fn int_between(&mut self, min: i64, max: i64) -> i64 {
+		let span = max.abs_diff(min).wrapping_add(1);
+		if span == 0 {
+			return self.next_u64() as i64;
+		}
+		// Numbers below this would make the smaller results a little more likely
+		let threshold = span.wrapping_neg() % span;
+		loop {
+			let n = self.next_u64();
+			if n >= threshold {
+				return min.wrapping_add((n % span) as i64);
+			}
+		}
+	}
+
+	/// Put values in a random order, every order equally likely
+	pub fn shuffle<T>(&mut self, values: &mut [T]) {
+		for i in (1..values.len()).rev() {
+			values.swap(i, self.int_between(0, i as i64) as usize);
+		}
+	}
+}
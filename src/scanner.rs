@@ -1,9 +1,25 @@
 use crate::{
 	error,
 	error_handler::{Error, ErrorHandler},
-	token::{LiteralType, Token, TokenType},
+	token::{LiteralType, SourceId, Token, TokenType},
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{
+	cell::RefCell,
+	ops::Range,
+	rc::Rc,
+	sync::atomic::{AtomicU32, Ordering},
+};
+
+// The id the next scanner gives its tokens, 0 is left for tokens made up outside of a scan
+static NEXT_SOURCE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A change to the source being scanned: old_len bytes from start are replaced by new_text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+	pub start: u32,
+	pub old_len: u32,
+	pub new_text: String,
+}
 
 // The scanner will scan through the input text and produce a list of tokens
 pub struct Scanner {
@@ -13,29 +29,199 @@ pub struct Scanner {
 	start: u32,
 	current: u32,
 	line: u32,
+	// Where the line being scanned starts in the source
+	line_start: u32,
+	// The line and column the token being scanned starts at, a string or comment can end on a
+	// later line
+	start_line: u32,
+	start_column: u32,
+	// Keep comments and whitespace as tokens instead of throwing them away
+	trivia: bool,
+	// The offset and line of every line start a token or newline was scanned from, in order.
+	// Nothing carries over from before one of these, so scanning can start again from any of
+	// them. Lines that start inside a string or comment aren't included
+	restarts: Vec<(u32, u32)>,
+	// An error stopped the last scan before the end of the source
+	stopped_early: bool,
+	// Stamped on every token, rescanning keeps it as the tokens are still from this source
+	source_id: SourceId,
 	error_handler: Rc<RefCell<ErrorHandler>>,
 }
 
 impl Scanner {
 	pub fn new(source: Vec<u8>, error_handler: Rc<RefCell<ErrorHandler>>) -> Scanner {
-		Scanner { source, tokens: Vec::new(), start: 0, current: 0, line: 1, error_handler }
+		Scanner {
+			source,
+			tokens: Vec::new(),
+			start: 0,
+			current: 0,
+			line: 1,
+			line_start: 0,
+			start_line: 1,
+			start_column: 1,
+			trivia: false,
+			restarts: Vec::new(),
+			stopped_early: false,
+			source_id: NEXT_SOURCE_ID.fetch_add(1, Ordering::Relaxed),
+			error_handler,
+		}
+	}
+
+	/// A scanner that also emits Comment and Whitespace tokens, for tools like formatters that
+	/// need to see the whole source. The parser skips these so the tokens can still be run
+	pub fn with_trivia(source: Vec<u8>, error_handler: Rc<RefCell<ErrorHandler>>) -> Scanner {
+		Scanner { trivia: true, ..Self::new(source, error_handler) }
 	}
 
 	// Scan all tokens in the source
 	pub fn scan_tokens(&mut self) {
-		while !self.is_at_end() && self.error_handler.borrow().had_error == false {
+		// Tokens are cut out of the source by byte, which is only safe once we know every
+		// multi-byte character in it is whole
+		if let Err(e) = std::str::from_utf8(&self.source) {
+			let valid = &self.source[..e.valid_up_to()];
+			let line = self.line + valid.iter().filter(|&&c| c == b'\n').count() as u32;
+			error!(self, Error::SyntaxError(line, "The source isn't valid UTF-8.".to_string()));
+			self.current = self.source.len() as u32;
+		}
+		while !self.is_at_end() && !self.error_handler.borrow().had_error {
 			self.start = self.current;
+			if self.start == self.line_start {
+				self.restarts.push((self.start, self.line));
+			}
+			self.start_line = self.line;
+			self.start_column = self.start - self.line_start + 1;
 			self.scan_token();
 		}
+		self.stopped_early = self.error_handler.borrow().had_error;
+		self.push_eof();
+	}
+
+	/// The source as it is after every edit so far
+	pub fn source(&self) -> &[u8] {
+		&self.source
+	}
+
+	/// The id stamped on every token this scanner makes
+	pub fn source_id(&self) -> SourceId {
+		self.source_id
+	}
+
+	/// Apply an edit to the source and bring the tokens up to date, for editors that scan on
+	/// every keystroke. Only the lines from just before the edit are scanned again, up to the
+	/// first line start after it where scanning is where it was before, and the tokens after
+	/// that are moved along. The tokens always come out the same as scanning the edited source
+	/// from scratch would give. The error handler is reset first, as it would be for a new scan.
+	///
+	/// Returns the range of tokens that were scanned again, the end of file token is always
+	/// remade as well. Panics if the edit goes past the end of the source
+	pub fn rescan(&mut self, edit: &Edit) -> Range<usize> {
+		let start = edit.start as usize;
+		let end = start + edit.old_len as usize;
+		assert!(end <= self.source.len(), "the edit goes past the end of the source");
+		let on_boundary = |i: usize| self.source.get(i).map_or(true, |&c| c & 0xC0 != 0x80);
+		let whole_characters = on_boundary(start) && on_boundary(end);
+		self.source.splice(start..end, edit.new_text.bytes());
+		self.error_handler.borrow_mut().reset();
+		// After an error there is no knowing what the rest of the source scans as, and
+		// splitting a character leaves the source invalid
+		if self.stopped_early || !whole_characters {
+			self.tokens.clear();
+			self.restarts.clear();
+			self.current = 0;
+			self.line = 1;
+			self.line_start = 0;
+			self.scan_tokens();
+			return 0..self.tokens.len() - 1;
+		}
 
-		self.tokens.push(Token::new(
+		// Start from the last line start before the edit. Anything earlier can't be changed by
+		// it, even a token that ends right where the edit starts could run on into it
+		let kept = self.restarts.partition_point(|&(offset, _)| offset < edit.start);
+		let (restart, line) = match kept {
+			0 => (0, 1),
+			_ => self.restarts[kept - 1],
+		};
+		let old_restarts = self.restarts.split_off(kept.saturating_sub(1));
+		self.tokens.pop();
+		let first = self.tokens.partition_point(|token| token.offset < restart);
+		let old_tokens = self.tokens.split_off(first);
+		self.current = restart;
+		self.line = line;
+		self.line_start = restart;
+
+		// Once scanning reaches a line start after the edit that was a restart before, the rest
+		// scans the same as it did, only further along
+		let new_end = edit.start + edit.new_text.len() as u32;
+		let moved = |offset: u32| offset + edit.old_len - edit.new_text.len() as u32;
+		let mut resync = None;
+		while !self.is_at_end() && !self.error_handler.borrow().had_error {
+			self.start = self.current;
+			if self.start == self.line_start {
+				if self.start >= new_end {
+					let old = moved(self.start);
+					if let Ok(i) = old_restarts.binary_search_by_key(&old, |&(offset, _)| offset) {
+						resync = Some((old, old_restarts[i].1, i));
+						break;
+					}
+				}
+				self.restarts.push((self.start, self.line));
+			}
+			self.start_line = self.line;
+			self.start_column = self.start - self.line_start + 1;
+			self.scan_token();
+		}
+		let scanned = first..self.tokens.len();
+
+		self.stopped_early = self.error_handler.borrow().had_error;
+		if let Some((old, old_line, i)) = resync {
+			let shift_offset = |offset: u32| offset + self.start - old;
+			let shift_line = |line: u32| line + self.line - old_line;
+			let rest = old_tokens.partition_point(|token| token.offset < old);
+			self.tokens.extend(old_tokens.into_iter().skip(rest).map(|token| Token {
+				offset: shift_offset(token.offset),
+				line: shift_line(token.line),
+				..token
+			}));
+			self.restarts.extend(
+				old_restarts[i..]
+					.iter()
+					.map(|&(offset, line)| (shift_offset(offset), shift_line(line))),
+			);
+			self.current = self.source.len() as u32;
+		}
+		self.push_eof();
+		scanned
+	}
+
+	// The end of the file is reported just after the last real token, rather than on a line of
+	// trailing whitespace
+	fn push_eof(&mut self) {
+		let (line, column) = self
+			.tokens
+			.iter()
+			.rev()
+			.find(|token| !matches!(token.token_type, TokenType::Comment | TokenType::Whitespace))
+			.map_or((1, 1), |token| (token.line, token.column + token.length));
+		self.push(Token::new(
 			TokenType::Eof,
 			String::default(),
 			LiteralType::Null,
-			self.line,
+			line,
+			column,
+			self.current,
 		))
 	}
 
+	fn push(&mut self, token: Token) {
+		self.tokens.push(Token { source: self.source_id, ..token });
+	}
+
+	// Move on to the next line, called after consuming a newline
+	fn new_line(&mut self) {
+		self.line += 1;
+		self.line_start = self.current;
+	}
+
 	// Check if we are at the end of the source
 	fn is_at_end(&self) -> bool {
 		self.current >= self.source.len() as u32
@@ -56,6 +242,7 @@ impl Scanner {
 			b',' => self.add_token(TokenType::Comma, None),
 			b'.' => self.add_token(TokenType::Dot, None),
 			b';' => self.add_token(TokenType::Semicolon, None),
+			b':' => self.add_token(TokenType::Colon, None),
 			b'%' => self.add_token(TokenType::Modulo, None),
 
 			// One or Two Character Tokens
@@ -89,6 +276,12 @@ impl Scanner {
 					while self.peek() != b'\n' && !self.is_at_end() {
 						self.advance();
 					}
+					if self.trivia {
+						let text = self.range_to_string(self.start + 2, self.current);
+						self.add_token(TokenType::Comment, Some(LiteralType::String(text)));
+					}
+				} else if self.match_char(b'*') {
+					self.block_comment();
 				} else if self.match_char(b'=') {
 					self.add_token(TokenType::SlashEqual, None)
 				} else {
@@ -117,24 +310,51 @@ impl Scanner {
 				} else {
 					self.add_token(TokenType::Star, None)
 				},
+			// && and || are the same as the and and or keywords
+			b'&' =>
+				if self.match_char(b'&') {
+					self.add_token(TokenType::And, None)
+				} else {
+					error!(
+						self,
+						Error::SyntaxError(self.line, "Expected '&&' or 'and'.".to_string())
+					)
+				},
+			b'|' =>
+				if self.match_char(b'|') {
+					self.add_token(TokenType::Or, None)
+				} else {
+					error!(
+						self,
+						Error::SyntaxError(self.line, "Expected '||' or 'or'.".to_string())
+					)
+				},
 
 			// Ignore whitespace
+			b' ' | b'\r' | b'\t' | b'\n' if self.trivia => self.whitespace(c),
 			b' ' => (),
 			b'\r' => (),
 			b'\t' => (),
-			b'\n' => self.line += 1,
+			b'\n' => self.new_line(),
 
 			// Handle strings
 			b'"' => self.string(),
 
-			// // Handle Arrays
-			// b'[' => self.array(),
-
 			// Numbers
 			f if self.is_digit(f) => self.number(),
 			f if self.is_alpha(f) => self.identifier(),
 
-			_ => error!(self, Error::SyntaxError(self.line, "Unexpected character.".to_string())),
+			_ => {
+				// Take the rest of a multi-byte character so the error can show all of it
+				while self.peek() & 0xC0 == 0x80 {
+					self.advance();
+				}
+				let character = self.range_to_string(self.start, self.current);
+				error!(
+					self,
+					Error::SyntaxError(self.line, format!("Unexpected character '{}'.", character))
+				)
+			},
 		}
 	}
 
@@ -158,7 +378,7 @@ impl Scanner {
 		if self.current + 1 >= self.source.len() as u32 {
 			return b'\0';
 		}
-		return self.source[(self.current + 1) as usize];
+		self.source[(self.current + 1) as usize]
 	}
 
 	// Add a token to the list of tokens
@@ -168,7 +388,14 @@ impl Scanner {
 			Some(l) => l,
 			None => LiteralType::Null,
 		};
-		self.tokens.push(Token::new(token_type, text, literal, self.line));
+		self.push(Token::new(
+			token_type,
+			text,
+			literal,
+			self.start_line,
+			self.start_column,
+			self.start,
+		));
 	}
 
 	// Is the character a digit?
@@ -201,14 +428,100 @@ impl Scanner {
 		true
 	}
 
+	// Group a run of whitespace into one token, it keeps the line it started on
+	fn whitespace(&mut self, first: u8) {
+		if first == b'\n' {
+			self.new_line();
+		}
+		while matches!(self.peek(), b' ' | b'\r' | b'\t' | b'\n') {
+			if self.advance() == b'\n' {
+				self.new_line();
+			}
+		}
+		let text = self.range_to_string(self.start, self.current);
+		self.push(Token::new(
+			TokenType::Whitespace,
+			text,
+			LiteralType::Null,
+			self.start_line,
+			self.start_column,
+			self.start,
+		));
+	}
+
+	// A block comment goes until the */ that closes it. They nest, so commenting out code that
+	// already has a block comment in it works
+	fn block_comment(&mut self) {
+		let mut depth = 1;
+		while depth > 0 {
+			if self.is_at_end() {
+				error!(
+					self,
+					Error::SyntaxError(self.start_line, "Unterminated block comment.".to_string())
+				);
+				return;
+			}
+			match self.advance() {
+				b'/' if self.match_char(b'*') => depth += 1,
+				b'*' if self.match_char(b'/') => depth -= 1,
+				b'\n' => self.new_line(),
+				_ => (),
+			}
+		}
+		if self.trivia {
+			let text = self.range_to_string(self.start + 2, self.current - 2);
+			let lexeme = self.range_to_string(self.start, self.current);
+			self.push(Token::new(
+				TokenType::Comment,
+				lexeme,
+				LiteralType::String(text),
+				self.start_line,
+				self.start_column,
+				self.start,
+			));
+		}
+	}
+
 	// Handle strings
 	fn string(&mut self) {
-		// Run until eof or closing character
+		// Run until eof or closing character, replacing escape sequences as we go
+		let mut value = Vec::new();
 		while self.peek() != b'"' && !self.is_at_end() {
-			if self.peek() == b'\n' {
-				self.line += 1;
+			let c = self.advance();
+			match c {
+				b'\n' => {
+					self.new_line();
+					value.push(c);
+				},
+				b'\\' if !self.is_at_end() => {
+					let escaped = match self.peek() {
+						b'n' => b'\n',
+						b't' => b'\t',
+						b'r' => b'\r',
+						b'0' => b'\0',
+						c @ (b'\\' | b'"') => c,
+						_ => {
+							// Leave the character to be read as part of the string, it may be
+							// more than one byte
+							let end = (self.current as usize + 4).min(self.source.len());
+							let rest = &self.source[self.current as usize..end];
+							let escape =
+								String::from_utf8_lossy(rest).chars().next().unwrap_or('?');
+							error!(
+								self,
+								Error::SyntaxError(
+									self.line,
+									format!("Unknown escape sequence '\\{}' in string.", escape)
+								)
+							);
+							continue;
+						},
+					};
+					self.advance();
+					value.push(escaped);
+				},
+				_ => value.push(c),
 			}
-			self.advance();
 		}
 
 		if self.is_at_end() {
@@ -219,31 +532,66 @@ impl Scanner {
 		// To advance past the closing "
 		self.advance();
 
-		// Trim surrounding quotes
-		let value: String = self.range_to_string(self.start + 1, self.current - 1);
+		// Safe to unwrap as only whole ascii characters are escaped in utf-8
+		let value = String::from_utf8(value).unwrap();
 		self.add_token(TokenType::String, Some(LiteralType::String(value)));
 	}
 
 	// Handle floating point numbers and decimals. Does not support decimal at the start or end
 	fn number(&mut self) {
-		while self.is_digit(self.peek()) {
-			self.advance();
+		if !self.digits() {
+			return;
 		}
 
 		// Check if we have a decimal, then consume the trailing digits
 		if self.peek() == b'.' && self.is_digit(self.peek_next()) {
 			// Consume the decimal
 			self.advance();
+			if !self.digits() {
+				return;
+			}
+		}
 
-			while self.is_digit(self.peek()) {
+		// An exponent like e9, e+9 or e-9
+		if matches!(self.peek(), b'e' | b'E') {
+			self.advance();
+			if matches!(self.peek(), b'+' | b'-') {
 				self.advance();
 			}
+			if !self.is_digit(self.peek()) {
+				error!(
+					self,
+					Error::SyntaxError(self.line, "Expected digits in the exponent.".to_string())
+				);
+				return;
+			}
+			if !self.digits() {
+				return;
+			}
 		}
-		let literal_str = self.range_to_string(self.start, self.current);
+		let literal_str = self.range_to_string(self.start, self.current).replace('_', "");
 		let literal: LiteralType = LiteralType::Number(literal_str.parse::<f64>().unwrap());
 		self.add_token(TokenType::Number, Some(literal));
 	}
 
+	// Consume a run of digits, which can be split up with underscores like 1_000_000. Returns
+	// false after reporting an underscore that isn't between two digits
+	fn digits(&mut self) -> bool {
+		while self.is_digit(self.peek()) || self.peek() == b'_' {
+			if self.advance() == b'_' && !self.is_digit(self.peek()) {
+				error!(
+					self,
+					Error::SyntaxError(
+						self.line,
+						"An underscore in a number has to be between two digits.".to_string()
+					)
+				);
+				return false;
+			}
+		}
+		true
+	}
+
 	// Handle identifiers
 	fn identifier(&mut self) {
 		while self.is_alpha_numeric(self.peek()) {
@@ -258,7 +606,9 @@ impl Scanner {
 	fn get_identifier_type(&self, text: &str) -> TokenType {
 		match text {
 			"and" => TokenType::And,
+			"break" => TokenType::Break,
 			"class" => TokenType::Class,
+			"continue" => TokenType::Continue,
 			"else" => TokenType::Else,
 			"false" => TokenType::False,
 			"nah" => TokenType::False,
@@ -266,10 +616,13 @@ impl Scanner {
 			"yeah" => TokenType::True,
 			"funk" => TokenType::Funk,
 			"for" => TokenType::For,
+			"global" => TokenType::Global,
 			"if" => TokenType::If,
+			"import" => TokenType::Import,
 			"null" => TokenType::Null,
 			"or" => TokenType::Or,
 			"print" => TokenType::Print,
+			"pure" => TokenType::Pure,
 			"return" => TokenType::Return,
 			"super" => TokenType::Super,
 			"this" => TokenType::This,
@@ -281,7 +634,8 @@ impl Scanner {
 
 	// Convert a start and end range into a string from the source byte array
 	fn range_to_string(&mut self, start: u32, end: u32) -> String {
-		// Safe to unwrap as we know it is utf-8
+		// Safe to unwrap as scan_tokens checked the source is UTF-8, and tokens start and end on
+		// whole characters
 		String::from_utf8(self.source[start as usize..end as usize].to_vec()).unwrap()
 	}
 }
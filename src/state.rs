@@ -0,0 +1,251 @@
+use crate::{callable::Callable, interpreter::Interpreter, token::LiteralType};
+use std::{collections::BTreeMap, fs, path::Path, rc::Rc};
+
+/// Bumped whenever the layout of a state file changes
+pub const STATE_VERSION: u32 = 1;
+const STATE_HEADER: &str = "JASN-STATE";
+// How deeply arrays and maps can nest in a state file. Saving and loading recurse once per
+// level, so anything deeper is skipped when saving and refused when loading
+const MAX_DEPTH: usize = 512;
+
+/// A state file holds the global data values of an interpreter so they can be restored by a
+/// later run. The layout is a header line followed by one `name value` line per global where
+/// values are encoded as:
+/// - `null`, `true`, `false`
+/// - `n:<f64 bits as hex>` so numbers round trip exactly
+/// - `s:<byte length>:<bytes>` so strings need no escaping
+/// - `a:<element count>:` followed by the elements separated by `,`
+/// - `m:<entry count>:` followed by the entries separated by `,`, each one a key encoded like a
+///   string and then its value
+pub struct State {}
+
+impl State {
+	/// Write the globals to the state file, returning the names of any values that had to be
+	/// skipped. Native and prelude functions are skipped silently as every interpreter defines
+	/// them
+	pub fn save(interpreter: &Interpreter, path: &Path) -> Result<Vec<String>, String> {
+		let mut output = format!("{} {}\n", STATE_HEADER, STATE_VERSION);
+		let mut skipped = Vec::new();
+		for (name, value) in interpreter.global.borrow().entries() {
+			match Self::encode(&value) {
+				Some(encoded) => output.push_str(&format!("{} {}\n", name, encoded)),
+				None => match value {
+					LiteralType::Callable(Callable::NativeFunction(_)) => (),
+					LiteralType::Callable(Callable::Function(_))
+						if interpreter.is_prelude(&name) =>
+						(),
+					_ => skipped.push(name),
+				},
+			}
+		}
+		fs::write(path, output)
+			.map_err(|e| format!("Unable to write state file '{}': {}", path.display(), e))?;
+		Ok(skipped)
+	}
+
+	/// Define every global stored in the state file on the interpreter
+	pub fn load(interpreter: &mut Interpreter, path: &Path) -> Result<(), String> {
+		let contents = fs::read(path)
+			.map_err(|e| format!("Unable to read state file '{}': {}", path.display(), e))?;
+		let values = Self::decode(&contents)
+			.map_err(|e| format!("Invalid state file '{}': {}", path.display(), e))?;
+		for (name, value) in values {
+			interpreter.global.borrow_mut().define(name, value);
+		}
+		Ok(())
+	}
+
+	/// Encode a value, returns None for values that can't be stored such as callables, arrays
+	/// or maps that contain themselves and ones nested deeper than MAX_DEPTH
+	fn encode(value: &LiteralType) -> Option<String> {
+		Self::encode_nested(value, &mut Vec::new())
+	}
+
+	// Parents are the addresses of the arrays and maps value is inside of
+	fn encode_nested(value: &LiteralType, parents: &mut Vec<*const ()>) -> Option<String> {
+		match value {
+			LiteralType::Null => Some("null".to_string()),
+			LiteralType::Bool(b) => Some(b.to_string()),
+			LiteralType::Number(n) => Some(format!("n:{:016x}", n.to_bits())),
+			LiteralType::String(s) => Some(format!("s:{}:{}", s.len(), s)),
+			LiteralType::Array(array) => {
+				let address = Rc::as_ptr(array) as *const ();
+				if parents.contains(&address) || parents.len() >= MAX_DEPTH {
+					return None;
+				}
+				parents.push(address);
+				let values = array.borrow();
+				let elements: Option<Vec<String>> =
+					values.iter().map(|value| Self::encode_nested(value, parents)).collect();
+				parents.pop();
+				Some(format!("a:{}:{}", values.len(), elements?.join(",")))
+			},
+			LiteralType::Map(map) => {
+				let address = Rc::as_ptr(map) as *const ();
+				if parents.contains(&address) || parents.len() >= MAX_DEPTH {
+					return None;
+				}
+				parents.push(address);
+				let map = map.borrow();
+				let entries: Option<Vec<String>> = map
+					.iter()
+					.map(|(key, value)| {
+						Some(format!(
+							"{}:{}{}",
+							key.len(),
+							key,
+							Self::encode_nested(value, parents)?
+						))
+					})
+					.collect();
+				parents.pop();
+				Some(format!("m:{}:{}", map.len(), entries?.join(",")))
+			},
+			LiteralType::Callable(_) => None,
+		}
+	}
+
+	/// Decode the full contents of a state file into (name, value) pairs
+	fn decode(contents: &[u8]) -> Result<Vec<(String, LiteralType)>, String> {
+		let mut decoder = Decoder { source: contents, current: 0, depth: 0 };
+		let header = decoder.take_until(b'\n')?;
+		let version = header
+			.strip_prefix(STATE_HEADER)
+			.and_then(|version| version.trim().parse::<u32>().ok())
+			.ok_or("missing state file header")?;
+		if version != STATE_VERSION {
+			return Err(format!(
+				"version {} is not supported, expected version {}",
+				version, STATE_VERSION
+			));
+		}
+
+		let mut values = Vec::new();
+		while !decoder.is_at_end() {
+			let name = decoder.take_until(b' ')?;
+			let value = decoder.value()?;
+			decoder.expect(b'\n')?;
+			values.push((name, value));
+		}
+		Ok(values)
+	}
+}
+
+// Walks through the bytes of a state file
+struct Decoder<'a> {
+	source: &'a [u8],
+	current: usize,
+	// How many arrays and maps the value being read is inside of
+	depth: usize,
+}
+
+impl Decoder<'_> {
+	fn is_at_end(&self) -> bool {
+		self.current >= self.source.len()
+	}
+
+	// Return the text up to the delimiter and move past the delimiter
+	fn take_until(&mut self, delimiter: u8) -> Result<String, String> {
+		let length = self.source[self.current..]
+			.iter()
+			.position(|c| *c == delimiter)
+			.ok_or("unexpected end of file")?;
+		let text = self.take(length)?;
+		self.current += 1;
+		Ok(text)
+	}
+
+	// Return the next length bytes as a string
+	fn take(&mut self, length: usize) -> Result<String, String> {
+		let end = match self.current.checked_add(length) {
+			Some(end) if end <= self.source.len() => end,
+			_ => return Err("unexpected end of file".to_string()),
+		};
+		let text = String::from_utf8(self.source[self.current..end].to_vec())
+			.map_err(|_| "invalid utf-8".to_string())?;
+		self.current = end;
+		Ok(text)
+	}
+
+	fn expect(&mut self, expected: u8) -> Result<(), String> {
+		if self.source.get(self.current) != Some(&expected) {
+			return Err(format!("expected '{}' at byte {}", expected as char, self.current));
+		}
+		self.current += 1;
+		Ok(())
+	}
+
+	// Read a length or count followed by ':'
+	fn count(&mut self) -> Result<usize, String> {
+		let count = self.take_until(b':')?;
+		count.parse().map_err(|_| format!("invalid length '{}'", count))
+	}
+
+	fn value(&mut self) -> Result<LiteralType, String> {
+		if self.depth > MAX_DEPTH {
+			return Err(format!(
+				"values nested more than {} deep at byte {}",
+				MAX_DEPTH, self.current
+			));
+		}
+		self.depth += 1;
+		let value = self.value_inside();
+		self.depth -= 1;
+		value
+	}
+
+	// Read a value once value has checked how deeply it is nested
+	fn value_inside(&mut self) -> Result<LiteralType, String> {
+		for (keyword, value) in [
+			("null", LiteralType::Null),
+			("true", LiteralType::Bool(true)),
+			("false", LiteralType::Bool(false)),
+		] {
+			if self.source[self.current..].starts_with(keyword.as_bytes()) {
+				self.current += keyword.len();
+				return Ok(value);
+			}
+		}
+
+		let tag = self.take(2)?;
+		match tag.as_str() {
+			"n:" => {
+				let bits = self.take(16)?;
+				let bits = u64::from_str_radix(&bits, 16)
+					.map_err(|_| format!("invalid number '{}'", bits))?;
+				Ok(LiteralType::Number(f64::from_bits(bits)))
+			},
+			"s:" => {
+				let length = self.count()?;
+				Ok(LiteralType::String(self.take(length)?))
+			},
+			"a:" => {
+				let count = self.count()?;
+				// Every element takes at least a byte, so a count the rest of the file can't
+				// hold is found out before it can ask for too much memory
+				let mut values = Vec::with_capacity(count.min(self.source.len() - self.current));
+				for i in 0..count {
+					if i > 0 {
+						self.expect(b',')?;
+					}
+					values.push(self.value()?);
+				}
+				Ok(LiteralType::array(values))
+			},
+			"m:" => {
+				let count = self.count()?;
+				let mut entries = BTreeMap::new();
+				for i in 0..count {
+					if i > 0 {
+						self.expect(b',')?;
+					}
+					let length = self.count()?;
+					let key = self.take(length)?;
+					entries.insert(key, self.value()?);
+				}
+				Ok(LiteralType::map(entries))
+			},
+			_ => Err(format!("unknown value type '{}'", tag)),
+		}
+	}
+}
@@ -1,11 +1,16 @@
-use crate::{error_handler::Error, expressions::Expr, token::Token};
+use crate::{error_handler::Error, expressions, expressions::Expr, token::Token};
 
 pub trait Visitor {
 	fn visit_block(&mut self, block: &Block) -> Result<(), Error>;
+	fn visit_break(&mut self, break_stmt: &Break) -> Result<(), Error>;
 	fn visit_class(&mut self, class: &Class) -> Result<(), Error>;
+	fn visit_continue(&mut self, continue_stmt: &Continue) -> Result<(), Error>;
 	fn visit_expression(&mut self, expression: &Expression) -> Result<(), Error>;
 	fn visit_function(&mut self, function: &Function) -> Result<(), Error>;
+	fn visit_global(&mut self, global: &Global) -> Result<(), Error>;
 	fn visit_if(&mut self, if_stmt: &If) -> Result<(), Error>;
+	fn visit_import(&mut self, import: &Import) -> Result<(), Error>;
+	fn visit_multi_assign(&mut self, multi_assign: &MultiAssign) -> Result<(), Error>;
 	fn visit_print(&mut self, print: &Print) -> Result<(), Error>;
 	fn visit_return(&mut self, return_stmt: &Return) -> Result<(), Error>;
 	fn visit_variable(&mut self, variable: &Variable) -> Result<(), Error>;
@@ -15,10 +20,15 @@ pub trait Visitor {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
 	Block(Box<Block>),
+	Break(Break),
 	Class(Class),
+	Continue(Continue),
 	Expression(Expression),
 	Function(Function),
+	Global(Global),
 	If(Box<If>),
+	Import(Import),
+	MultiAssign(MultiAssign),
 	Print(Print),
 	Return(Return),
 	Variable(Variable),
@@ -29,16 +39,94 @@ impl Stmt {
 	pub fn accept<V: Visitor>(&self, visitor: &mut V) -> Result<(), Error> {
 		match self {
 			Stmt::Block(block) => visitor.visit_block(block),
+			Stmt::Break(break_stmt) => visitor.visit_break(break_stmt),
 			Stmt::Class(class) => visitor.visit_class(class),
+			Stmt::Continue(continue_stmt) => visitor.visit_continue(continue_stmt),
 			Stmt::Expression(expression) => visitor.visit_expression(expression),
 			Stmt::Function(function) => visitor.visit_function(function),
+			Stmt::Global(global) => visitor.visit_global(global),
 			Stmt::If(if_stmt) => visitor.visit_if(if_stmt),
+			Stmt::Import(import) => visitor.visit_import(import),
+			Stmt::MultiAssign(multi_assign) => visitor.visit_multi_assign(multi_assign),
 			Stmt::Print(print) => visitor.visit_print(print),
 			Stmt::Return(return_stmt) => visitor.visit_return(return_stmt),
 			Stmt::Variable(variable) => visitor.visit_variable(variable),
 			Stmt::While(while_stmt) => visitor.visit_while(while_stmt),
 		}
 	}
+
+	/// Call f on this statement and then on every statement nested inside it, including the
+	/// bodies of functions and methods
+	pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Stmt)) {
+		f(self);
+		match self {
+			Stmt::Block(block) => block.statements.iter().for_each(|stmt| stmt.walk(f)),
+			Stmt::Class(class) => class
+				.methods
+				.iter()
+				.for_each(|method| method.body.iter().for_each(|stmt| stmt.walk(f))),
+			Stmt::Function(function) => function.body.iter().for_each(|stmt| stmt.walk(f)),
+			Stmt::If(if_stmt) => {
+				if_stmt.then_branch.walk(f);
+				if let Some(else_branch) = &if_stmt.else_branch {
+					else_branch.walk(f);
+				}
+			},
+			Stmt::While(while_stmt) => while_stmt.body.walk(f),
+			Stmt::Break(_) |
+			Stmt::Continue(_) |
+			Stmt::Expression(_) |
+			Stmt::Global(_) |
+			Stmt::Import(_) |
+			Stmt::MultiAssign(_) |
+			Stmt::Print(_) |
+			Stmt::Return(_) |
+			Stmt::Variable(_) => (),
+		}
+	}
+
+	/// The line this statement starts on, as near as its tokens can tell. None for blocks, which
+	/// only hold other statements
+	pub fn line(&self) -> Option<u32> {
+		match self {
+			Stmt::Block(_) => None,
+			Stmt::Break(break_stmt) => Some(break_stmt.keyword.line),
+			Stmt::Class(class) => Some(class.name.line),
+			Stmt::Continue(continue_stmt) => Some(continue_stmt.keyword.line),
+			Stmt::Expression(expression) => expression.expression.token().map(|token| token.line),
+			Stmt::Function(function) => Some(function.name.line),
+			Stmt::Global(global) => Some(global.keyword.line),
+			Stmt::If(if_stmt) => if_stmt.condition.token().map(|token| token.line),
+			Stmt::Import(import) => Some(import.keyword.line),
+			Stmt::MultiAssign(multi_assign) => Some(multi_assign.equals.line),
+			Stmt::Print(print) => Some(print.keyword.line),
+			Stmt::Return(return_stmt) => Some(return_stmt.keyword.line),
+			Stmt::Variable(variable) => Some(variable.name.line),
+			Stmt::While(while_stmt) => Some(while_stmt.keyword.line),
+		}
+	}
+
+	/// The expressions that belong directly to this statement, not to statements inside it
+	pub fn expressions(&self) -> Vec<&Expr> {
+		match self {
+			Stmt::Expression(expression) => vec![&expression.expression],
+			Stmt::Global(global) => vec![&global.value],
+			Stmt::If(if_stmt) => vec![&if_stmt.condition],
+			Stmt::MultiAssign(multi_assign) =>
+				multi_assign.targets.iter().chain(&multi_assign.values).collect(),
+			Stmt::Print(print) => vec![&print.expression],
+			Stmt::Return(return_stmt) => return_stmt.value.iter().collect(),
+			Stmt::Variable(variable) => variable.initializer.iter().collect(),
+			Stmt::While(while_stmt) =>
+				std::iter::once(&while_stmt.condition).chain(&while_stmt.increment).collect(),
+			Stmt::Block(_) |
+			Stmt::Break(_) |
+			Stmt::Class(_) |
+			Stmt::Continue(_) |
+			Stmt::Function(_) |
+			Stmt::Import(_) => vec![],
+		}
+	}
 }
 
 // Block statement
@@ -47,14 +135,26 @@ pub struct Block {
 	pub statements: Vec<Stmt>,
 }
 
+// Break statement
+#[derive(Debug, PartialEq, Clone)]
+pub struct Break {
+	pub keyword: Token,
+}
+
 // Class statement
 #[derive(Debug, PartialEq, Clone)]
 pub struct Class {
 	pub name: Token,
-	// pub superclass: Option<Variable>,
+	pub superclass: Option<expressions::Variable>,
 	pub methods: Vec<Function>,
 }
 
+// Continue statement
+#[derive(Debug, PartialEq, Clone)]
+pub struct Continue {
+	pub keyword: Token,
+}
+
 // Expression statement
 #[derive(Debug, PartialEq, Clone)]
 pub struct Expression {
@@ -67,6 +167,18 @@ pub struct Function {
 	pub name: Token,
 	pub params: Vec<Token>,
 	pub body: Vec<Stmt>,
+	// Declared with `pure funk`, the resolver checks the body can't change or observe anything
+	// outside of it
+	pub pure: bool,
+}
+
+// Global statement, `global x = value` sets a global from anywhere, creating it if it doesn't
+// exist yet
+#[derive(Debug, PartialEq, Clone)]
+pub struct Global {
+	pub keyword: Token,
+	pub name: Token,
+	pub value: Expr,
 }
 
 // If statement
@@ -77,9 +189,25 @@ pub struct If {
 	pub else_branch: Option<Stmt>,
 }
 
+// Import statement, path is the string token naming the file
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+	pub keyword: Token,
+	pub path: Token,
+}
+
+// Assignment to several targets at once, `a, b = b, a`. A single value is unpacked from an array
+#[derive(Debug, PartialEq, Clone)]
+pub struct MultiAssign {
+	pub targets: Vec<Expr>,
+	pub equals: Token,
+	pub values: Vec<Expr>,
+}
+
 // Print statement
 #[derive(Debug, PartialEq, Clone)]
 pub struct Print {
+	pub keyword: Token,
 	pub expression: Expr,
 }
 
@@ -100,6 +228,11 @@ pub struct Variable {
 // While statement
 #[derive(Debug, PartialEq, Clone)]
 pub struct While {
+	// The 'while' or 'for' keyword the loop was written with
+	pub keyword: Token,
 	pub condition: Expr,
 	pub body: Stmt,
+	// Run after every pass through the body, even one cut short by continue. Only for loops
+	// have one
+	pub increment: Option<Expr>,
 }
@@ -1,5 +1,11 @@
 use crate::callable::Callable;
 use core::hash::Hash;
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	fmt,
+	rc::Rc,
+};
 
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct Token {
@@ -7,24 +13,36 @@ pub struct Token {
 	pub lexeme: String,
 	pub literal: LiteralType,
 	pub line: u32,
+	// How far into its line the lexeme starts in bytes, counting from 1. Tokens that weren't
+	// scanned from source have column 0
+	pub column: u32,
+	// Where the lexeme starts in the source, in bytes. This also keeps two identical lexemes on
+	// the same line from being equal, which matters for anything keyed on an Expr
+	pub offset: u32,
+	// The length of the lexeme in bytes
+	pub length: u32,
+	// The scan the token came from, 0 for tokens that weren't scanned. The rest of the token
+	// only says where it is inside its own source
+	pub source: SourceId,
 }
 
-impl Token {
-	pub fn get_line(&self) -> u32 {
-		if self.token_type == TokenType::Eof {
-			self.line.saturating_sub(1)
-		} else {
-			self.line
-		}
-	}
-}
+/// Tells apart the sources tokens were scanned from, every scanner gets a new one
+pub type SourceId = u32;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Arrays are shared, so every copy of an array value sees changes made through any of them
+pub type ArrayRef = Rc<RefCell<Vec<LiteralType>>>;
+
+/// Maps are shared like arrays. Keys are kept sorted so maps print and compare the same way
+/// every time
+pub type MapRef = Rc<RefCell<BTreeMap<String, LiteralType>>>;
+
+#[derive(Debug, Clone)]
 pub enum LiteralType {
 	String(String),
 	Number(f64),
 	Bool(bool),
-	Array(Vec<LiteralType>),
+	Array(ArrayRef),
+	Map(MapRef),
 	Callable(Callable),
 	Null,
 }
@@ -33,15 +51,97 @@ impl Hash for LiteralType {
 	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
 		match self {
 			Self::String(s) => s.hash(state),
+			// 0 == -0, so they have to hash the same. NaN is never equal to anything but all NaNs
+			// may as well share a bucket
+			Self::Number(n) if *n == 0.0 => 0f64.to_bits().hash(state),
+			Self::Number(n) if n.is_nan() => f64::NAN.to_bits().hash(state),
 			Self::Number(n) => n.to_bits().hash(state),
 			Self::Bool(b) => b.hash(state),
-			Self::Array(a) => a.hash(state),
+			// Only the length of nested arrays and maps is hashed, they can contain themselves.
+			// Equal values still hash the same as their nested collections have equal lengths
+			Self::Array(a) => {
+				let values = a.borrow();
+				values.len().hash(state);
+				for value in values.iter() {
+					value.hash_shallow(state);
+				}
+			},
+			Self::Map(m) => {
+				let entries = m.borrow();
+				entries.len().hash(state);
+				for (key, value) in entries.iter() {
+					key.hash(state);
+					value.hash_shallow(state);
+				}
+			},
+			// The display form includes names and parameters, so it spreads different callables
+			// out well enough without having to hash closures
 			Self::Callable(c) => c.to_string().hash(state),
 			Self::Null => "null".hash(state),
 		}
 	}
 }
 
+impl LiteralType {
+	// Hash a value inside an array or map, without going into nested collections
+	fn hash_shallow<H: std::hash::Hasher>(&self, state: &mut H) {
+		match self {
+			Self::Array(nested) => nested.borrow().len().hash(state),
+			Self::Map(nested) => nested.borrow().len().hash(state),
+			value => value.hash(state),
+		}
+	}
+}
+
+// Arrays are equal when their elements are, and maps when they have the same keys with equal
+// values. Collections that contain themselves are compared without going round forever: a pair
+// already being compared further up is taken to be equal, so only a difference somewhere else
+// can make them unequal
+impl PartialEq for LiteralType {
+	fn eq(&self, other: &Self) -> bool {
+		// The arrays or maps being compared, by address
+		type Pair = (*const (), *const ());
+		fn equal(a: &LiteralType, b: &LiteralType, comparing: &mut Vec<Pair>) -> bool {
+			match (a, b) {
+				(LiteralType::Array(a), LiteralType::Array(b)) => {
+					let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+					if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+						return true;
+					}
+					comparing.push(pair);
+					let (a, b) = (a.borrow(), b.borrow());
+					let same = a.len() == b.len() &&
+						a.iter().zip(b.iter()).all(|(a, b)| equal(a, b, comparing));
+					comparing.pop();
+					same
+				},
+				(LiteralType::Map(a), LiteralType::Map(b)) => {
+					let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+					if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+						return true;
+					}
+					comparing.push(pair);
+					let (a, b) = (a.borrow(), b.borrow());
+					// Both iterate in key order, so matching entries line up
+					let same = a.len() == b.len() &&
+						a.iter().zip(b.iter()).all(|((a_key, a), (b_key, b))| {
+							a_key == b_key && equal(a, b, comparing)
+						});
+					comparing.pop();
+					same
+				},
+				(LiteralType::String(a), LiteralType::String(b)) => a == b,
+				(LiteralType::Number(a), LiteralType::Number(b)) => a == b,
+				(LiteralType::Bool(a), LiteralType::Bool(b)) => a == b,
+				(LiteralType::Callable(a), LiteralType::Callable(b)) => a == b,
+				(LiteralType::Null, LiteralType::Null) => true,
+				_ => false,
+			}
+		}
+		equal(self, other, &mut Vec::new())
+	}
+}
+
 impl Eq for LiteralType {}
 
 impl LiteralType {
@@ -54,6 +154,206 @@ impl LiteralType {
 			_ => true,
 		}
 	}
+
+	/// The name of the value's type, used in error messages
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Self::String(_) => "string",
+			Self::Number(_) => "number",
+			Self::Bool(_) => "bool",
+			Self::Array(_) => "array",
+			Self::Map(_) => "map",
+			Self::Callable(Callable::NativeFunction(_)) | Self::Callable(Callable::Function(_)) =>
+				"function",
+			Self::Callable(Callable::Class(_)) => "class",
+			Self::Callable(Callable::Instance(_)) => "instance",
+			Self::Null => "null",
+		}
+	}
+
+	/// Wrap elements up as a new array
+	pub fn array(values: Vec<LiteralType>) -> Self {
+		Self::Array(Rc::new(RefCell::new(values)))
+	}
+
+	/// Wrap entries up as a new map
+	pub fn map(entries: BTreeMap<String, LiteralType>) -> Self {
+		Self::Map(Rc::new(RefCell::new(entries)))
+	}
+
+	/// Copy a value so that none of its arrays or maps are shared with the original. Ones that
+	/// contain themselves are copied into new ones that do the same
+	pub fn deep_copy(&self) -> Self {
+		// Copies made so far, keyed by the address of the original
+		fn copy(value: &LiteralType, copies: &mut HashMap<*const (), LiteralType>) -> LiteralType {
+			match value {
+				LiteralType::Array(array) => {
+					if let Some(copied) = copies.get(&(Rc::as_ptr(array) as *const ())) {
+						return copied.clone();
+					}
+					let copied: ArrayRef = Default::default();
+					copies
+						.insert(Rc::as_ptr(array) as *const (), LiteralType::Array(copied.clone()));
+					let values: Vec<LiteralType> =
+						array.borrow().iter().map(|value| copy(value, copies)).collect();
+					*copied.borrow_mut() = values;
+					LiteralType::Array(copied)
+				},
+				LiteralType::Map(map) => {
+					if let Some(copied) = copies.get(&(Rc::as_ptr(map) as *const ())) {
+						return copied.clone();
+					}
+					let copied: MapRef = Default::default();
+					copies.insert(Rc::as_ptr(map) as *const (), LiteralType::Map(copied.clone()));
+					let entries = map
+						.borrow()
+						.iter()
+						.map(|(key, value)| (key.clone(), copy(value, copies)))
+						.collect();
+					*copied.borrow_mut() = entries;
+					LiteralType::Map(copied)
+				},
+				value => value.clone(),
+			}
+		}
+		copy(self, &mut HashMap::new())
+	}
+}
+
+/// How much of a value gets rendered when it is printed. Arrays and maps longer than
+/// max_elements show their first elements and a count of the rest, ones nested deeper than
+/// max_depth show as …
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderLimits {
+	pub max_elements: Option<usize>,
+	pub max_depth: Option<usize>,
+}
+
+impl Default for RenderLimits {
+	fn default() -> Self {
+		Self { max_elements: Some(100), max_depth: Some(16) }
+	}
+}
+
+impl RenderLimits {
+	/// Render everything, this is what Display uses
+	pub fn unlimited() -> Self {
+		Self { max_elements: None, max_depth: None }
+	}
+}
+
+/// Displays a value within some RenderLimits
+struct Rendered<'a> {
+	value: &'a LiteralType,
+	limits: &'a RenderLimits,
+	depth: usize,
+	// The array or map this one is nested in, used to spot ones that contain themselves
+	parent: Option<&'a Rendered<'a>>,
+}
+
+impl Rendered<'_> {
+	// Whether the array or map at address is this value or one of its parents
+	fn is_inside(&self, address: *const ()) -> bool {
+		let mut current = Some(self);
+		while let Some(rendered) = current {
+			let outer = match rendered.value {
+				LiteralType::Array(outer) => Rc::as_ptr(outer) as *const (),
+				LiteralType::Map(outer) => Rc::as_ptr(outer) as *const (),
+				_ => std::ptr::null(),
+			};
+			if outer == address {
+				return true;
+			}
+			current = rendered.parent;
+		}
+		false
+	}
+
+	fn nested<'a>(&'a self, value: &'a LiteralType) -> Rendered<'a> {
+		Rendered { value, limits: self.limits, depth: self.depth + 1, parent: Some(self) }
+	}
+
+	// Write the elements of an array or entries of a map between brackets, up to the limits
+	fn write_elements<T>(
+		&self,
+		f: &mut fmt::Formatter,
+		brackets: (&str, &str),
+		elements: impl ExactSizeIterator<Item = T>,
+		mut write_element: impl FnMut(&mut fmt::Formatter, T) -> fmt::Result,
+	) -> fmt::Result {
+		let length = elements.len();
+		let shown = self.limits.max_elements.map_or(length, |max| length.min(max));
+		write!(f, "{}", brackets.0)?;
+		for (i, element) in elements.take(shown).enumerate() {
+			if i > 0 {
+				write!(f, ", ")?;
+			}
+			write_element(f, element)?;
+		}
+		if shown < length {
+			if shown > 0 {
+				write!(f, ", ")?;
+			}
+			write!(f, "…, +{} more", length - shown)?;
+		}
+		write!(f, "{}", brackets.1)
+	}
+}
+
+/// Write a number with the fewest digits that still read back as exactly the same number, so
+/// to_number(to_string(x)) is always x. Very large and very small numbers use an exponent
+/// rather than spelling out hundreds of zeros, and -0, inf, -inf and NaN keep their sign and
+/// spelling
+fn write_number(f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+	let magnitude = n.abs();
+	if magnitude.is_finite() && magnitude != 0.0 && !(1e-6..1e21).contains(&magnitude) {
+		write!(f, "{:e}", n)
+	} else {
+		write!(f, "{}", n)
+	}
+}
+
+impl fmt::Display for Rendered<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let address = match self.value {
+			LiteralType::Null => return write!(f, "null"),
+			LiteralType::Number(n) => return write_number(f, *n),
+			LiteralType::Bool(true) => return write!(f, "yeah"),
+			LiteralType::Bool(false) => return write!(f, "nah"),
+			LiteralType::String(s) => return write!(f, "{}", s),
+			LiteralType::Callable(c) => return write!(f, "{}", c),
+			LiteralType::Array(array) => Rc::as_ptr(array) as *const (),
+			LiteralType::Map(map) => Rc::as_ptr(map) as *const (),
+		};
+		if self.limits.max_depth.is_some_and(|max| self.depth >= max) {
+			return write!(f, "…");
+		}
+		let inside = self.parent.is_some_and(|parent| parent.is_inside(address));
+		match self.value {
+			LiteralType::Array(_) if inside => write!(f, "[…]"),
+			LiteralType::Map(_) if inside => write!(f, "{{…}}"),
+			LiteralType::Array(array) => {
+				let values = array.borrow();
+				self.write_elements(f, ("[", "]"), values.iter(), |f, value| {
+					write!(f, "{}", self.nested(value))
+				})
+			},
+			LiteralType::Map(map) => {
+				let entries = map.borrow();
+				self.write_elements(f, ("{", "}"), entries.iter(), |f, (key, value)| {
+					write!(f, "{}: {}", key, self.nested(value))
+				})
+			},
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl LiteralType {
+	/// Render the value for printing without building more than the limits allow
+	pub fn render(&self, limits: &RenderLimits) -> String {
+		Rendered { value: self, limits, depth: 0, parent: None }.to_string()
+	}
 }
 
 impl TryInto<f64> for LiteralType {
@@ -78,39 +378,70 @@ impl TryInto<bool> for LiteralType {
 	}
 }
 
-impl ToString for LiteralType {
-	fn to_string(&self) -> String {
-		match self {
-			Self::Null => "null".to_string(),
-			Self::Number(n) => n.to_string(),
-			Self::Bool(b) =>
-				if *b {
-					"yeah".to_string()
-				} else {
-					"nah".to_string()
-				},
-			Self::String(s) => s.clone(),
-			Self::Callable(c) => c.to_string(),
-			// Self::Array(_) => "array".to_string(),
-			Self::Array(val) => {
-				let mut array = String::from("[");
-				for (i, v) in val.iter().enumerate() {
-					let s: String = (*v).clone().to_string();
-					array.push_str(&s);
-					if i != val.len() - 1 {
-						array.push_str(", ");
-					}
-				}
-				array.push_str("]");
-				array
-			},
-		}
+impl fmt::Display for LiteralType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			Rendered { value: self, limits: &RenderLimits::unlimited(), depth: 0, parent: None }
+		)
 	}
 }
 
+// Shown as `Type 'lexeme' (line N)`, used by the --tokens dump
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:?} '{}' (line {})", self.token_type, self.lexeme, self.line)
+	}
+}
+
+/// Where a token is in its source, without the token itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+	pub line: u32,
+	// Counted in bytes from 1, 0 for tokens that weren't scanned from source
+	pub column: u32,
+	// Bytes from the start of the source
+	pub offset: u32,
+	pub length: u32,
+}
+
 impl Token {
-	pub fn new(token_type: TokenType, lexeme: String, literal: LiteralType, line: u32) -> Self {
-		Self { token_type, lexeme, literal, line }
+	pub fn new(
+		token_type: TokenType,
+		lexeme: String,
+		literal: LiteralType,
+		line: u32,
+		column: u32,
+		offset: u32,
+	) -> Self {
+		let length = lexeme.len() as u32;
+		Self { token_type, lexeme, literal, line, column, offset, length, source: 0 }
+	}
+
+	pub fn span(&self) -> Span {
+		Span { line: self.line, column: self.column, offset: self.offset, length: self.length }
+	}
+
+	/// Where the token is for error messages, like "line 12, col 8"
+	pub fn location(&self) -> String {
+		self.span().location()
+	}
+}
+
+impl Span {
+	/// A span for errors that only know which line they happened on
+	pub fn line(line: u32) -> Self {
+		Self { line, ..Self::default() }
+	}
+
+	/// Where the span is for error messages, like "line 12, col 8", or just "line 12" when the
+	/// column isn't known
+	pub fn location(&self) -> String {
+		match self.column {
+			0 => format!("line {}", self.line),
+			column => format!("line {}, col {}", self.line, column),
+		}
 	}
 }
 
@@ -126,6 +457,7 @@ pub enum TokenType {
 	Comma,
 	Dot,
 	Semicolon,
+	Colon,
 	Modulo,
 
 	// One or Two Character Tokens
@@ -155,15 +487,20 @@ pub enum TokenType {
 
 	// Keywords
 	And,
+	Break,
 	Class,
+	Continue,
 	Else,
 	False,
 	Funk,
 	For,
+	Global,
 	If,
+	Import,
 	Null,
 	Or,
 	Print,
+	Pure,
 	Return,
 	Super,
 	This,
@@ -171,6 +508,70 @@ pub enum TokenType {
 	Var,
 	While,
 
+	// Trivia, only produced when the scanner is asked to keep it
+	Comment,
+	Whitespace,
+
 	// End of File
 	Eof,
 }
+
+impl TokenType {
+	/// Comments and whitespace, which have no effect on how a program runs
+	pub fn is_trivia(&self) -> bool {
+		matches!(self, Self::Comment | Self::Whitespace)
+	}
+
+	/// Words the scanner turns into something other than an identifier
+	pub fn is_keyword(&self) -> bool {
+		matches!(
+			self,
+			Self::And |
+				Self::Break | Self::Class |
+				Self::Continue |
+				Self::Else | Self::False |
+				Self::Funk | Self::For |
+				Self::Global | Self::If |
+				Self::Import | Self::Null |
+				Self::Or | Self::Print |
+				Self::Pure | Self::Return |
+				Self::Super | Self::This |
+				Self::True | Self::Var |
+				Self::While
+		)
+	}
+
+	/// Keywords that can only begin a statement
+	pub fn starts_statement(&self) -> bool {
+		matches!(
+			self,
+			Self::Break |
+				Self::Class | Self::Continue |
+				Self::Funk | Self::For |
+				Self::Global | Self::If |
+				Self::Import | Self::Print |
+				Self::Pure | Self::Return |
+				Self::Var | Self::While
+		)
+	}
+
+	/// Unary, binary and assignment operators
+	pub fn is_operator(&self) -> bool {
+		matches!(
+			self,
+			Self::Bang |
+				Self::BangEqual |
+				Self::Equal | Self::EqualEqual |
+				Self::Greater |
+				Self::GreaterEqual |
+				Self::Less | Self::LessEqual |
+				Self::Plus | Self::PlusPlus |
+				Self::PlusEqual |
+				Self::Minus | Self::MinusMinus |
+				Self::MinusEqual |
+				Self::Slash | Self::SlashEqual |
+				Self::Star | Self::StarEqual |
+				Self::Modulo
+		)
+	}
+}
@@ -0,0 +1,37 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use jasn::{
+	error_handler::ErrorHandler,
+	interpreter::{Interpreter, InterpreterBuilder, Output, SnippetResult},
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+/// A builder for an interpreter that reads nothing and reports errors nowhere, run_snippet
+/// collects what a script prints and the errors it reports
+pub fn builder() -> InterpreterBuilder {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(Rc::clone(&output));
+	Interpreter::builder(Rc::new(RefCell::new(error_handler)))
+		.input(Rc::new(RefCell::new(io::empty())))
+		.output(output)
+}
+
+/// Run a script in a fresh interpreter with the default settings
+pub fn run(source: &str) -> SnippetResult {
+	builder().build().run_snippet(source)
+}
+
+/// Run a script that should work, giving the lines it printed
+pub fn lines(source: &str) -> Vec<String> {
+	let result = run(source);
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	result.output.lines().map(str::to_string).collect()
+}
+
+/// Run a script that should report errors, giving them
+pub fn errors(source: &str) -> Vec<String> {
+	let result = run(source);
+	assert!(!result.diagnostics.is_empty(), "expected an error, printed {:?}", result.output);
+	result.diagnostics
+}
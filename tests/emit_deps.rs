@@ -0,0 +1,46 @@
+use jasn::{deps::Dependencies, error_handler::ErrorHandler, interpreter::Output};
+use std::{
+	cell::RefCell,
+	fs, io,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+
+fn find(script: &str) -> Dependencies {
+	let output: Output = Rc::new(RefCell::new(io::sink()));
+	let error_handler = ErrorHandler::with_output(output);
+	Dependencies::find(&Path::new("examples/deps").join(script), &error_handler).unwrap()
+}
+
+fn fixture(path: &str) -> PathBuf {
+	fs::canonicalize(Path::new("examples/deps").join(path)).unwrap()
+}
+
+#[test]
+fn every_import_is_listed_once_in_order() {
+	let dependencies = find("main.jasn");
+	assert!(dependencies.diagnostics.is_empty(), "{:?}", dependencies.diagnostics);
+	assert_eq!(dependencies.files, [fixture("shapes.jasn"), fixture("util/numbers.jasn")]);
+}
+
+#[test]
+fn the_list_is_the_same_every_time() {
+	assert_eq!(find("main.jasn").files, find("main.jasn").files);
+}
+
+#[test]
+fn a_missing_file_names_the_import_line() {
+	let dependencies = find("missing.jasn");
+	assert_eq!(dependencies.files, [fixture("shapes.jasn"), fixture("util/numbers.jasn")]);
+	assert_eq!(dependencies.diagnostics.len(), 1, "{:?}", dependencies.diagnostics);
+	let expected = format!("{}:3: Can't import 'nowhere.jasn'", fixture("missing.jasn").display());
+	assert!(dependencies.diagnostics[0].starts_with(&expected), "{:?}", dependencies.diagnostics);
+}
+
+#[test]
+fn an_import_cycle_is_reported() {
+	let dependencies = find("cycle_a.jasn");
+	assert_eq!(dependencies.files, [fixture("cycle_b.jasn")]);
+	assert_eq!(dependencies.diagnostics.len(), 1, "{:?}", dependencies.diagnostics);
+	assert!(dependencies.diagnostics[0].contains("Import cycle"), "{:?}", dependencies.diagnostics);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+#[test]
+fn parse_errors_give_the_column() {
+	let errors = common::errors("var x = ;");
+	assert!(errors[0].starts_with("[line 1, col 9] Parse Error"), "{:?}", errors);
+}
+
+#[test]
+fn resolver_errors_give_the_column() {
+	let errors = common::errors("{ var a = 1;\n  var a = 2; }");
+	assert!(errors[0].starts_with("[line 2, col 7] Resolver Error"), "{:?}", errors);
+}
+
+#[test]
+fn runtime_errors_give_the_column_of_the_token_at_fault() {
+	let cases = [
+		("var s = \"a\";\nprint 1 - s;", "[line 2, col 9] Runtime Error: Right operand of '-'"),
+		("print missing;", "[line 1, col 7] Runtime Error: Undefined variable 'missing'"),
+		("var a = [1];\nprint a[3];", "[line 2, col 8] Runtime Error: Array index 3"),
+		("var n = null;\nn();", "[line 2, col 3] Runtime Error: Can only call"),
+	];
+	for (source, expected) in cases {
+		let errors = common::errors(source);
+		assert!(errors[0].starts_with(expected), "{}: {:?}", source, errors);
+	}
+}
+
+#[test]
+fn natives_report_the_closing_paren_of_their_call() {
+	let errors = common::errors("print 1;\nprint   len(5);");
+	assert!(errors[0].starts_with("[line 2, col 14] Runtime Error: len"), "{:?}", errors);
+}
@@ -0,0 +1,50 @@
+//! Runs every script in examples/ through the jasn binary and compares what it prints, errors
+//! included, with tests/expected/<name>.txt. Set JASN_BLESS=1 to write the expected output from
+//! the current build instead
+use std::{
+	env, fs,
+	path::Path,
+	process::{Command, Stdio},
+};
+
+// These print timings or never finish, so their output can't be pinned
+const SKIPPED: [&str; 5] =
+	["benchmark", "bulk_benchmark", "string_concat", "while_true", "guess_the_number"];
+
+#[test]
+fn examples_print_what_they_did_before() {
+	let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+	let bless = env::var_os("JASN_BLESS").is_some();
+	let mut scripts: Vec<_> = fs::read_dir(root.join("examples"))
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|extension| extension == "jasn"))
+		.collect();
+	scripts.sort();
+
+	let mut failed = Vec::new();
+	for script in scripts {
+		let name = script.file_stem().unwrap().to_string_lossy().into_owned();
+		if SKIPPED.contains(&name.as_str()) {
+			continue;
+		}
+		let output = Command::new(env!("CARGO_BIN_EXE_jasn"))
+			.arg(script.strip_prefix(root).unwrap())
+			.current_dir(root)
+			.stdin(Stdio::null())
+			.output()
+			.unwrap();
+		let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+		printed.push_str(&String::from_utf8_lossy(&output.stderr));
+		printed.push_str(&format!("exit code {}\n", output.status.code().unwrap_or(-1)));
+
+		let expected = root.join("tests/expected").join(format!("{}.txt", name));
+		if bless {
+			fs::write(&expected, &printed).unwrap();
+		} else if fs::read_to_string(&expected).ok().as_deref() != Some(printed.as_str()) {
+			eprintln!("{} printed:\n{}", name, printed);
+			failed.push(name);
+		}
+	}
+	assert!(failed.is_empty(), "these examples printed something new: {:?}", failed);
+}
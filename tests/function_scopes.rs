@@ -0,0 +1,17 @@
+mod common;
+
+use common::errors;
+
+#[test]
+fn a_repeated_parameter_leaves_the_enclosing_loop_alone() {
+	let errors = errors("while (yeah) {\n\tfunk g(a, a) {}\n\tbreak;\n}\nprint \"after\";");
+	assert_eq!(errors.len(), 1, "{:?}", errors);
+	assert!(errors[0].contains("There's already a variable with this name"), "{:?}", errors);
+}
+
+#[test]
+fn a_repeated_parameter_leaves_the_pure_scope_behind() {
+	let errors = errors("pure funk f(a, a) { return a; }\nprint 1;\nvar x = f(1, 2);");
+	assert_eq!(errors.len(), 1, "{:?}", errors);
+	assert!(!errors[0].contains("pure"), "{:?}", errors);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{errors, lines};
+
+#[test]
+fn unique_keeps_the_first_of_equal_values() {
+	assert_eq!(lines("print unique([3, 1, 3, [1], 2, [1], 1]);"), ["[3, 1, [1], 2]"]);
+	assert_eq!(lines("print unique([]);"), ["[]"]);
+}
+
+#[test]
+fn unique_treats_zero_and_negative_zero_as_equal() {
+	assert_eq!(
+		lines("print 0 == -0; print unique([0, -0]); print unique([[-0], [0]]);"),
+		["yeah", "[0]", "[[-0]]"]
+	);
+}
+
+#[test]
+fn count_of_counts_equal_elements() {
+	assert_eq!(
+		lines("print count_of([1, 2, 1, [1], 1], 1); print count_of([[1], 1], [1]);"),
+		["3", "1"]
+	);
+}
+
+#[test]
+fn group_by_groups_instances_by_a_field_in_encounter_order() {
+	let output = lines(
+		"class Pet { init(name, kind) { this.name = name; this.kind = kind; } }
+		var pets = [Pet(\"rex\", \"dog\"), Pet(\"tom\", \"cat\"), Pet(\"fido\", \"dog\"),
+			Pet(\"kit\", \"cat\"), Pet(\"polly\", \"bird\"), Pet(\"spot\", \"dog\")];
+		funk kind(pet) { return pet.kind; }
+		funk name(pet) { return pet.name; }
+		var groups = group_by(pets, kind);
+		print keys(groups);
+		print [len(groups.dog), len(groups.cat), len(groups.bird)];
+		print map(groups.dog, name);
+		print map(groups.cat, name);",
+	);
+	assert_eq!(output, ["[bird, cat, dog]", "[3, 2, 1]", "[rex, fido, spot]", "[tom, kit]"]);
+}
+
+#[test]
+fn group_by_keys_are_the_string_form_of_the_key() {
+	let output = lines(
+		"funk parity(n) { return n % 2 == 0; }
+		print group_by([1, 2, 3, 4, 5], parity);",
+	);
+	assert_eq!(output, ["{nah: [1, 3, 5], yeah: [2, 4]}"]);
+}
+
+#[test]
+fn group_by_needs_a_key_function() {
+	let errors = errors("group_by([1], 1);");
+	assert!(errors[0].contains("group_by expects a function"), "{:?}", errors);
+}
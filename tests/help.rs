@@ -0,0 +1,38 @@
+mod common;
+
+#[test]
+fn help_describes_one_native() {
+	let lines = common::lines("help(\"len\");");
+	assert_eq!(lines[0], "len(value)");
+	assert!(lines[1].contains("Count the characters of a string"), "{:?}", lines);
+}
+
+#[test]
+fn help_lists_every_native() {
+	let interpreter = common::builder().build();
+	let listing = common::lines("help();").join("\n");
+	for native in interpreter.natives() {
+		assert!(listing.contains(&native.name), "help() doesn't list {}", native.name);
+	}
+}
+
+#[test]
+fn every_native_is_described() {
+	let interpreter = common::builder().build();
+	for native in interpreter.natives() {
+		assert!(!native.docs.category.is_empty(), "{} has no category", native.name);
+		assert!(
+			native.docs.signature.starts_with(&format!("{}(", native.name)),
+			"{} has the signature {:?}",
+			native.name,
+			native.docs.signature
+		);
+		assert!(!native.docs.description.is_empty(), "{} has no description", native.name);
+	}
+}
+
+#[test]
+fn help_for_an_unknown_name_is_an_error() {
+	let errors = common::errors("help(\"nope\");");
+	assert!(errors[0].contains("help doesn't know a native called 'nope'"), "{:?}", errors);
+}
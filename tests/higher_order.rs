@@ -0,0 +1,28 @@
+mod common;
+
+use common::{errors, lines};
+
+#[test]
+fn natives_taking_a_range_of_arguments_can_be_passed_as_callbacks() {
+	assert_eq!(lines("print reduce([3, 1, 2], 0, max); print map([[4, 9]], min);"), ["3", "[4]"]);
+	assert_eq!(lines("print reduce([1, 2, 3], 0, min);"), ["0"]);
+}
+
+#[test]
+fn a_callback_taking_the_wrong_number_of_arguments_is_an_error() {
+	let cases = [
+		(
+			"funk add(a, b) { return a + b; } map([1], add);",
+			"map expects a function taking 1 argument(s) but it takes 2",
+		),
+		("reduce([1], 0, sqrt);", "reduce expects a function taking 2 argument(s) but it takes 1"),
+		(
+			"reduce([1], 0, help);",
+			"reduce expects a function taking 2 argument(s) but it takes 0 to 1",
+		),
+	];
+	for (source, message) in cases {
+		let errors = errors(source);
+		assert!(errors[0].contains(message), "{}: {:?}", source, errors);
+	}
+}
@@ -0,0 +1,28 @@
+mod common;
+
+const SIDE_EFFECT: &str = "var ran = 0; funk touch() { ran = ran + 1; return yeah; }";
+
+#[test]
+fn and_skips_its_right_side_when_the_left_is_falsey() {
+	let lines = common::lines(&format!("{} print nah and touch(); print ran;", SIDE_EFFECT));
+	assert_eq!(lines, ["nah", "0"]);
+}
+
+#[test]
+fn or_skips_its_right_side_when_the_left_is_truthy() {
+	let lines = common::lines(&format!("{} print yeah || touch(); print ran;", SIDE_EFFECT));
+	assert_eq!(lines, ["yeah", "0"]);
+}
+
+#[test]
+fn the_right_side_runs_when_it_decides_the_result() {
+	let lines = common::lines(&format!("{} print yeah && touch(); print ran;", SIDE_EFFECT));
+	assert_eq!(lines, ["yeah", "1"]);
+}
+
+#[test]
+fn logical_operators_bind_looser_than_equality() {
+	// Read as (1 == 2) or (3 == 3), and `and` before `or`
+	let lines = common::lines("print 1 == 2 or 3 == 3; print yeah or nah and nah;");
+	assert_eq!(lines, ["yeah", "yeah"]);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use jasn::interpreter::MissingField;
+
+const POINT: &str = "class Point { init() { this.x = 1; } } var p = Point();";
+
+fn run_with(missing_field: MissingField, source: &str) -> jasn::interpreter::SnippetResult {
+	common::builder()
+		.missing_field(missing_field)
+		.build()
+		.run_snippet(&format!("{} {}", POINT, source))
+}
+
+#[test]
+fn missing_field_is_an_error_by_default() {
+	let result = run_with(MissingField::Error, "print p.y;");
+	assert_eq!(result.output, "");
+	assert_eq!(result.diagnostics.len(), 1, "{:?}", result.diagnostics);
+	assert!(result.diagnostics[0].contains("Undefined property 'y'"), "{:?}", result.diagnostics);
+}
+
+#[test]
+fn missing_field_can_read_as_null() {
+	let result = run_with(MissingField::Null, "print p.y; print p.x;");
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	assert_eq!(result.output, "null\n1\n");
+}
+
+#[test]
+fn get_or_never_errors_under_either_policy() {
+	for policy in [MissingField::Error, MissingField::Null] {
+		let result = run_with(policy, "print get_or(p, \"x\", 5); print get_or(p, \"y\", 5);");
+		assert!(result.diagnostics.is_empty(), "{:?}: {:?}", policy, result.diagnostics);
+		assert_eq!(result.output, "1\n5\n", "{:?}", policy);
+	}
+}
+
+#[test]
+fn get_or_needs_a_field_name() {
+	let errors = common::errors(&format!("{} get_or(p, 1, 5);", POINT));
+	assert!(errors[0].contains("get_or expects a field name but found 1"), "{:?}", errors);
+}
+
+#[test]
+fn missing_key_follows_the_policy() {
+	let result = run_with(MissingField::Error, "var m = {\"a\": 1}; print m.b;");
+	assert!(
+		result.diagnostics[0].contains("There's no key 'b' in the map."),
+		"{:?}",
+		result.diagnostics
+	);
+	let result = run_with(MissingField::Null, "var m = {\"a\": 1}; print m.b; print m[\"b\"];");
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	assert_eq!(result.output, "null\nnull\n");
+}
+
+#[test]
+fn get_or_reads_maps_like_instances() {
+	for policy in [MissingField::Error, MissingField::Null] {
+		let result = run_with(
+			policy,
+			"var m = {\"a\": 1}; print get_or(m, \"a\", 5); print get_or(m, \"b\", 5); print m;",
+		);
+		assert!(result.diagnostics.is_empty(), "{:?}: {:?}", policy, result.diagnostics);
+		assert_eq!(result.output, "1\n5\n{a: 1}\n", "{:?}", policy);
+	}
+	let errors = common::errors("get_or([1], \"a\", 5);");
+	assert!(
+		errors[0].contains("get_or expects an instance or a map but found [1]"),
+		"{:?}",
+		errors
+	);
+}
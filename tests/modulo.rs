@@ -0,0 +1,17 @@
+mod common;
+
+#[test]
+fn modulo_binds_like_multiplication() {
+	assert_eq!(common::lines("print 10 % 3; print 1 + 10 % 3; print 2 * 7 % 4;"), ["1", "2", "2"]);
+}
+
+#[test]
+fn modulo_keeps_the_sign_of_the_left_side() {
+	assert_eq!(common::lines("print -7 % 3; print 7.5 % 2;"), ["-1", "1.5"]);
+}
+
+#[test]
+fn modulo_by_zero_is_an_error() {
+	let errors = common::errors("print 5 % 0;");
+	assert!(errors[0].contains("Modulo by zero."), "{:?}", errors);
+}
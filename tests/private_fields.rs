@@ -0,0 +1,42 @@
+mod common;
+
+use jasn::interpreter::{PrivateAccess, SnippetResult};
+
+const POINT: &str =
+	"class Point { init() { this._x = 1; } x() { return this._x; } } var p = Point();";
+
+fn run(private_access: PrivateAccess, source: &str) -> SnippetResult {
+	common::builder()
+		.private_access(private_access)
+		.build()
+		.run_snippet(&format!("{}\n{}", POINT, source))
+}
+
+#[test]
+fn inside_the_class_is_allowed() {
+	let result = run(PrivateAccess::Error, "print p.x();");
+	assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+	assert_eq!(result.output, "1\n");
+}
+
+#[test]
+fn outside_the_class_warns_by_default() {
+	let result = run(PrivateAccess::Warn, "print p._x;");
+	assert_eq!(result.output, "1\n");
+	assert_eq!(result.diagnostics.len(), 1, "{:?}", result.diagnostics);
+	assert!(result.diagnostics[0].contains("'_x' is private to Point"), "{:?}", result.diagnostics);
+}
+
+#[test]
+fn strict_mode_rejects_every_kind_of_write() {
+	for write in ["p._x = 9;", "p._x += 9;", "p._x++;", "var n; p._x, n = 9, 9;"] {
+		let result = run(PrivateAccess::Error, &format!("{}\nprint p.x();", write));
+		assert_eq!(result.output, "", "{}", write);
+		assert!(
+			result.diagnostics.iter().any(|d| d.contains("'_x' is private to Point")),
+			"{}: {:?}",
+			write,
+			result.diagnostics
+		);
+	}
+}
@@ -0,0 +1,73 @@
+//! Snapshots of --resolve-dump, which lists where the resolver bound each variable reference
+use jasn::{interpreter::Output, Options, Session};
+use std::{cell::RefCell, fs, io, path::Path, rc::Rc};
+
+fn dump(source: &str) -> String {
+	let options = Options { resolve_dump: true, ..Default::default() };
+	let output = Rc::new(RefCell::new(Vec::new()));
+	let mut session = Session::with_io(
+		&options,
+		Rc::new(RefCell::new(io::empty())),
+		Rc::clone(&output) as Output,
+	);
+	session.run_source(source).unwrap();
+	let printed = output.borrow().clone();
+	String::from_utf8(printed).unwrap()
+}
+
+#[test]
+fn nested_scopes_fixture() {
+	let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/nested_scopes.jasn");
+	let source = fs::read_to_string(fixture).unwrap();
+	assert_eq!(
+		dump(&source),
+		"[line 4] a -> global
+[line 4] b -> depth 0
+[line 7] a -> depth 0
+[line 7] c -> depth 1
+[line 10] b -> depth 1
+[line 10] c -> depth 1
+[line 12] inner -> depth 0
+[line 14] outer -> global
+"
+	);
+}
+
+// A for loop's increment is resolved after its body, as that's when it runs
+#[test]
+fn methods_loops_and_assignments() {
+	let source = "class Counter {
+	init() {
+		this.count = 0;
+	}
+	add(n) {
+		this.count = this.count + n;
+		return this;
+	}
+}
+var counter = Counter();
+for (var i = 0; i < 3; i = i + 1) {
+	counter.add(i);
+}
+";
+	assert_eq!(
+		dump(source),
+		"[line 3] this -> depth 1
+[line 6] this -> depth 1
+[line 6] this -> depth 1
+[line 6] n -> depth 0
+[line 7] this -> depth 1
+[line 10] Counter -> global
+[line 11] i -> depth 0
+[line 12] counter -> global
+[line 12] i -> depth 1
+[line 11] i -> depth 0
+[line 11] i -> depth 0
+"
+	);
+}
+
+#[test]
+fn nothing_runs() {
+	assert_eq!(dump("var x = 1;\nprint x;\n"), "[line 2] x -> global\n");
+}
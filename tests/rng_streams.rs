@@ -0,0 +1,45 @@
+mod common;
+
+const DRAWS: &str = "funk draws(r) {
+	var out = [];
+	for (var i = 0; i < 5; i++) { push(out, rng_int(r, 1, 100)); }
+	return out;
+}";
+
+#[test]
+fn streams_with_the_same_seed_agree() {
+	let lines = common::lines(&format!(
+		"{} print draws(rng_new(42)) == draws(rng_new(42)); print draws(rng_new(42)) == draws(rng_new(7));",
+		DRAWS
+	));
+	assert_eq!(lines, ["yeah", "nah"]);
+}
+
+#[test]
+fn drawing_from_one_stream_leaves_the_other_alone() {
+	let lines = common::lines(
+		"var a = rng_new(1); var b = rng_new(1);
+		var first = rng_next(a); rng_next(a); rng_next(a);
+		print rng_next(b) == first;",
+	);
+	assert_eq!(lines, ["yeah"]);
+}
+
+#[test]
+fn shuffling_with_the_same_seed_gives_the_same_order() {
+	let lines = common::lines(
+		"var x = [1, 2, 3, 4, 5, 6, 7, 8]; var y = [1, 2, 3, 4, 5, 6, 7, 8];
+		rng_shuffle(rng_new(3), x); rng_shuffle(rng_new(3), y);
+		print x == y; print len(x);",
+	);
+	assert_eq!(lines, ["yeah", "8"]);
+}
+
+#[test]
+fn the_sequence_is_the_same_on_every_platform() {
+	// These change only when the algorithm does, which the docs have to say
+	let lines = common::lines(
+		"var r = rng_new(42); print rng_next(r); print rng_int(r, 1, 100); print rng_int(r, 1, 100);",
+	);
+	assert_eq!(lines, ["0.16153508438882802", "82", "16"]);
+}
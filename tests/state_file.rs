@@ -0,0 +1,68 @@
+//! Runs the jasn binary several times against one state file, checking that global data values
+//! carry over from one run to the next and functions don't
+use std::{env, fs, path::Path, process::Command};
+
+const COUNTER: &str = "if (index_of(globals(), \"total\") == -1) {
+	global total = 0;
+	global seen = {};
+}
+total = total + 1;
+seen[to_string(total)] = [total, \"run\"];
+funk helper() {}
+print total;
+print seen;
+";
+
+fn run(script: &Path, state: &Path) -> (String, String) {
+	let output = Command::new(env!("CARGO_BIN_EXE_jasn"))
+		.arg("--state")
+		.arg(state)
+		.arg(script)
+		.output()
+		.unwrap();
+	assert!(output.status.success(), "{:?}", output);
+	(
+		String::from_utf8_lossy(&output.stdout).into_owned(),
+		String::from_utf8_lossy(&output.stderr).into_owned(),
+	)
+}
+
+#[test]
+fn globals_accumulate_across_runs() {
+	let directory = env::temp_dir().join(format!("jasn_state_test_{}", std::process::id()));
+	fs::create_dir_all(&directory).unwrap();
+	let script = directory.join("counter.jasn");
+	let state = directory.join("counter.state");
+	fs::write(&script, COUNTER).unwrap();
+
+	let (first, warnings) = run(&script, &state);
+	assert!(first.ends_with("1\n{1: [1, run]}\n"), "{}", first);
+	assert!(warnings.contains("helper"), "the skipped function should be named: {}", warnings);
+	let (second, _) = run(&script, &state);
+	assert!(second.ends_with("2\n{1: [1, run], 2: [2, run]}\n"), "{}", second);
+	let (third, _) = run(&script, &state);
+	assert!(third.contains("\n3\n"), "{}", third);
+	fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn a_corrupt_state_file_is_an_error() {
+	let directory = env::temp_dir().join(format!("jasn_corrupt_state_{}", std::process::id()));
+	fs::create_dir_all(&directory).unwrap();
+	let script = directory.join("empty.jasn");
+	let state = directory.join("corrupt.state");
+	fs::write(&script, "print \"ran\";").unwrap();
+	fs::write(&state, "JASN-STATE 999\nx null\n").unwrap();
+
+	let output = Command::new(env!("CARGO_BIN_EXE_jasn"))
+		.arg("--state")
+		.arg(&state)
+		.arg(&script)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(!output.status.success());
+	assert!(stderr.contains("version"), "{}", stderr);
+	assert!(!String::from_utf8_lossy(&output.stdout).contains("ran"));
+	fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,17 @@
+mod common;
+
+const SCRIPT: &str = "print \"one\";\nprint missing;\nprint \"three\";";
+
+#[test]
+fn a_runtime_error_stops_the_script() {
+	let result = common::run(SCRIPT);
+	assert_eq!(result.output, "one\n");
+	assert_eq!(result.diagnostics.len(), 1, "{:?}", result.diagnostics);
+}
+
+#[test]
+fn continue_on_error_runs_the_rest() {
+	let result = common::builder().continue_on_error(true).build().run_snippet(SCRIPT);
+	assert_eq!(result.output, "one\nthree\n");
+	assert_eq!(result.diagnostics.len(), 1, "{:?}", result.diagnostics);
+}
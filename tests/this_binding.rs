@@ -0,0 +1,28 @@
+mod common;
+
+const COUNTER: &str = "class Counter {
+	init() { this.count = 0; }
+	bump() { this.count = this.count + 1; return this.count; }
+}";
+
+#[test]
+fn methods_can_set_fields_through_this() {
+	let lines = common::lines(&format!("{} var c = Counter(); c.bump(); print c.count;", COUNTER));
+	assert_eq!(lines, ["1"]);
+}
+
+#[test]
+fn a_stored_method_stays_bound_to_its_instance() {
+	let lines = common::lines(&format!(
+		"{} var a = Counter(); var b = Counter(); var bump = a.bump; bump(); bump(); b.bump();
+		print a.count; print b.count;",
+		COUNTER
+	));
+	assert_eq!(lines, ["2", "1"]);
+}
+
+#[test]
+fn this_outside_a_class_is_an_error() {
+	let errors = common::errors("print this;");
+	assert!(errors[0].contains("Resolver Error"), "{:?}", errors);
+}
@@ -0,0 +1,25 @@
+mod common;
+
+#[test]
+fn try_call_gives_the_result_when_the_call_works() {
+	let lines = common::lines("funk half(n) { return n / 2; } print try_call(half, [8]);");
+	assert_eq!(lines, ["[yeah, 4]"]);
+}
+
+#[test]
+fn try_call_captures_the_error_without_reporting_it() {
+	let lines = common::lines("funk broken(n) { return n / 0; } print try_call(broken, [8]);");
+	assert_eq!(lines, ["[nah, Division by zero.]"]);
+}
+
+#[test]
+fn try_call_treats_a_function_without_return_as_success() {
+	let lines = common::lines("funk nothing() {} print try_call(nothing, []);");
+	assert_eq!(lines, ["[yeah, null]"]);
+}
+
+#[test]
+fn try_call_still_checks_what_it_is_given() {
+	let errors = common::errors("try_call(5, []);");
+	assert!(errors[0].contains("try_call"), "{:?}", errors);
+}